@@ -0,0 +1,188 @@
+//  Chunk Shader - Merged chunk meshes. Everything is already in world space and the colour comes from the vertex,
+//  so there's no instance data. The only material is the stud normal map, worn by flat tops in place of real studs.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+@group(1) @binding(0)
+var<uniform> light: Light;
+
+struct Fog {
+    //  The sky colour, which fog fades towards
+    color: vec3<f32>,
+    //  0 off, 1 linear, 2 exponential, 3 height
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    height: f32,
+    height_falloff: f32,
+}
+@group(1) @binding(1)
+var<uniform> fog: Fog;
+
+fn fog_amount(world_position: vec3<f32>, view_position: vec3<f32>) -> f32 {
+    let d = distance(world_position, view_position);
+    if (fog.mode == 1u) {
+        return clamp((d - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+    }
+    if (fog.mode == 2u) {
+        return 1.0 - exp(-fog.density * max(d - fog.start, 0.0));
+    }
+    if (fog.mode == 3u) {
+        //  Density falls off exponentially with height, integrated along the ray from the camera
+        let base = fog.density * exp(-fog.height_falloff * (view_position.y - fog.height));
+        let k = fog.height_falloff * (world_position.y - view_position.y);
+        var along = 1.0;
+        if (abs(k) > 0.0001) {
+            along = (1.0 - exp(-k)) / k;
+        }
+        return clamp(1.0 - exp(-base * along * max(d - fog.start, 0.0)), 0.0, 1.0);
+    }
+    return 0.0;
+}
+
+fn apply_fog(color: vec3<f32>, world_position: vec3<f32>, view_position: vec3<f32>) -> vec3<f32> {
+    return mix(color, fog.color, fog_amount(world_position, view_position));
+}
+
+@group(2) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(2) @binding(3)
+var s_normal: sampler;
+
+//  How much ambient light reaches each pixel, from the SSAO pass. 1x1 and white when SSAO is off.
+@group(3) @binding(0)
+var t_ao: texture_2d<f32>;
+
+fn ambient_occlusion(position: vec4<f32>) -> f32 {
+    let max_coords = vec2<i32>(textureDimensions(t_ao)) - 1;
+    return textureLoad(t_ao, min(vec2<i32>(position.xy), max_coords), 0).r;
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) tex_coords: vec2<f32>,
+    @location(4) stud_normal: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) tex_coords: vec2<f32>,
+    @location(4) stud_normal: f32,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.world_position = model.position;
+    out.world_normal = model.normal;
+    out.color = model.color;
+    out.tex_coords = model.tex_coords;
+    out.stud_normal = model.stud_normal;
+    return out;
+}
+
+//  Plastic, so a fairly tight untinted highlight
+let SPECULAR: f32 = 0.5;
+let SHININESS: f32 = 32.0;
+
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+    //  Flat tops face +y with u along +x and v along +z, and like the model textures the map's green points along -v
+    let tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    let mapped_normal = vec3<f32>(tangent_normal.x, tangent_normal.z, -tangent_normal.y);
+    return normalize(mix(in.world_normal, mapped_normal, in.stud_normal));
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let ambient_color = light.color * 0.1 * ambient_occlusion(in.clip_position);
+    let normal = surface_normal(in);
+
+    let light_dir = normalize(light.position - in.world_position);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let half_dir = normalize(view_dir + light_dir);
+
+    let diffuse_color = light.color * max(dot(normal, light_dir), 0.0);
+    let specular_color = light.color * pow(max(dot(normal, half_dir), 0.0), SHININESS) * SPECULAR;
+
+    let result = (ambient_color + diffuse_color) * in.color.rgb + specular_color;
+    return vec4<f32>(apply_fog(result, in.world_position, camera.view_pos.xyz), in.color.a);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) material: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+}
+
+//  The deferred path's geometry pass, laid out like shader.wgsl's. Chunks are always opaque.
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOutput {
+    let normal = surface_normal(in);
+    var out: GBufferOutput;
+    out.albedo = in.color;
+    out.normal = vec4<f32>(normal * 0.5 + 0.5, 1.0);
+    out.material = vec4<f32>(vec3<f32>(SPECULAR), SHININESS / 256.0);
+    out.emissive = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    return out;
+}
+
+//  Normal prepass for SSAO. The stud normal map is left out, flat tops have nothing in the depth buffer to match it.
+@fragment
+fn fs_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+}
+
+//  Debug views, see engine/debug_view.rs
+
+//  The camera's far plane, the depth view goes from black at the camera to white there
+let DEPTH_VIEW_RANGE: f32 = 100.0;
+let WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.2, 1.0, 0.4);
+
+//  An 8x8 checker per UV tile, tinted red along u and green along v so flipped or rotated UVs stand out
+fn uv_checker(uv: vec2<f32>) -> vec3<f32> {
+    let cells = floor(uv * 8.0);
+    let checker = select(0.2, 0.9, fract((cells.x + cells.y) * 0.5) > 0.25);
+    return mix(vec3<f32>(checker), vec3<f32>(fract(uv), 0.0), 0.5);
+}
+
+@fragment
+fn fs_debug_uv(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(uv_checker(in.tex_coords), 1.0);
+}
+
+@fragment
+fn fs_debug_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(surface_normal(in) * 0.5 + 0.5, 1.0);
+}
+
+@fragment
+fn fs_debug_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(vec3<f32>(distance(camera.view_pos.xyz, in.world_position) / DEPTH_VIEW_RANGE), 1.0);
+}
+
+//  Drawn with PolygonMode::Line
+@fragment
+fn fs_debug_wireframe(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
@@ -0,0 +1,191 @@
+//  GPU Culling - One thread per instance. Instances inside the frustum (and not hidden behind last frame's depth)
+//  pick a LOD level and are copied into that level's region of the culled buffer, counted for the indirect draw.
+
+struct Params {
+    //  Left, right, bottom, top, near, far as (normal, distance)
+    frustum: array<vec4<f32>, 6>,
+    //  The view projection last frame's depth was rendered with, used to look up the Hi-Z buffer
+    hiz_view_proj: mat4x4<f32>,
+    hiz_size: vec2<f32>,
+    instance_count: u32,
+    use_hiz: u32,
+    hiz_mip_count: u32,
+    arg_count: u32,
+    //  Projected sizes in pixels where the flat top and impostor levels take over
+    lod_boundaries: vec2<f32>,
+    eye: vec3<f32>,
+    pixels_per_unit: f32,
+    lod_hysteresis: f32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+//  One per LOD level of every brick type, level `l` of type `t` is at t * LOD_COUNT + l
+struct BrickType {
+    //  Model space bounding sphere, xyz centre and w radius
+    sphere: vec4<f32>,
+    //  Where this type's instances start in the culled buffer
+    instance_offset: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+//  Matches InstanceRaw, a mat4 + mat3 + vec4 of tightly packed floats and a u32 of flags. Held as raw words so the
+//  flags are copied bit for bit.
+let INSTANCE_WORDS: u32 = 30u;
+let LOD_COUNT: u32 = 3u;
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> instances: array<u32>;
+@group(0) @binding(2)
+var<storage, read> instance_types: array<u32>;
+@group(0) @binding(3)
+var<storage, read> brick_types: array<BrickType>;
+@group(0) @binding(4)
+var<storage, read_write> culled: array<u32>;
+@group(0) @binding(5)
+var<storage, read_write> counts: array<atomic<u32>>;
+@group(0) @binding(6)
+var<storage, read_write> args: array<DrawArgs>;
+//  Which brick type each draw belongs to
+@group(0) @binding(7)
+var<storage, read> arg_types: array<u32>;
+//  The level each instance was drawn at last frame, for the hysteresis
+@group(0) @binding(8)
+var<storage, read_write> lod_state: array<u32>;
+
+@group(1) @binding(0)
+var hiz: texture_2d<f32>;
+
+fn load_hiz(coords: vec2<i32>, level: i32) -> f32 {
+    return textureLoad(hiz, coords, level).r;
+}
+
+//  Projects the sphere's bounding box with last frame's camera and compares its nearest depth against the farthest depth
+//  in the Hi-Z texels it covers. A mip is picked where the box covers at most 2x2 texels.
+fn occluded(center: vec3<f32>, radius: f32) -> bool {
+    var min_uv = vec2<f32>(1.0, 1.0);
+    var max_uv = vec2<f32>(0.0, 0.0);
+    var nearest = 1.0;
+    for (var i = 0u; i < 8u; i = i + 1u) {
+        let offset = vec3<f32>(
+            select(-radius, radius, (i & 1u) != 0u),
+            select(-radius, radius, (i & 2u) != 0u),
+            select(-radius, radius, (i & 4u) != 0u),
+        );
+        let clip = params.hiz_view_proj * vec4<f32>(center + offset, 1.0);
+        //  Crosses the camera plane, can't say anything about it
+        if (clip.w <= 0.0) {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        min_uv = min(min_uv, uv);
+        max_uv = max(max_uv, uv);
+        nearest = min(nearest, ndc.z);
+    }
+    if (nearest <= 0.0) {
+        return false;
+    }
+    min_uv = clamp(min_uv, vec2<f32>(0.0), vec2<f32>(1.0));
+    max_uv = clamp(max_uv, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    let size = (max_uv - min_uv) * params.hiz_size;
+    let level = i32(clamp(ceil(log2(max(max(size.x, size.y), 1.0))), 0.0, f32(params.hiz_mip_count - 1u)));
+    let dims = textureDimensions(hiz, level);
+    let lo = clamp(vec2<i32>(min_uv * vec2<f32>(dims)), vec2<i32>(0), dims - 1);
+    let hi = clamp(vec2<i32>(max_uv * vec2<f32>(dims)), vec2<i32>(0), dims - 1);
+
+    let farthest = max(
+        max(load_hiz(lo, level), load_hiz(hi, level)),
+        max(load_hiz(vec2<i32>(lo.x, hi.y), level), load_hiz(vec2<i32>(hi.x, lo.y), level)),
+    );
+    return nearest > farthest;
+}
+
+//  Same as LodSettings::select, moves off the current level only once it's clearly past a boundary
+fn select_lod(current: u32, pixels: f32) -> u32 {
+    var lod = min(current, LOD_COUNT - 1u);
+    loop {
+        if (lod == 0u || pixels <= params.lod_boundaries[lod - 1u] * (1.0 + params.lod_hysteresis)) {
+            break;
+        }
+        lod = lod - 1u;
+    }
+    loop {
+        if (lod >= LOD_COUNT - 1u || pixels >= params.lod_boundaries[lod] * (1.0 - params.lod_hysteresis)) {
+            break;
+        }
+        lod = lod + 1u;
+    }
+    return lod;
+}
+
+fn instance_column(word: u32) -> vec4<f32> {
+    return bitcast<vec4<f32>>(vec4<u32>(instances[word], instances[word + 1u], instances[word + 2u], instances[word + 3u]));
+}
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.instance_count) {
+        return;
+    }
+
+    let base = index * INSTANCE_WORDS;
+    let model = mat4x4<f32>(
+        instance_column(base),
+        instance_column(base + 4u),
+        instance_column(base + 8u),
+        instance_column(base + 12u),
+    );
+    let base_type = instance_types[index] * LOD_COUNT;
+    let sphere = brick_types[base_type].sphere;
+    let center = (model * vec4<f32>(sphere.xyz, 1.0)).xyz;
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+    let radius = sphere.w * scale;
+
+    for (var i = 0; i < 6; i = i + 1) {
+        let plane = params.frustum[i];
+        if (dot(plane.xyz, center) + plane.w < -radius) {
+            return;
+        }
+    }
+    if (params.use_hiz != 0u && occluded(center, radius)) {
+        return;
+    }
+
+    let distance = length(center - params.eye);
+    let pixels = select(2.0 * radius * params.pixels_per_unit / distance, 3.4e38, distance <= radius);
+    let lod = select_lod(lod_state[index], pixels);
+    lod_state[index] = lod;
+
+    let brick_type = base_type + lod;
+    let slot = atomicAdd(&counts[brick_type], 1u);
+    let out = (brick_types[brick_type].instance_offset + slot) * INSTANCE_WORDS;
+    for (var i = 0u; i < INSTANCE_WORDS; i = i + 1u) {
+        culled[out + i] = instances[base + i];
+    }
+}
+
+//  Every mesh of a brick type draws the same instances, so they all get the type's count
+@compute @workgroup_size(64)
+fn write_args(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.arg_count) {
+        return;
+    }
+    args[index].instance_count = atomicLoad(&counts[arg_types[index]]);
+}
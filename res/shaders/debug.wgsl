@@ -0,0 +1,61 @@
+//  Debug Meshes - Geometry built on the CPU from a model's meshes, drawn for every instance. Tangent frames are
+//  lines coloured per axis, and wireframes are triangles carrying barycentric coordinates for adapters that can't
+//  draw with PolygonMode::Line.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct DebugVertex {
+    @location(0) position: vec3<f32>,
+    //  The colour for lines, barycentric coordinates for wireframes
+    @location(1) data: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) data: vec3<f32>,
+}
+
+let WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.2, 1.0, 0.4);
+//  In pixels
+let WIREFRAME_WIDTH: f32 = 1.0;
+
+@vertex
+fn vs_main(vertex: DebugVertex, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(vertex.position, 1.0);
+    out.data = vertex.data;
+    return out;
+}
+
+@fragment
+fn fs_lines(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.data, 1.0);
+}
+
+//  Keeps only what's within a pixel or so of an edge, where one of the coordinates is close to 0
+@fragment
+fn fs_wireframe(in: VertexOutput) -> @location(0) vec4<f32> {
+    let edge = in.data / max(fwidth(in.data), vec3<f32>(0.0001));
+    if (min(edge.x, min(edge.y, edge.z)) > WIREFRAME_WIDTH) {
+        discard;
+    }
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
@@ -0,0 +1,133 @@
+//  Deferred Lighting - Lights the G-buffer the geometry pass drew, once per pixel for every light, with the same
+//  Blinn-Phong model as the forward shaders. Pixels nothing was drawn to are discarded so the clear colour shows.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    //  How far the light reaches, 0 for everywhere without falling off
+    range: f32,
+    color: vec3<f32>,
+}
+
+struct Lighting {
+    inv_view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+    light_count: u32,
+    lights: array<PointLight, 64>,
+}
+
+@group(0) @binding(0)
+var t_albedo: texture_2d<f32>;
+@group(0) @binding(1)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(2)
+var t_material: texture_2d<f32>;
+@group(0) @binding(3)
+var t_emissive: texture_2d<f32>;
+//  Bound as plain floats rather than texture_depth_2d, the GL backend can't load from depth textures
+@group(0) @binding(4)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(5)
+var<uniform> lighting: Lighting;
+
+//  How much ambient light reaches each pixel, from the SSAO pass. 1x1 and white when SSAO is off.
+@group(1) @binding(0)
+var t_ao: texture_2d<f32>;
+
+fn ambient_occlusion(p: vec2<i32>) -> f32 {
+    let max_coords = vec2<i32>(textureDimensions(t_ao)) - 1;
+    return textureLoad(t_ao, min(p, max_coords), 0).r;
+}
+
+struct Fog {
+    //  The sky colour, which fog fades towards
+    color: vec3<f32>,
+    //  0 off, 1 linear, 2 exponential, 3 height
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    height: f32,
+    height_falloff: f32,
+}
+@group(2) @binding(1)
+var<uniform> fog: Fog;
+
+fn fog_amount(world_position: vec3<f32>, view_position: vec3<f32>) -> f32 {
+    let d = distance(world_position, view_position);
+    if (fog.mode == 1u) {
+        return clamp((d - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+    }
+    if (fog.mode == 2u) {
+        return 1.0 - exp(-fog.density * max(d - fog.start, 0.0));
+    }
+    if (fog.mode == 3u) {
+        //  Density falls off exponentially with height, integrated along the ray from the camera
+        let base = fog.density * exp(-fog.height_falloff * (view_position.y - fog.height));
+        let k = fog.height_falloff * (world_position.y - view_position.y);
+        var along = 1.0;
+        if (abs(k) > 0.0001) {
+            along = (1.0 - exp(-k)) / k;
+        }
+        return clamp(1.0 - exp(-base * along * max(d - fog.start, 0.0)), 0.0, 1.0);
+    }
+    return 0.0;
+}
+
+fn apply_fog(color: vec3<f32>, world_position: vec3<f32>, view_position: vec3<f32>) -> vec3<f32> {
+    return mix(color, fog.color, fog_amount(world_position, view_position));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let depth = textureLoad(t_depth, p, 0).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(textureDimensions(t_depth));
+    let world = lighting.inv_view_proj * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let position = world.xyz / world.w;
+
+    let albedo = textureLoad(t_albedo, p, 0).rgb;
+    let normal = normalize(textureLoad(t_normal, p, 0).xyz * 2.0 - 1.0);
+    let material = textureLoad(t_material, p, 0);
+    let shininess = material.a * 256.0;
+    let ao = ambient_occlusion(p);
+    let view_dir = normalize(lighting.view_pos.xyz - position);
+
+    var result = textureLoad(t_emissive, p, 0).rgb;
+    for (var i = 0u; i < min(lighting.light_count, 64u); i = i + 1u) {
+        let light = lighting.lights[i];
+        let to_light = light.position - position;
+        var attenuation = 1.0;
+        if (light.range > 0.0) {
+            let falloff = clamp(1.0 - length(to_light) / light.range, 0.0, 1.0);
+            attenuation = falloff * falloff;
+        }
+        if (attenuation <= 0.0) {
+            continue;
+        }
+        let light_dir = normalize(to_light);
+        let half_dir = normalize(view_dir + light_dir);
+
+        let ambient_color = light.color * 0.1 * ao;
+        let diffuse_color = light.color * max(dot(normal, light_dir), 0.0);
+        let specular_color = light.color * pow(max(dot(normal, half_dir), 0.0), shininess) * material.rgb;
+        result = result + ((ambient_color + diffuse_color) * albedo + specular_color) * attenuation;
+    }
+    return vec4<f32>(apply_fog(result, position, lighting.view_pos.xyz), 1.0);
+}
@@ -0,0 +1,155 @@
+//  FXAA - Finds edges from luma contrast, walks along each one to find where it ends and nudges the sample position
+//  across it by how far the pixel is from the end. Based on FXAA 3.11's quality preset.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+//  One triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_linear: sampler;
+
+let EDGE_THRESHOLD_MIN: f32 = 0.0312;
+let EDGE_THRESHOLD_MAX: f32 = 0.125;
+let SUBPIXEL_QUALITY: f32 = 0.75;
+let ITERATIONS: i32 = 12;
+
+//  Perceptual enough, the colours are linear so the square root brings them closer to what the eye sees
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(t_color, s_linear, uv, 0.0).rgb);
+}
+
+//  Step sizes get bigger the further along the edge the search gets
+fn step_quality(i: i32) -> f32 {
+    if (i < 5) {
+        return 1.0;
+    }
+    if (i == 5) {
+        return 1.5;
+    }
+    if (i < 10) {
+        return 2.0;
+    }
+    if (i == 10) {
+        return 4.0;
+    }
+    return 8.0;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_color));
+    let uv = in.uv;
+    let center = textureSampleLevel(t_color, s_linear, uv, 0.0);
+
+    let l_c = luma(center.rgb);
+    let l_u = luma_at(uv + vec2<f32>(0.0, -texel.y));
+    let l_d = luma_at(uv + vec2<f32>(0.0, texel.y));
+    let l_l = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+    let l_r = luma_at(uv + vec2<f32>(texel.x, 0.0));
+    let l_min = min(l_c, min(min(l_u, l_d), min(l_l, l_r)));
+    let l_max = max(l_c, max(max(l_u, l_d), max(l_l, l_r)));
+    let range = l_max - l_min;
+    if (range < max(EDGE_THRESHOLD_MIN, l_max * EDGE_THRESHOLD_MAX)) {
+        return center;
+    }
+
+    let l_ul = luma_at(uv + vec2<f32>(-texel.x, -texel.y));
+    let l_ur = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+    let l_dl = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+    let l_dr = luma_at(uv + vec2<f32>(texel.x, texel.y));
+    let l_ud = l_u + l_d;
+    let l_lr = l_l + l_r;
+    let l_left_corners = l_ul + l_dl;
+    let l_right_corners = l_ur + l_dr;
+    let l_up_corners = l_ul + l_ur;
+    let l_down_corners = l_dl + l_dr;
+
+    //  Which way the edge runs
+    let edge_horizontal = abs(-2.0 * l_l + l_left_corners) + abs(-2.0 * l_c + l_ud) * 2.0 + abs(-2.0 * l_r + l_right_corners);
+    let edge_vertical = abs(-2.0 * l_u + l_up_corners) + abs(-2.0 * l_c + l_lr) * 2.0 + abs(-2.0 * l_d + l_down_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    //  Which side of the pixel the edge is on
+    let l_n = select(l_l, l_u, is_horizontal);
+    let l_p = select(l_r, l_d, is_horizontal);
+    let gradient_n = abs(l_n - l_c);
+    let gradient_p = abs(l_p - l_c);
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    let gradient_scaled = 0.25 * max(gradient_n, gradient_p);
+    var l_local_average = 0.5 * (l_p + l_c);
+    if (gradient_n >= gradient_p) {
+        step_length = -step_length;
+        l_local_average = 0.5 * (l_n + l_c);
+    }
+
+    //  Walk both ways along the edge, half a pixel across so the samples straddle it
+    var edge_uv = uv;
+    if (is_horizontal) {
+        edge_uv.y = edge_uv.y + step_length * 0.5;
+    } else {
+        edge_uv.x = edge_uv.x + step_length * 0.5;
+    }
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv1 = edge_uv - offset;
+    var uv2 = edge_uv + offset;
+    var end1 = luma_at(uv1) - l_local_average;
+    var end2 = luma_at(uv2) - l_local_average;
+    var reached1 = abs(end1) >= gradient_scaled;
+    var reached2 = abs(end2) >= gradient_scaled;
+    for (var i = 1; i < ITERATIONS; i = i + 1) {
+        if (reached1 && reached2) {
+            break;
+        }
+        if (!reached1) {
+            uv1 = uv1 - offset * step_quality(i);
+            end1 = luma_at(uv1) - l_local_average;
+            reached1 = abs(end1) >= gradient_scaled;
+        }
+        if (!reached2) {
+            uv2 = uv2 + offset * step_quality(i);
+            end2 = luma_at(uv2) - l_local_average;
+            reached2 = abs(end2) >= gradient_scaled;
+        }
+    }
+
+    //  The closer end decides how far across the edge to sample
+    let distance1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let distance2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+    let closer_is_1 = distance1 < distance2;
+    let distance = min(distance1, distance2);
+    let pixel_offset = -distance / (distance1 + distance2) + 0.5;
+    let center_is_darker = l_c < l_local_average;
+    let correct_variation = (select(end2, end1, closer_is_1) < 0.0) != center_is_darker;
+    var final_offset = select(0.0, pixel_offset, correct_variation);
+
+    //  Thin features a pixel wide have no proper edge to walk, blur them by how much they stand out instead
+    let l_average = (1.0 / 12.0) * (2.0 * (l_ud + l_lr) + l_left_corners + l_right_corners);
+    let subpixel = clamp(abs(l_average - l_c) / range, 0.0, 1.0);
+    let subpixel_smooth = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+    final_offset = max(final_offset, subpixel_smooth * subpixel_smooth * SUBPIXEL_QUALITY);
+
+    var final_uv = uv;
+    if (is_horizontal) {
+        final_uv.y = final_uv.y + final_offset * step_length;
+    } else {
+        final_uv.x = final_uv.x + final_offset * step_length;
+    }
+    return vec4<f32>(textureSampleLevel(t_color, s_linear, final_uv, 0.0).rgb, center.a);
+}
@@ -0,0 +1,32 @@
+//  GUI - Quads from engine/gui.rs, positioned on the CPU. Untextured quads sample a white texel.
+
+@group(0) @binding(0)
+var t_image: texture_2d<f32>;
+@group(0) @binding(1)
+var s_image: sampler;
+
+struct GuiVertex {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(vertex: GuiVertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(vertex.position, 0.0, 1.0);
+    out.tex_coords = vertex.tex_coords;
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_image, s_image, in.tex_coords) * in.color;
+}
@@ -0,0 +1,62 @@
+//  Hi-Z - Builds a depth pyramid where every texel holds the farthest depth of the texels under it, so the culling pass
+//  can test big objects against a handful of texels.
+
+//  Bound as plain floats rather than texture_depth_2d, the GL backend can't load from depth textures
+@group(0) @binding(0)
+var depth: texture_2d<f32>;
+@group(0) @binding(1)
+var dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var src: texture_2d<f32>;
+//  The depth buffer when MSAA is on, only copy_depth_msaa uses it
+@group(0) @binding(3)
+var depth_msaa: texture_multisampled_2d<f32>;
+
+@compute @workgroup_size(8, 8)
+fn copy_depth(@builtin(global_invocation_id) id: vec3<u32>) {
+    let coords = vec2<i32>(id.xy);
+    let dims = textureDimensions(dst);
+    if (coords.x >= dims.x || coords.y >= dims.y) {
+        return;
+    }
+    textureStore(dst, coords, vec4<f32>(textureLoad(depth, coords, 0).r, 0.0, 0.0, 1.0));
+}
+
+//  Same again with one depth per sample, the farthest of them stands for the whole pixel
+@compute @workgroup_size(8, 8)
+fn copy_depth_msaa(@builtin(global_invocation_id) id: vec3<u32>) {
+    let coords = vec2<i32>(id.xy);
+    let dims = textureDimensions(dst);
+    if (coords.x >= dims.x || coords.y >= dims.y) {
+        return;
+    }
+    var farthest = 0.0;
+    let samples = i32(textureNumSamples(depth_msaa));
+    for (var i = 0; i < samples; i = i + 1) {
+        farthest = max(farthest, textureLoad(depth_msaa, coords, i).r);
+    }
+    textureStore(dst, coords, vec4<f32>(farthest, 0.0, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn reduce(@builtin(global_invocation_id) id: vec3<u32>) {
+    let coords = vec2<i32>(id.xy);
+    let dims = textureDimensions(dst);
+    if (coords.x >= dims.x || coords.y >= dims.y) {
+        return;
+    }
+
+    let src_dims = textureDimensions(src, 0);
+    let base = coords * 2;
+    var farthest = 0.0;
+    //  Odd sized levels have a row/column left over that has to go into the last texel, or it'd be lost
+    let extra_x = select(0, 1, coords.x == dims.x - 1 && (src_dims.x & 1) == 1);
+    let extra_y = select(0, 1, coords.y == dims.y - 1 && (src_dims.y & 1) == 1);
+    for (var y = 0; y <= 1 + extra_y; y = y + 1) {
+        for (var x = 0; x <= 1 + extra_x; x = x + 1) {
+            let c = min(base + vec2<i32>(x, y), src_dims - 1);
+            farthest = max(farthest, textureLoad(src, c, 0).r);
+        }
+    }
+    textureStore(dst, coords, vec4<f32>(farthest, 0.0, 0.0, 1.0));
+}
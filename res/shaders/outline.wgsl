@@ -0,0 +1,86 @@
+//  Outline - Draws a coloured silhouette around selected and hovered instances. vs_mask marks every pixel they cover
+//  in the stencil buffer, then vs_outline draws them again pushed out from their centre on screen, and the stencil
+//  test keeps only the rim. Both ignore depth so the outline shows through anything in front. Instances without any
+//  flags are collapsed to a point so they draw nothing.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct Outline {
+    selected_color: vec4<f32>,
+    hover_color: vec4<f32>,
+    viewport: vec2<f32>,
+    //  In pixels
+    width: f32,
+}
+@group(3) @binding(0)
+var<uniform> outline: Outline;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(13) flags: u32,
+}
+
+struct OutlineOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+let SELECTED: u32 = 1u;
+let HIDDEN: vec4<f32> = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+
+fn model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+}
+
+@vertex
+fn vs_mask(model: VertexInput, instance: InstanceInput) -> OutlineOutput {
+    var out: OutlineOutput;
+    out.clip_position = HIDDEN;
+    if (instance.flags != 0u) {
+        out.clip_position = camera.view_proj * model_matrix(instance) * vec4<f32>(model.position, 1.0);
+    }
+    out.color = vec4<f32>(0.0);
+    return out;
+}
+
+@vertex
+fn vs_outline(model: VertexInput, instance: InstanceInput) -> OutlineOutput {
+    var out: OutlineOutput;
+    out.clip_position = HIDDEN;
+    out.color = select(outline.hover_color, outline.selected_color, (instance.flags & SELECTED) != 0u);
+    if (instance.flags == 0u) {
+        return out;
+    }
+    let transform = camera.view_proj * model_matrix(instance);
+    let center = transform * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    var clip = transform * vec4<f32>(model.position, 1.0);
+    //  Every copy of a vertex moves the same way, unlike pushing along normals, so hard edges don't split open
+    let away = clip.xy / clip.w - center.xy / max(center.w, 0.0001);
+    if (dot(away, away) > 0.0) {
+        clip = vec4<f32>(clip.xy + normalize(away) * outline.width * 2.0 / outline.viewport * clip.w, clip.zw);
+    }
+    out.clip_position = clip;
+    return out;
+}
+
+@fragment
+fn fs_main(in: OutlineOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -15,6 +15,8 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) color: vec4<f32>,
+    @location(13) flags: u32,
 };
 
 struct VertexInput {
@@ -31,6 +33,11 @@ struct VertexOutput {
     @location(1) tangent_position: vec3<f32>,
     @location(2) tangent_light_position: vec3<f32>,
     @location(3) tangent_view_position: vec3<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) world_normal: vec3<f32>,
+    @location(6) world_tangent: vec3<f32>,
+    @location(7) world_bitangent: vec3<f32>,
+    @location(8) world_position: vec3<f32>,
 };
 
 struct Light {
@@ -41,6 +48,47 @@ struct Light {
 @group(2) @binding(0)
 var<uniform> light: Light;
 
+struct Fog {
+    //  The sky colour, which fog fades towards
+    color: vec3<f32>,
+    //  0 off, 1 linear, 2 exponential, 3 height
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    height: f32,
+    height_falloff: f32,
+}
+@group(2) @binding(1)
+var<uniform> fog: Fog;
+
+fn fog_amount(world_position: vec3<f32>, view_position: vec3<f32>) -> f32 {
+    let d = distance(world_position, view_position);
+    if (fog.mode == 1u) {
+        return clamp((d - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+    }
+    if (fog.mode == 2u) {
+        return 1.0 - exp(-fog.density * max(d - fog.start, 0.0));
+    }
+    if (fog.mode == 3u) {
+        //  Density falls off exponentially with height, integrated along the ray from the camera
+        let base = fog.density * exp(-fog.height_falloff * (view_position.y - fog.height));
+        let k = fog.height_falloff * (world_position.y - view_position.y);
+        var along = 1.0;
+        if (abs(k) > 0.0001) {
+            along = (1.0 - exp(-k)) / k;
+        }
+        return clamp(1.0 - exp(-base * along * max(d - fog.start, 0.0)), 0.0, 1.0);
+    }
+    return 0.0;
+}
+
+fn apply_fog(color: vec3<f32>, world_position: vec3<f32>, view_position: vec3<f32>) -> vec3<f32> {
+    return mix(color, fog.color, fog_amount(world_position, view_position));
+}
+
+let HOVERED: u32 = 2u;
+
 @vertex
 //  variables defined with 'var' can be modified but must specify their type
 //  variables defined with 'let' can have their type inferred but cannot be changed during the shader
@@ -78,6 +126,15 @@ fn vs_main(
     out.tangent_position = tangent_matrix * world_position.xyz;
     out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
     out.tangent_light_position = tangent_matrix * light.position;
+    out.color = instance.color;
+    //  Hovered bricks are lightened a little on top of their outline
+    if ((instance.flags & HOVERED) != 0u) {
+        out.color = vec4<f32>(mix(instance.color.rgb, vec3<f32>(1.0), 0.25), instance.color.a);
+    }
+    out.world_normal = world_normal;
+    out.world_tangent = world_tangent;
+    out.world_bitangent = world_bitangent;
+    out.world_position = world_position.xyz;
     return out;
 }
 
@@ -92,14 +149,47 @@ var t_normal: texture_2d<f32>;
 @group(0) @binding(3)
 var s_normal: sampler;
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+struct Material {
+    diffuse: vec3<f32>,
+    opacity: f32,
+    specular: vec3<f32>,
+    shininess: f32,
+    emissive: vec3<f32>,
+}
+@group(0) @binding(4)
+var<uniform> material: Material;
+
+//  How much ambient light reaches each pixel, from the SSAO pass. 1x1 and white when SSAO is off.
+@group(3) @binding(0)
+var t_ao: texture_2d<f32>;
+
+fn ambient_occlusion(position: vec4<f32>) -> f32 {
+    let max_coords = vec2<i32>(textureDimensions(t_ao)) - 1;
+    return textureLoad(t_ao, min(vec2<i32>(position.xy), max_coords), 0).r;
+}
+
+fn object_color(in: VertexOutput) -> vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * vec4<f32>(material.diffuse, material.opacity) * in.color;
+}
+
+//  The normal map's normal in world space
+fn mapped_normal(in: VertexOutput) -> vec3<f32> {
+    let tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    return normalize(tangent_matrix * tangent_normal);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let object_color: vec4<f32> = object_color(in);
     let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
     
     // We don't need (or want) much ambient light, so 0.1 is fine
     let ambient_strength = 0.1;
-    let ambient_color = light.color * ambient_strength;
+    let ambient_color = light.color * ambient_strength * ambient_occlusion(in.clip_position);
 
     // Create the lighting vectors
     let tangent_normal = object_normal.xyz * 2.0 - 1.0;
@@ -110,10 +200,86 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
     let diffuse_color = light.color * diffuse_strength;
 
-    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), material.shininess);
+    let specular_color = specular_strength * light.color * material.specular;
+
+    //  Specular highlights aren't tinted by the surface colour, emissive isn't affected by lighting at all
+    let result = (ambient_color + diffuse_color) * object_color.xyz + specular_color + material.emissive;
+
+    return vec4<f32>(apply_fog(result, in.world_position, camera.view_pos.xyz), object_color.a);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+//  Meshes with a transparent material, blended over everything opaque on both render paths
+@fragment
+fn fs_transparent(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    //  World space, packed into 0..1
+    @location(1) normal: vec4<f32>,
+    //  Specular colour, and shininess / 256 in alpha
+    @location(2) material: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+}
+
+//  The deferred path's geometry pass. Meshes with transparent materials are left for the transparent pass.
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOutput {
+    let color = object_color(in);
+    let normal = mapped_normal(in);
+
+    var out: GBufferOutput;
+    out.albedo = color;
+    out.normal = vec4<f32>(normal * 0.5 + 0.5, 1.0);
+    out.material = vec4<f32>(material.specular, material.shininess / 256.0);
+    out.emissive = vec4<f32>(material.emissive, 1.0);
+    return out;
+}
+
+//  Normal prepass for SSAO, only depth and the surface's world space normal
+@fragment
+fn fs_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(normalize(in.world_normal) * 0.5 + 0.5, 1.0);
+}
+
+//  Debug views, see engine/debug_view.rs
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+//  The camera's far plane, the depth view goes from black at the camera to white there
+let DEPTH_VIEW_RANGE: f32 = 100.0;
+let WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.2, 1.0, 0.4);
 
-    return vec4<f32>(result, object_color.a);
-}
\ No newline at end of file
+//  An 8x8 checker per UV tile, tinted red along u and green along v so flipped or rotated UVs stand out
+fn uv_checker(uv: vec2<f32>) -> vec3<f32> {
+    let cells = floor(uv * 8.0);
+    let checker = select(0.2, 0.9, fract((cells.x + cells.y) * 0.5) > 0.25);
+    return mix(vec3<f32>(checker), vec3<f32>(fract(uv), 0.0), 0.5);
+}
+
+@fragment
+fn fs_debug_uv(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(uv_checker(in.tex_coords), 1.0);
+}
+
+//  After the normal map, so this shows both the tangents from loading and how the map is read
+@fragment
+fn fs_debug_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(mapped_normal(in) * 0.5 + 0.5, 1.0);
+}
+
+@fragment
+fn fs_debug_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(vec3<f32>(distance(camera.view_pos.xyz, in.world_position) / DEPTH_VIEW_RANGE), 1.0);
+}
+
+//  Drawn with PolygonMode::Line
+@fragment
+fn fs_debug_wireframe(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
@@ -0,0 +1,194 @@
+//  SMAA - Morphological anti-aliasing in SMAA 1x's three passes: find edges, work out how much of each pixel along an
+//  edge the real silhouette covers, then blend each pixel with its neighbours by that much. The coverage is worked out
+//  directly from the shape of the edge rather than looked up in SMAA's precomputed area texture, and only horizontal
+//  and vertical edges are handled.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+//  The scene colour for the edge and blend passes, the edges for the weights pass
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_linear: sampler;
+//  Only used by the blend pass
+@group(0) @binding(2)
+var t_weights: texture_2d<f32>;
+
+let THRESHOLD: f32 = 0.1;
+//  An edge is ignored next to one this many times stronger, so busy areas don't get blurred
+let LOCAL_CONTRAST_FACTOR: f32 = 2.0;
+let MAX_SEARCH: i32 = 16;
+
+fn load(coords: vec2<i32>) -> vec4<f32> {
+    let dims = vec2<i32>(textureDimensions(t_input));
+    return textureLoad(t_input, clamp(coords, vec2<i32>(0), dims - 1), 0);
+}
+
+fn luma(coords: vec2<i32>) -> f32 {
+    return sqrt(dot(load(coords).rgb, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+//  Red is an edge on the pixel's left side, green one on its top
+@fragment
+fn fs_edges(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let l = luma(p);
+    let l_left = luma(p + vec2<i32>(-1, 0));
+    let l_top = luma(p + vec2<i32>(0, -1));
+    let delta = abs(vec2<f32>(l - l_left, l - l_top));
+    var edges = step(vec2<f32>(THRESHOLD), delta);
+    if (edges.x + edges.y == 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    let l_right = luma(p + vec2<i32>(1, 0));
+    let l_bottom = luma(p + vec2<i32>(0, 1));
+    let l_left_left = luma(p + vec2<i32>(-2, 0));
+    let l_top_top = luma(p + vec2<i32>(0, -2));
+    let neighbours = max(
+        max(abs(l - l_right), abs(l - l_bottom)),
+        max(abs(l_left - l_left_left), abs(l_top - l_top_top)),
+    );
+    let strongest = max(max(delta.x, delta.y), neighbours);
+    edges = edges * step(vec2<f32>(strongest), LOCAL_CONTRAST_FACTOR * delta);
+    return vec4<f32>(edges, 0.0, 0.0);
+}
+
+fn left_edge(coords: vec2<i32>) -> f32 {
+    return step(0.5, load(coords).r);
+}
+
+fn top_edge(coords: vec2<i32>) -> f32 {
+    return step(0.5, load(coords).g);
+}
+
+//  Height of the silhouette above the edge at `t` along it. `a` and `b` are the heights at the two ends, +-0.5 where
+//  another edge crosses there and 0 where none does. Ends that go opposite ways are joined by one straight line,
+//  otherwise each end's line meets the edge halfway along.
+fn silhouette(t: f32, length: f32, a: f32, b: f32) -> f32 {
+    if (a * b < 0.0) {
+        return mix(a, b, t / length);
+    }
+    let half_length = length * 0.5;
+    if (t <= half_length) {
+        return a * (1.0 - t / half_length);
+    }
+    return b * (t - half_length) / half_length;
+}
+
+//  How much of the pixel from t0 to t0 + 1 along the edge the silhouette covers, positive when it's on the far side
+fn coverage(t0: f32, length: f32, a: f32, b: f32) -> f32 {
+    let t1 = t0 + 1.0;
+    if (a * b < 0.0) {
+        return 0.5 * (silhouette(t0, length, a, b) + silhouette(t1, length, a, b));
+    }
+    //  The line bends halfway along, so each side of that is integrated separately
+    let half_length = length * 0.5;
+    var area = 0.0;
+    let m0 = min(t1, half_length);
+    if (m0 > t0) {
+        area = area + (m0 - t0) * 0.5 * (silhouette(t0, length, a, b) + silhouette(m0, length, a, b));
+    }
+    let m1 = max(t0, half_length);
+    if (t1 > m1) {
+        area = area + (t1 - m1) * 0.5 * (silhouette(m1, length, a, b) + silhouette(t1, length, a, b));
+    }
+    return area;
+}
+
+//  How far this pixel should blend into each neighbour across its top and left edges. Red is towards the pixel above,
+//  green is how far the pixel above should blend down into this one, blue and alpha are the same for left and right.
+@fragment
+fn fs_weights(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    var weights = vec4<f32>(0.0);
+
+    if (top_edge(p) > 0.0) {
+        var left = 0;
+        for (var i = 1; i <= MAX_SEARCH; i = i + 1) {
+            if (top_edge(p + vec2<i32>(-i, 0)) == 0.0) {
+                break;
+            }
+            left = i;
+        }
+        var right = 0;
+        for (var i = 1; i <= MAX_SEARCH; i = i + 1) {
+            if (top_edge(p + vec2<i32>(i, 0)) == 0.0) {
+                break;
+            }
+            right = i;
+        }
+        //  Edges crossing the ends, going up counts as towards the pixel above
+        let start = p + vec2<i32>(-left, 0);
+        let end = p + vec2<i32>(right + 1, 0);
+        let a = 0.5 * (left_edge(start + vec2<i32>(0, -1)) - left_edge(start));
+        let b = 0.5 * (left_edge(end + vec2<i32>(0, -1)) - left_edge(end));
+        let h = coverage(f32(left), f32(left + right + 1), a, b);
+        weights.r = max(-h, 0.0);
+        weights.g = max(h, 0.0);
+    }
+
+    if (left_edge(p) > 0.0) {
+        var up = 0;
+        for (var i = 1; i <= MAX_SEARCH; i = i + 1) {
+            if (left_edge(p + vec2<i32>(0, -i)) == 0.0) {
+                break;
+            }
+            up = i;
+        }
+        var down = 0;
+        for (var i = 1; i <= MAX_SEARCH; i = i + 1) {
+            if (left_edge(p + vec2<i32>(0, i)) == 0.0) {
+                break;
+            }
+            down = i;
+        }
+        //  Going left counts as towards the pixel on the left
+        let start = p + vec2<i32>(0, -up);
+        let end = p + vec2<i32>(0, down + 1);
+        let a = 0.5 * (top_edge(start + vec2<i32>(-1, 0)) - top_edge(start));
+        let b = 0.5 * (top_edge(end + vec2<i32>(-1, 0)) - top_edge(end));
+        let h = coverage(f32(up), f32(up + down + 1), a, b);
+        weights.b = max(-h, 0.0);
+        weights.a = max(h, 0.0);
+    }
+    return weights;
+}
+
+fn load_weights(coords: vec2<i32>) -> vec4<f32> {
+    let dims = vec2<i32>(textureDimensions(t_weights));
+    return textureLoad(t_weights, clamp(coords, vec2<i32>(0), dims - 1), 0);
+}
+
+@fragment
+fn fs_blend(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let color = load(p);
+    let up = load_weights(p).r;
+    let down = load_weights(p + vec2<i32>(0, 1)).g;
+    let left = load_weights(p).b;
+    let right = load_weights(p + vec2<i32>(1, 0)).a;
+    let total = up + down + left + right;
+    if (total == 0.0) {
+        return color;
+    }
+
+    let scale = 1.0 / max(total, 1.0);
+    let neighbours = up * load(p + vec2<i32>(0, -1)).rgb
+        + down * load(p + vec2<i32>(0, 1)).rgb
+        + left * load(p + vec2<i32>(-1, 0)).rgb
+        + right * load(p + vec2<i32>(1, 0)).rgb;
+    return vec4<f32>(color.rgb * (1.0 - total * scale) + neighbours * scale, color.a);
+}
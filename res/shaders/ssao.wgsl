@@ -0,0 +1,150 @@
+//  SSAO - Rebuilds each pixel's world position from the normal prepass's depth, then checks how many points in a
+//  hemisphere around its normal are hidden behind something else on screen. The result is noisy from the per pixel
+//  rotation of the kernel, so it's blurred along both axes with weights that don't cross depth or normal edges.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+struct Ssao {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+    radius: f32,
+    intensity: f32,
+    sample_count: u32,
+    //  Points in the unit hemisphere around +z, closer to the middle the earlier they are
+    kernel: array<vec4<f32>, 64>,
+}
+
+//  Bound as plain floats rather than texture_depth_2d, the GL backend can't load from depth textures
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+//  World space normals packed into 0..1
+@group(0) @binding(1)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(2)
+var<uniform> ssao: Ssao;
+//  Only used by the blur passes
+@group(0) @binding(3)
+var t_ao: texture_2d<f32>;
+
+let PI: f32 = 3.14159265;
+//  Keeps flat surfaces from shadowing themselves through depth precision
+let BIAS: f32 = 0.02;
+let BLUR_RADIUS: i32 = 4;
+
+fn world_position(p: vec2<i32>, depth: f32) -> vec3<f32> {
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(textureDimensions(t_depth));
+    let world = ssao.inv_view_proj * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+fn load_normal(p: vec2<i32>) -> vec3<f32> {
+    return normalize(textureLoad(t_normal, p, 0).xyz * 2.0 - 1.0);
+}
+
+@fragment
+fn fs_ssao(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let depth = textureLoad(t_depth, p, 0).r;
+    if (depth >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+    let normal = load_normal(p);
+    let position = world_position(p, depth);
+    let distance_to_camera = distance(ssao.view_pos.xyz, position);
+
+    //  Any two directions at right angles to the normal, turned by a different angle for each pixel
+    let sign = select(-1.0, 1.0, normal.z >= 0.0);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    let t1 = vec3<f32>(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x);
+    let t2 = vec3<f32>(b, sign + normal.y * normal.y * a, -normal.y);
+    let noise = fract(52.9829189 * fract(dot(in.position.xy, vec2<f32>(0.06711056, 0.00583715))));
+    let angle = noise * 2.0 * PI;
+    let tangent = cos(angle) * t1 + sin(angle) * t2;
+    let bitangent = cross(normal, tangent);
+
+    let dims = vec2<f32>(textureDimensions(t_depth));
+    let count = min(ssao.sample_count, 64u);
+    var occlusion = 0.0;
+    for (var i = 0u; i < count; i = i + 1u) {
+        let k = ssao.kernel[i].xyz;
+        let sample_position = position + (tangent * k.x + bitangent * k.y + normal * k.z) * ssao.radius;
+        let clip = ssao.view_proj * vec4<f32>(sample_position, 1.0);
+        if (clip.w <= 0.0) {
+            continue;
+        }
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+            continue;
+        }
+        let q = vec2<i32>(uv * dims);
+        let scene_depth = textureLoad(t_depth, q, 0).r;
+        if (scene_depth >= 1.0) {
+            continue;
+        }
+        let scene_distance = distance(ssao.view_pos.xyz, world_position(q, scene_depth));
+        let sample_distance = distance(ssao.view_pos.xyz, sample_position);
+        //  Something far in front of the point doesn't shadow it, it just hides it
+        let in_range = smoothstep(0.0, 1.0, ssao.radius / max(abs(distance_to_camera - scene_distance), 0.0001));
+        occlusion = occlusion + select(0.0, in_range, scene_distance <= sample_distance - BIAS);
+    }
+
+    let ao = 1.0 - ssao.intensity * occlusion / f32(max(count, 1u));
+    return vec4<f32>(clamp(ao, 0.0, 1.0));
+}
+
+fn blur(p: vec2<i32>, direction: vec2<i32>) -> vec4<f32> {
+    let depth = textureLoad(t_depth, p, 0).r;
+    let center = textureLoad(t_ao, p, 0).r;
+    if (depth >= 1.0) {
+        return vec4<f32>(center);
+    }
+    let normal = load_normal(p);
+    let distance_to_camera = distance(ssao.view_pos.xyz, world_position(p, depth));
+    let max_coords = vec2<i32>(textureDimensions(t_ao)) - 1;
+
+    var total = center;
+    var total_weight = 1.0;
+    for (var i = -BLUR_RADIUS; i <= BLUR_RADIUS; i = i + 1) {
+        if (i == 0) {
+            continue;
+        }
+        let q = clamp(p + direction * i, vec2<i32>(0), max_coords);
+        let q_depth = textureLoad(t_depth, q, 0).r;
+        if (q_depth >= 1.0) {
+            continue;
+        }
+        let q_distance = distance(ssao.view_pos.xyz, world_position(q, q_depth));
+        let gaussian = exp(-f32(i * i) / 12.5);
+        let depth_weight = max(1.0 - abs(q_distance - distance_to_camera) / ssao.radius, 0.0);
+        let normal_weight = pow(max(dot(normal, load_normal(q)), 0.0), 8.0);
+        let weight = gaussian * depth_weight * normal_weight;
+        total = total + textureLoad(t_ao, q, 0).r * weight;
+        total_weight = total_weight + weight;
+    }
+    return vec4<f32>(total / total_weight);
+}
+
+@fragment
+fn fs_blur_horizontal(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.position.xy), vec2<i32>(1, 0));
+}
+
+@fragment
+fn fs_blur_vertical(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.position.xy), vec2<i32>(0, 1));
+}
@@ -0,0 +1,85 @@
+//  TAA - Every frame is drawn with the projection nudged by a different fraction of a pixel, and blended into the history
+//  of the frames before it. Each pixel finds where it was last frame from the depth buffer, and the history is clamped
+//  to the colours around it now so anything that's moved or appeared doesn't leave a ghost.
+
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+struct Taa {
+    //  This frame's jittered view_proj, inverted
+    inv_view_proj: mat4x4<f32>,
+    //  Last frame's view_proj without the jitter
+    prev_view_proj: mat4x4<f32>,
+    //  How much of this frame goes into the result
+    blend: f32,
+    //  0 until the history has something in it
+    history_valid: u32,
+}
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_linear: sampler;
+@group(0) @binding(2)
+var t_history: texture_2d<f32>;
+//  Bound as plain floats rather than texture_depth_2d, the GL backend can't load from depth textures
+@group(0) @binding(3)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(4)
+var<uniform> taa: Taa;
+
+struct TaaOutput {
+    @location(0) color: vec4<f32>,
+    //  The same again, kept as next frame's history
+    @location(1) history: vec4<f32>,
+}
+
+fn load(coords: vec2<i32>) -> vec3<f32> {
+    let dims = vec2<i32>(textureDimensions(t_color));
+    return textureLoad(t_color, clamp(coords, vec2<i32>(0), dims - 1), 0).rgb;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> TaaOutput {
+    let p = vec2<i32>(in.position.xy);
+    let current = textureLoad(t_color, p, 0);
+
+    //  Back to world space, then into last frame's screen
+    let depth = textureLoad(t_depth, p, 0).r;
+    let ndc = vec4<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0, depth, 1.0);
+    let world = taa.inv_view_proj * ndc;
+    let prev_clip = taa.prev_view_proj * vec4<f32>(world.xyz / world.w, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    let prev_uv = vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5);
+
+    var color_min = current.rgb;
+    var color_max = current.rgb;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let neighbour = load(p + vec2<i32>(x, y));
+            color_min = min(color_min, neighbour);
+            color_max = max(color_max, neighbour);
+        }
+    }
+    let history = clamp(textureSampleLevel(t_history, s_linear, prev_uv, 0.0).rgb, color_min, color_max);
+
+    let on_screen = all(prev_uv >= vec2<f32>(0.0)) && all(prev_uv <= vec2<f32>(1.0));
+    let use_history = taa.history_valid != 0u && prev_clip.w > 0.0 && on_screen;
+    let result = select(current.rgb, mix(history, current.rgb, taa.blend), use_history);
+
+    var out: TaaOutput;
+    out.color = vec4<f32>(result, current.a);
+    out.history = out.color;
+    return out;
+}
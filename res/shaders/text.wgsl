@@ -0,0 +1,61 @@
+//  Text - Glyph quads from engine/text.rs, coloured coverage out of the glyph atlas. Screen text is positioned in
+//  pixels from the top left, world text is offset from its anchor along the camera's axes so it always faces it.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Text {
+    viewport: vec2<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> text: Text;
+@group(1) @binding(1)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(2)
+var s_atlas: sampler;
+
+struct TextVertex {
+    @location(0) position: vec3<f32>,
+    @location(1) offset: vec2<f32>,
+    @location(2) tex_coords: vec2<f32>,
+    @location(3) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_screen(vertex: TextVertex) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = vertex.position.xy / text.viewport * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.tex_coords = vertex.tex_coords;
+    out.color = vertex.color;
+    return out;
+}
+
+//  Offsets are y down like the layout, so up is subtracted
+@vertex
+fn vs_world(vertex: TextVertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = vertex.position + text.right.xyz * vertex.offset.x - text.up.xyz * vertex.offset.y;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.tex_coords = vertex.tex_coords;
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.tex_coords).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
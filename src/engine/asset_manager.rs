@@ -0,0 +1,520 @@
+//  ASSET MANAGER - Decodes images and meshes on worker threads and uploads them to the GPU a few at a time each frame.
+//  Until an asset is ready its handle resolves to a placeholder (a checkerboard texture or a cube) so the game can keep drawing.
+
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::engine::{lod, model, resources, texture};
+use crate::game::uniform::MaterialUniform;
+pub use crate::engine::assets::{Assets, Handle, LoadState};
+
+//  How many bytes we're willing to upload to the GPU per frame, at least one asset is always uploaded
+pub const DEFAULT_UPLOAD_BUDGET: usize = 8 * 1024 * 1024;
+
+//  Counts what was asked for. A model's textures are part of the model, it's only done once they are, so the
+//  total never grows behind a load that's already running.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed >= self.total
+    }
+
+    //  0.0 - 1.0, for a loading bar
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+}
+
+//  Requests hold on to their handle so the slot can't be evicted while a worker is busy with it
+enum Request {
+    //  `counted` is false for textures a model's materials asked for, they're part of the model's progress
+    Texture { handle: Handle<texture::Texture>, path: String, is_normal_map: bool, counted: bool },
+    Model { handle: Handle<model::Model>, path: String },
+}
+
+enum Decoded {
+    Texture { handle: Handle<texture::Texture>, image: image::DynamicImage, is_normal_map: bool, label: String, counted: bool },
+    TextureFailed { handle: Handle<texture::Texture>, error: anyhow::Error, counted: bool },
+    //  The lower LOD levels are generated here too so the main thread only has to upload them
+    Model { handle: Handle<model::Model>, data: resources::ModelData, lods: Vec<(lod::Lod, Vec<model::MeshData>)> },
+    ModelFailed { handle: Handle<model::Model>, error: anyhow::Error },
+}
+
+impl Decoded {
+    fn upload_size(&self) -> usize {
+        match self {
+            Decoded::Texture { image, .. } => 4 * (image.width() * image.height()) as usize,
+            Decoded::Model { data, lods, .. } => {
+                data.upload_size() + lods.iter().flat_map(|(_, meshes)| meshes).map(|m| m.upload_size()).sum::<usize>()
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn decode(request: Request) -> Decoded {
+    match request {
+        Request::Texture { handle, path, is_normal_map, counted } => {
+            match pollster::block_on(resources::load_image(&path)) {
+                Ok(image) => Decoded::Texture { handle, image, is_normal_map, label: path, counted },
+                Err(error) => Decoded::TextureFailed { handle, error: error.context(path), counted },
+            }
+        }
+        Request::Model { handle, path } => {
+            match pollster::block_on(resources::load_model_obj_data(&path)) {
+                Ok(data) => {
+                    let lods = lod::generate_lods(&data.meshes);
+                    Decoded::Model { handle, data, lods }
+                }
+                Err(error) => Decoded::ModelFailed { handle, error: error.context(path) },
+            }
+        }
+    }
+}
+
+//  A material whose textures are still loading, it's drawn with placeholder textures until they arrive
+struct PendingMaterial {
+    handle: Handle<model::Material>,
+    name: String,
+    diffuse: Handle<texture::Texture>,
+    normal: Handle<texture::Texture>,
+    uniform: MaterialUniform,
+}
+
+//  A model that's been uploaded but is still waiting on its materials' textures
+struct PendingModel {
+    materials: Vec<Handle<model::Material>>,
+}
+
+pub struct AssetManager {
+    //  Only None while dropping, so the workers see the channel close
+    request_sender: Option<mpsc::Sender<Request>>,
+    //  Only used when there are no worker threads (wasm), requests are decoded inline in update() instead
+    request_receiver: Arc<Mutex<mpsc::Receiver<Request>>>,
+    decoded_sender: mpsc::Sender<Decoded>,
+    decoded_receiver: mpsc::Receiver<Decoded>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+    //  Decoded assets waiting for their turn to be uploaded
+    pending_uploads: VecDeque<Decoded>,
+    pending_materials: Vec<PendingMaterial>,
+    pending_models: Vec<PendingModel>,
+    upload_budget: usize,
+    keep_mesh_geometry: bool,
+    pub textures: Assets<texture::Texture>,
+    pub materials: Assets<model::Material>,
+    pub models: Assets<model::Model>,
+    progress: LoadProgress,
+    placeholder_texture: Handle<texture::Texture>,
+    //  Used for maps a material doesn't have
+    default_diffuse: Handle<texture::Texture>,
+    default_normal: Handle<texture::Texture>,
+    placeholder_material: Handle<model::Material>,
+    //  For meshes from files without any materials, plain white so the instance colour shows as it is
+    default_material: Handle<model::Material>,
+    placeholder_model: Handle<model::Model>,
+}
+
+impl AssetManager {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> Self {
+        let num_workers = if cfg!(target_arch = "wasm32") {
+            0
+        } else {
+            std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2).clamp(1, 4)
+        };
+
+        let (request_sender, request_receiver) = mpsc::channel::<Request>();
+        let (decoded_sender, decoded_receiver) = mpsc::channel::<Decoded>();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+
+        let workers = (0..num_workers).map(|i| {
+            let requests = request_receiver.clone();
+            let decoded = decoded_sender.clone();
+            std::thread::Builder::new()
+                .name(format!("asset-worker-{}", i))
+                .spawn(move || loop {
+                    //  Only hold the lock while waiting for a request, not while decoding it
+                    let request = match requests.lock().unwrap().recv() {
+                        Ok(request) => request,
+                        Err(_) => break,
+                    };
+                    if decoded.send(decode(request)).is_err() {
+                        break;
+                    }
+                })
+                .expect("Failed to spawn asset worker")
+        }).collect();
+
+        let mut textures = Assets::new();
+        let mut materials = Assets::new();
+        let mut models = Assets::new();
+
+        let checkerboard = texture::Texture::from_image(
+            device, queue, &checkerboard_image(), Some("placeholder_texture"), false,
+        ).unwrap();
+        let default_diffuse = resources::builtin_texture(resources::DEFAULT_DIFFUSE_TEXTURE, device, queue, &mut textures);
+        let default_normal = resources::builtin_texture(resources::DEFAULT_NORMAL_TEXTURE, device, queue, &mut textures);
+        let placeholder_material = model::Material::new(
+            device,
+            "placeholder_material",
+            &checkerboard,
+            textures.get(&default_normal).unwrap(),
+            MaterialUniform::default(),
+            layout,
+        );
+        let default_material = model::Material::new(
+            device,
+            "default_material",
+            textures.get(&default_diffuse).unwrap(),
+            textures.get(&default_normal).unwrap(),
+            MaterialUniform::default(),
+            layout,
+        );
+        let default_material = materials.add(
+            default_material.with_textures(vec![default_diffuse.clone(), default_normal.clone()]),
+        );
+        let placeholder_texture = textures.add(checkerboard);
+        let placeholder_material = materials.add(
+            placeholder_material.with_textures(vec![placeholder_texture.clone(), default_normal.clone()]),
+        );
+        let placeholder_model = models.add(model::Model::new(
+            vec![cube_mesh("placeholder_model").upload(device, placeholder_material.clone(), false)],
+        ));
+
+        Self {
+            request_sender: Some(request_sender),
+            request_receiver,
+            decoded_sender,
+            decoded_receiver,
+            workers,
+            pending_uploads: VecDeque::new(),
+            pending_materials: Vec::new(),
+            pending_models: Vec::new(),
+            upload_budget: DEFAULT_UPLOAD_BUDGET,
+            keep_mesh_geometry: true,
+            textures,
+            materials,
+            models,
+            progress: LoadProgress::default(),
+            placeholder_texture,
+            default_diffuse,
+            default_normal,
+            placeholder_material,
+            default_material,
+            placeholder_model,
+        }
+    }
+
+    pub fn set_upload_budget(&mut self, bytes: usize) {
+        self.upload_budget = bytes;
+    }
+
+    //  Whether models loaded from now on keep a CPU copy of their vertices and indices. On by default, turn it off
+    //  to save memory if nothing needs picking or exporting.
+    pub fn set_keep_mesh_geometry(&mut self, keep: bool) {
+        self.keep_mesh_geometry = keep;
+    }
+
+    //  Loading the same path twice returns the same handle, the file is only decoded once
+    pub fn load_texture(&mut self, path: &str, is_normal_map: bool) -> Handle<texture::Texture> {
+        self.request_texture(path, is_normal_map, true)
+    }
+
+    fn request_texture(&mut self, path: &str, is_normal_map: bool, counted: bool) -> Handle<texture::Texture> {
+        let (handle, is_new) = self.textures.reserve(path);
+        if is_new {
+            let path = self.textures.path(&handle).unwrap_or(path).to_string();
+            if counted {
+                self.progress.total += 1;
+            }
+            self.request(Request::Texture { handle: handle.clone(), path, is_normal_map, counted });
+        }
+        handle
+    }
+
+    pub fn load_model(&mut self, path: &str) -> Handle<model::Model> {
+        let (handle, is_new) = self.models.reserve(path);
+        if is_new {
+            let path = self.models.path(&handle).unwrap_or(path).to_string();
+            self.progress.total += 1;
+            self.request(Request::Model { handle: handle.clone(), path });
+        }
+        handle
+    }
+
+    fn request(&mut self, request: Request) {
+        //  The receiver lives in self so sending can't fail
+        if let Some(sender) = &self.request_sender {
+            sender.send(request).unwrap();
+        }
+    }
+
+    //  Call once per frame. Collects finished decodes and uploads as many as fit in the budget.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) {
+        if self.workers.is_empty() {
+            //  Without threads decoding is the expensive part, so only do one per frame
+            let request = self.request_receiver.lock().unwrap().try_recv();
+            if let Ok(request) = request {
+                let _ = self.decoded_sender.send(decode(request));
+            }
+        }
+        self.pending_uploads.extend(self.decoded_receiver.try_iter());
+
+        let mut uploaded = 0;
+        while let Some(size) = self.pending_uploads.front().map(Decoded::upload_size) {
+            if uploaded > 0 && uploaded + size > self.upload_budget {
+                break;
+            }
+            uploaded += size;
+            if let Some(decoded) = self.pending_uploads.pop_front() {
+                self.upload(decoded, device, queue, layout);
+            }
+        }
+
+        self.resolve_materials(device, layout);
+        self.resolve_models();
+    }
+
+    fn upload(&mut self, decoded: Decoded, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) {
+        match decoded {
+            Decoded::Texture { handle, image, is_normal_map, label, counted } => {
+                match texture::Texture::from_image(device, queue, &image, Some(&label), is_normal_map) {
+                    Ok(texture) => {
+                        self.textures.insert(&handle, texture);
+                        self.progress.loaded += counted as usize;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to upload texture {:?}: {:#}", label, e);
+                        self.textures.mark_failed(&handle);
+                        self.progress.failed += counted as usize;
+                    }
+                }
+            }
+            Decoded::TextureFailed { handle, error, counted } => {
+                log::error!("Failed to load texture: {:#}", error);
+                self.textures.mark_failed(&handle);
+                self.progress.failed += counted as usize;
+            }
+            Decoded::Model { handle, data, lods } => {
+                let materials = data.materials.iter().map(|m| self.material_for(m, device, layout)).collect::<Vec<_>>();
+                let needs_stud_material = lods.iter().flat_map(|(_, meshes)| meshes).any(|m| m.material == lod::STUD_CAP_MATERIAL);
+                let stud_material = needs_stud_material.then(|| self.material_for(&stud_material_data(), device, layout));
+                let material = |index: usize| match (index, &stud_material) {
+                    (lod::STUD_CAP_MATERIAL, Some(stud)) => stud.clone(),
+                    _ => materials.get(index).unwrap_or(&self.default_material).clone(),
+                };
+                //  Only the full model keeps its geometry, that's the one exporters and picking want
+                let lods = lods
+                    .iter()
+                    .map(|(level, meshes)| {
+                        let meshes = meshes.iter().map(|m| m.upload(device, material(m.material), false)).collect();
+                        (*level, model::Model::new(meshes))
+                    })
+                    .collect();
+                let model = data
+                    .upload(device, &materials, &self.default_material, self.keep_mesh_geometry)
+                    .with_lods(lods);
+                self.models.insert(&handle, model);
+                //  Counted as loaded once its textures are in, see resolve_models()
+                let mut materials = materials;
+                materials.extend(stud_material);
+                self.pending_models.push(PendingModel { materials });
+            }
+            Decoded::ModelFailed { handle, error } => {
+                log::error!("Failed to load model: {:#}", error);
+                self.models.mark_failed(&handle);
+                self.progress.failed += 1;
+            }
+        }
+    }
+
+    //  Shares the material if another model already created it, otherwise creates it with placeholder
+    //  textures and queues the real ones
+    fn material_for(
+        &mut self,
+        data: &resources::MaterialData,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Handle<model::Material> {
+        let (handle, is_new) = self.materials.reserve(&data.key);
+        if is_new {
+            let diffuse = match &data.diffuse_path {
+                Some(path) => self.request_texture(path, false, false),
+                None => self.default_diffuse.clone(),
+            };
+            let normal = match &data.normal_path {
+                Some(path) => self.request_texture(path, true, false),
+                None => self.default_normal.clone(),
+            };
+            let material = model::Material::new(
+                device,
+                &data.name,
+                self.texture(&self.placeholder_texture),
+                self.texture(&self.default_normal),
+                data.uniform,
+                layout,
+            );
+            self.materials.insert(&handle, material);
+            self.pending_materials.push(PendingMaterial {
+                handle: handle.clone(),
+                name: data.name.clone(),
+                diffuse,
+                normal,
+                uniform: data.uniform,
+            });
+        }
+        handle
+    }
+
+    //  What chunks are drawn with, their flat tops use the stud normal map the same way flat top LODs do
+    pub fn stud_cap_material(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Handle<model::Material> {
+        self.material_for(&stud_material_data(), device, layout)
+    }
+
+    //  Rebuilds materials once all of their textures have loaded (or failed, then the placeholder stays in)
+    fn resolve_materials(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) {
+        let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_materials)
+            .into_iter()
+            .partition(|m| {
+                self.textures.state(&m.diffuse) != LoadState::Queued
+                    && self.textures.state(&m.normal) != LoadState::Queued
+            });
+        self.pending_materials = waiting;
+
+        for pending in ready {
+            let material = model::Material::new(
+                device,
+                &pending.name,
+                self.texture(&pending.diffuse),
+                self.normal_texture(&pending.normal),
+                pending.uniform,
+                layout,
+            ).with_textures(vec![pending.diffuse, pending.normal]);
+            self.materials.insert(&pending.handle, material);
+        }
+    }
+
+    fn resolve_models(&mut self) {
+        let pending_materials = &self.pending_materials;
+        let before = self.pending_models.len();
+        self.pending_models.retain(|model| {
+            model.materials.iter().any(|m| pending_materials.iter().any(|p| &p.handle == m))
+        });
+        self.progress.loaded += before - self.pending_models.len();
+    }
+
+    //  Drops every model, material and texture that nothing holds a handle to anymore.
+    //  Models go first since they hold the material handles, which hold the texture handles.
+    pub fn evict_unused(&mut self) -> usize {
+        self.models.evict_unused() + self.materials.evict_unused() + self.textures.evict_unused()
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    //  Falls back to the placeholder while loading or if the load failed
+    pub fn texture(&self, handle: &Handle<texture::Texture>) -> &texture::Texture {
+        self.textures.get(handle).unwrap_or_else(|| self.textures.get(&self.placeholder_texture).unwrap())
+    }
+
+    //  Same as texture() but with a flat normal map as the placeholder
+    pub fn normal_texture(&self, handle: &Handle<texture::Texture>) -> &texture::Texture {
+        self.textures.get(handle).unwrap_or_else(|| self.textures.get(&self.default_normal).unwrap())
+    }
+
+    pub fn material(&self, handle: &Handle<model::Material>) -> &model::Material {
+        self.materials.get(handle).unwrap_or_else(|| self.materials.get(&self.placeholder_material).unwrap())
+    }
+
+    pub fn model(&self, handle: &Handle<model::Model>) -> &model::Model {
+        self.models.get(handle).unwrap_or_else(|| self.models.get(&self.placeholder_model).unwrap())
+    }
+}
+
+impl Drop for AssetManager {
+    //  Closes the request channel and waits for the workers, after taking away whatever they hadn't started on
+    fn drop(&mut self) {
+        self.request_sender = None;
+        while self.request_receiver.lock().unwrap().try_recv().is_ok() {}
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+//  Magenta/black checkerboard, the universal "this texture is missing" texture
+pub fn checkerboard_image() -> image::DynamicImage {
+    const SIZE: u32 = 8;
+    let image = image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        if (x + y) % 2 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    });
+    image::DynamicImage::ImageRgba8(image)
+}
+
+//  What flat top LODs are drawn with, the stud normal map stands in for the studs that were taken off
+fn stud_material_data() -> resources::MaterialData {
+    resources::MaterialData {
+        name: "stud cap".to_string(),
+        key: "builtin/stud_cap".to_string(),
+        diffuse_path: None,
+        normal_path: Some("bricks/stud-normal.png".to_string()),
+        uniform: MaterialUniform::default(),
+    }
+}
+
+//  Unit cube from -1 to 1 with one quad per face, matching res/bricks/cube.obj
+pub fn cube_mesh(name: &str) -> model::MeshData {
+    //  (normal, tangent, bitangent) for each face
+    let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (normal, tangent, bitangent) in faces {
+        let base = vertices.len() as u32;
+        for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let (su, sv) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+            let position = [
+                normal[0] + tangent[0] * su + bitangent[0] * sv,
+                normal[1] + tangent[1] * su + bitangent[1] * sv,
+                normal[2] + tangent[2] * su + bitangent[2] * sv,
+            ];
+            vertices.push(model::ModelVertex {
+                position,
+                //  wgpu tex coords have v pointing down
+                tex_coords: [u, 1.0 - v],
+                normal,
+                tangent,
+                bitangent,
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    model::MeshData {
+        name: name.to_string(),
+        vertices,
+        indices,
+        material: 0,
+    }
+}
@@ -0,0 +1,202 @@
+//  ASSET STORE - Holds every loaded asset of one type, keyed by canonical path so the same file is only ever loaded once.
+//  Handles are reference counted, once the last handle to an asset is dropped it can be evicted with evict_unused().
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Weak};
+
+use crate::engine::vfs;
+
+#[derive(Debug)]
+struct HandleId {
+    index: usize,
+}
+
+pub struct Handle<T> {
+    id: Arc<HandleId>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn index(&self) -> usize {
+        self.id.index
+    }
+}
+
+//  Derives would put bounds on T, which the handle doesn't need
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id.clone(), _marker: PhantomData }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.id, &other.id)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.id.index)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    Queued,
+    Loaded,
+    Failed,
+}
+
+struct Entry<T> {
+    //  The store only keeps a weak reference, so the strong count is the number of handles out there
+    id: Weak<HandleId>,
+    path: Option<String>,
+    state: LoadState,
+    asset: Option<T>,
+}
+
+pub struct Assets<T> {
+    entries: Vec<Option<Entry<T>>>,
+    free: Vec<usize>,
+    by_path: HashMap<String, usize>,
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            free: Vec::new(),
+            by_path: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn allocate(&mut self, path: Option<String>, state: LoadState, asset: Option<T>) -> Handle<T> {
+        let index = self.free.pop().unwrap_or(self.entries.len());
+        let id = Arc::new(HandleId { index });
+        let entry = Entry { id: Arc::downgrade(&id), path: path.clone(), state, asset };
+        if index == self.entries.len() {
+            self.entries.push(Some(entry));
+        } else {
+            self.entries[index] = Some(entry);
+        }
+        if let Some(path) = path {
+            self.by_path.insert(path, index);
+        }
+        Handle { id, _marker: PhantomData }
+    }
+
+    fn entry(&self, handle: &Handle<T>) -> &Entry<T> {
+        //  A live handle keeps its entry from being evicted, so it's always there
+        self.entries[handle.index()].as_ref().expect("Handle used with the wrong asset store")
+    }
+
+    fn entry_mut(&mut self, handle: &Handle<T>) -> &mut Entry<T> {
+        self.entries[handle.index()].as_mut().expect("Handle used with the wrong asset store")
+    }
+
+    //  Adds an asset that didn't come from a file (placeholders, generated meshes...)
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        self.allocate(None, LoadState::Loaded, Some(asset))
+    }
+
+    pub fn add_with_path(&mut self, path: &str, asset: T) -> Handle<T> {
+        self.allocate(Some(vfs::normalize_path(path)), LoadState::Loaded, Some(asset))
+    }
+
+    //  Returns the existing handle for `path` if there is one, otherwise reserves an empty slot to be filled in later.
+    //  The bool is true when a new slot was reserved, i.e. the caller should start loading it.
+    pub fn reserve(&mut self, path: &str) -> (Handle<T>, bool) {
+        let path = vfs::normalize_path(path);
+        match self.revive(&path) {
+            Some(handle) => (handle, false),
+            None => (self.allocate(Some(path), LoadState::Queued, None), true),
+        }
+    }
+
+    pub fn get_handle(&mut self, path: &str) -> Option<Handle<T>> {
+        self.revive(&vfs::normalize_path(path))
+    }
+
+    //  Nobody may be using it but if it hasn't been evicted yet it's handed out again instead of being loaded twice
+    fn revive(&mut self, path: &str) -> Option<Handle<T>> {
+        let index = *self.by_path.get(path)?;
+        let entry = self.entries[index].as_mut()?;
+        let id = entry.id.upgrade().unwrap_or_else(|| {
+            let id = Arc::new(HandleId { index });
+            entry.id = Arc::downgrade(&id);
+            id
+        });
+        Some(Handle { id, _marker: PhantomData })
+    }
+
+    pub fn insert(&mut self, handle: &Handle<T>, asset: T) {
+        let entry = self.entry_mut(handle);
+        entry.asset = Some(asset);
+        entry.state = LoadState::Loaded;
+    }
+
+    pub fn mark_failed(&mut self, handle: &Handle<T>) {
+        self.entry_mut(handle).state = LoadState::Failed;
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entry(handle).asset.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entry_mut(handle).asset.as_mut()
+    }
+
+    pub fn state(&self, handle: &Handle<T>) -> LoadState {
+        self.entry(handle).state
+    }
+
+    pub fn path(&self, handle: &Handle<T>) -> Option<&str> {
+        self.entry(handle).path.as_deref()
+    }
+
+    //  Number of live handles to the asset, including `handle` itself
+    pub fn ref_count(&self, handle: &Handle<T>) -> usize {
+        Arc::strong_count(&handle.id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //  Drops every asset nobody holds a handle to. Queued assets are kept since a worker is still producing them.
+    //  Returns how many were evicted.
+    pub fn evict_unused(&mut self) -> usize {
+        let mut evicted = 0;
+        for index in 0..self.entries.len() {
+            let unused = match &self.entries[index] {
+                Some(entry) => entry.id.strong_count() == 0 && entry.state != LoadState::Queued,
+                None => false,
+            };
+            if unused {
+                if let Some(path) = self.entries[index].take().and_then(|e| e.path) {
+                    if self.by_path.get(&path) == Some(&index) {
+                        self.by_path.remove(&path);
+                    }
+                }
+                self.free.push(index);
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+}
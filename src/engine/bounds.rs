@@ -0,0 +1,158 @@
+//  BOUNDS - Axis aligned boxes and bounding spheres for meshes and models, used for culling, picking and framing.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector2, Vector3, Vector4};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    //  Inverted so that growing it by any point gives a box around just that point
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Point3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Self {
+        let mut aabb = Self::empty();
+        for p in points {
+            aabb.grow(p);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, p: Point3<f32>) {
+        self.min = Point3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+        self.max = Point3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut aabb = *self;
+        if !other.is_empty() {
+            aabb.grow(other.min);
+            aabb.grow(other.max);
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    //  Half the size along each axis
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    //  The box around this one after it's been moved by `transform`, which is bigger than it if there's a rotation
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        Aabb::from_points(self.corners().map(|c| transform.transform_point(c)))
+    }
+
+    //  How far along `ray` it first enters the box, 0 if it starts inside
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+        let (mut near, mut far) = (0.0f32, f32::MAX);
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let a = (self.min[axis] - ray.origin[axis]) * inverse;
+            let b = (self.max[axis] - ray.origin[axis]) * inverse;
+            //  NaN when the ray runs along a face, which the min/max calls ignore
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    //  From the near plane through a point on screen given in normalized device coordinates
+    pub fn from_ndc(inv_view_proj: &Matrix4<f32>, ndc: Vector2<f32>) -> Self {
+        let unproject = |z: f32| Point3::from_homogeneous(inv_view_proj * Vector4::new(ndc.x, ndc.y, z, 1.0));
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        Self { origin: near, direction: (far - near).normalize() }
+    }
+
+    //  Distances along the ray only carry over if `transform` doesn't scale
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Ray {
+        Self {
+            origin: transform.transform_point(self.origin),
+            direction: transform.transform_vector(self.direction),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    //  Centred on the box rather than the tightest possible fit, close enough for bricks and much cheaper
+    pub fn from_points(points: &[Point3<f32>], aabb: &Aabb) -> Self {
+        let center = if aabb.is_empty() { Point3::origin() } else { aabb.center() };
+        let radius = points.iter().map(|p| p.distance2(center)).fold(0.0, f32::max).sqrt();
+        Self { center, radius }
+    }
+
+    //  The sphere through the box's corners
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        if aabb.is_empty() {
+            return Self { center: Point3::origin(), radius: 0.0 };
+        }
+        Self { center: aabb.center(), radius: aabb.half_extents().magnitude() }
+    }
+
+    //  A sphere around all of `spheres`, centred on `center`
+    pub fn enclosing(center: Point3<f32>, spheres: &[BoundingSphere]) -> Self {
+        let radius = spheres.iter().map(|s| s.center.distance(center) + s.radius).fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> BoundingSphere {
+        //  Non-uniform scale stretches the sphere, the largest axis still contains it
+        let scale = [transform.x, transform.y, transform.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0, f32::max);
+        Self {
+            center: transform.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
@@ -0,0 +1,538 @@
+//  CHUNK MESH - Bakes every brick in a chunk into one vertex/index buffer so a whole chunk is a single draw.
+//  Faces against a neighbouring brick are left out, and what's left of a partly covered face is merged back into as
+//  few rectangles as possible. Meshing happens on a worker thread, only the upload is done on the main thread.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+use cgmath::Point3;
+use wgpu::util::DeviceExt;
+
+use crate::engine::bounds::Aabb;
+use crate::engine::lod::{Lod, LOD_COUNT};
+use crate::engine::model::{Material, MeshData, ModelVertex, Vertex};
+use crate::game::world::{Brick, ChunkCoord, ChunkSnapshot, PLATE_HEIGHT, STUD_SIZE};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkVertex {
+    //  World space, the chunk's position is already baked in
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 4],
+    //  In studs, so the stud normal map repeats once per stud
+    pub tex_coords: [f32; 2],
+    //  1 on flat tops where the stud normal map stands in for the studs, 0 everywhere else
+    pub stud_normal: f32,
+}
+
+impl Vertex for ChunkVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ChunkVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+//  CPU side result of meshing a chunk at one LOD level. Box faces are quads of 4 vertices and 6 indices, studs aren't.
+#[derive(Debug, Clone)]
+pub struct ChunkMeshData {
+    pub coord: ChunkCoord,
+    pub lod: Lod,
+    pub vertices: Vec<ChunkVertex>,
+    pub indices: Vec<u32>,
+    pub aabb: Aabb,
+}
+
+impl ChunkMeshData {
+    //  Only means something for the levels without studs
+    pub fn face_count(&self) -> usize {
+        self.indices.len() / 6
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+//  Every level of a chunk, they're all built at once so switching level never has to wait for the mesher
+#[derive(Debug, Clone)]
+pub struct ChunkLods {
+    pub coord: ChunkCoord,
+    pub levels: [ChunkMeshData; LOD_COUNT],
+}
+
+impl ChunkLods {
+    pub fn level(&self, lod: Lod) -> &ChunkMeshData {
+        &self.levels[lod.index()]
+    }
+
+    //  Bounds of the most detailed level, which is the biggest since it has the studs
+    pub fn aabb(&self) -> Aabb {
+        self.levels[0].aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(|l| l.is_empty())
+    }
+}
+
+//  +x, -x, +y, -y, +z, -z
+const DIRECTIONS: [(usize, i32); 6] = [(0, 1), (0, -1), (1, 1), (1, -1), (2, 1), (2, -1)];
+//  Real bricks have 4.8mm studs 1.7mm tall on an 8mm grid
+const STUD_RADIUS: f32 = 0.3 * STUD_SIZE;
+const STUD_HEIGHT: f32 = 0.2125 * STUD_SIZE;
+const STUD_SEGMENTS: usize = 12;
+
+pub fn mesh_chunk_lods(snapshot: &ChunkSnapshot) -> ChunkLods {
+    ChunkLods {
+        coord: snapshot.coord,
+        levels: Lod::ALL.map(|lod| mesh_chunk(snapshot, lod)),
+    }
+}
+
+//  Full and flat top mesh brick by brick so the seams between bricks stay, impostors merge faces across bricks of the
+//  same colour and ignore the seams.
+pub fn mesh_chunk(snapshot: &ChunkSnapshot, lod: Lod) -> ChunkMeshData {
+    let mut mesh = ChunkMeshData {
+        coord: snapshot.coord,
+        lod,
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        aabb: Aabb::empty(),
+    };
+    match lod {
+        Lod::Full | Lod::FlatTop => {
+            for brick in &snapshot.bricks {
+                for &(axis, sign) in &DIRECTIONS {
+                    mesh_face(snapshot, brick, axis, sign, lod, &mut mesh);
+                }
+            }
+        }
+        Lod::Impostor => mesh_merged(snapshot, &mut mesh),
+    }
+    mesh.aabb = Aabb::from_points(mesh.vertices.iter().map(|v| Point3::from(v.position)));
+    mesh
+}
+
+//  A lone brick of `size` cells with its lowest corner at the origin, for exporting world bricks. Flat tops so the box
+//  stays closed and can be baked for printing.
+pub fn brick_mesh_data(size: [i32; 3]) -> MeshData {
+    let brick = Brick::new([0; 3], size, [1.0; 4]);
+    let snapshot = ChunkSnapshot {
+        coord: brick.chunk(),
+        bricks: vec![brick],
+        occupied: brick.cells().collect(),
+    };
+    let mesh = mesh_chunk(&snapshot, Lod::FlatTop);
+    let vertices = mesh.vertices.iter()
+        .map(|v| {
+            let axis = (0..3).find(|&a| v.normal[a] != 0.0).unwrap_or(1);
+            let mut tangent = [0.0; 3];
+            tangent[(axis + 1) % 3] = 1.0;
+            let n = cgmath::Vector3::from(v.normal);
+            ModelVertex {
+                position: v.position,
+                tex_coords: v.tex_coords,
+                normal: v.normal,
+                tangent,
+                bitangent: n.cross(tangent.into()).into(),
+            }
+        })
+        .collect();
+    MeshData {
+        name: format!("brick_{}x{}x{}", size[0], size[1], size[2]),
+        vertices,
+        indices: mesh.indices,
+        material: 0,
+    }
+}
+
+//  The cell just outside side (axis, sign) of a brick, at (i, j) along the side
+fn outside_cell(brick: &Brick, axis: usize, sign: i32, i: usize, j: usize) -> [i32; 3] {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut cell = [0; 3];
+    cell[axis] = if sign > 0 { brick.position[axis] + brick.size[axis] } else { brick.position[axis] - 1 };
+    cell[u] = brick.position[u] + i as i32;
+    cell[v] = brick.position[v] + j as i32;
+    cell
+}
+
+//  One side of a brick. Works out which of the cells along it are uncovered, then greedily grows rectangles over them.
+fn mesh_face(snapshot: &ChunkSnapshot, brick: &Brick, axis: usize, sign: i32, lod: Lod, mesh: &mut ChunkMeshData) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (width, height) = (brick.size[u] as usize, brick.size[v] as usize);
+    let is_top = axis == 1 && sign > 0;
+
+    let mut exposed = vec![None; width * height];
+    for j in 0..height {
+        for i in 0..width {
+            let cell = outside_cell(brick, axis, sign, i, j);
+            if !snapshot.occupied.contains(&cell) {
+                exposed[j * width + i] = Some(());
+                if is_top && lod == Lod::Full {
+                    push_stud(mesh, cell, brick.color);
+                }
+            }
+        }
+    }
+
+    let plane = if sign > 0 { brick.position[axis] + brick.size[axis] } else { brick.position[axis] };
+    let stud_normal = if is_top && lod == Lod::FlatTop { 1.0 } else { 0.0 };
+    greedy_rects(&mut exposed, width, height, |i, j, w, h, ()| {
+        let u0 = brick.position[u] + i as i32;
+        let v0 = brick.position[v] + j as i32;
+        push_quad(mesh, axis, sign, plane, [u0, u0 + w as i32], [v0, v0 + h as i32], brick.color, stud_normal);
+    });
+}
+
+//  Colours as bits so they can be compared and hashed
+type ColorKey = [u32; 4];
+
+//  Every uncovered cell side in the chunk, grouped by the plane it's in and merged with its neighbours of the same colour
+fn mesh_merged(snapshot: &ChunkSnapshot, mesh: &mut ChunkMeshData) {
+    let mut planes: HashMap<(usize, i32, i32), HashMap<[i32; 2], ColorKey>> = HashMap::new();
+    let mut colors = HashMap::new();
+    for brick in &snapshot.bricks {
+        let key = brick.color.map(f32::to_bits);
+        colors.insert(key, brick.color);
+        for &(axis, sign) in &DIRECTIONS {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for j in 0..brick.size[v] as usize {
+                for i in 0..brick.size[u] as usize {
+                    let cell = outside_cell(brick, axis, sign, i, j);
+                    if !snapshot.occupied.contains(&cell) {
+                        let plane = if sign > 0 { cell[axis] } else { cell[axis] + 1 };
+                        planes.entry((axis, sign, plane)).or_default().insert([cell[u], cell[v]], key);
+                    }
+                }
+            }
+        }
+    }
+
+    //  Sorted so the same chunk always produces the same mesh
+    let mut keys = planes.keys().copied().collect::<Vec<_>>();
+    keys.sort_unstable();
+    for key in keys {
+        let (axis, sign, plane) = key;
+        let cells = &planes[&key];
+        let min = cells.keys().fold([i32::MAX; 2], |m, c| [m[0].min(c[0]), m[1].min(c[1])]);
+        let max = cells.keys().fold([i32::MIN; 2], |m, c| [m[0].max(c[0]), m[1].max(c[1])]);
+        let (width, height) = ((max[0] - min[0] + 1) as usize, (max[1] - min[1] + 1) as usize);
+        let mut grid = vec![None; width * height];
+        for (c, color) in cells {
+            grid[(c[1] - min[1]) as usize * width + (c[0] - min[0]) as usize] = Some(*color);
+        }
+        greedy_rects(&mut grid, width, height, |i, j, w, h, color| {
+            let u0 = min[0] + i as i32;
+            let v0 = min[1] + j as i32;
+            push_quad(mesh, axis, sign, plane, [u0, u0 + w as i32], [v0, v0 + h as i32], colors[&color], 0.0);
+        });
+    }
+}
+
+//  Covers the filled cells of a width x height grid with as few rectangles of matching cells as it can, row by row.
+//  Calls `emit` with (column, row, width, height, value) for each and empties the grid as it goes.
+fn greedy_rects<K: Copy + PartialEq>(
+    grid: &mut [Option<K>],
+    width: usize,
+    height: usize,
+    mut emit: impl FnMut(usize, usize, usize, usize, K),
+) {
+    for j in 0..height {
+        let mut i = 0;
+        while i < width {
+            let Some(value) = grid[j * width + i] else {
+                i += 1;
+                continue;
+            };
+            let mut w = 1;
+            while i + w < width && grid[j * width + i + w] == Some(value) {
+                w += 1;
+            }
+            let mut h = 1;
+            while j + h < height && (i..i + w).all(|x| grid[(j + h) * width + x] == Some(value)) {
+                h += 1;
+            }
+            for y in j..j + h {
+                grid[y * width + i..y * width + i + w].fill(None);
+            }
+            emit(i, j, w, h, value);
+            i += w;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn push_quad(
+    mesh: &mut ChunkMeshData,
+    axis: usize,
+    sign: i32,
+    plane: i32,
+    us: [i32; 2],
+    vs: [i32; 2],
+    color: [f32; 4],
+    stud_normal: f32,
+) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let scale = [STUD_SIZE, PLATE_HEIGHT, STUD_SIZE];
+    let corner = |cu: i32, cv: i32| {
+        let mut p = [0.0; 3];
+        p[axis] = plane as f32 * scale[axis];
+        p[u] = cu as f32 * scale[u];
+        p[v] = cv as f32 * scale[v];
+        p
+    };
+    let mut normal = [0.0; 3];
+    normal[axis] = sign as f32;
+
+    //  u, v, axis is a right handed basis, so going round u then v is counter clockwise seen from the +axis side
+    let corners = [(us[0], vs[0]), (us[1], vs[0]), (us[1], vs[1]), (us[0], vs[1])];
+    let start = mesh.vertices.len() as u32;
+    mesh.vertices.extend(corners.iter().map(|&(cu, cv)| {
+        let position = corner(cu, cv);
+        ChunkVertex {
+            position,
+            normal,
+            color,
+            tex_coords: [position[0] / STUD_SIZE, position[2] / STUD_SIZE],
+            stud_normal,
+        }
+    }));
+    let order: [u32; 6] = if sign > 0 { [0, 1, 2, 0, 2, 3] } else { [0, 2, 1, 0, 3, 2] };
+    mesh.indices.extend(order.iter().map(|i| start + i));
+}
+
+//  A cylinder on top of `cell`, which is the empty cell above a brick's top face
+fn push_stud(mesh: &mut ChunkMeshData, cell: [i32; 3], color: [f32; 4]) {
+    let center = [(cell[0] as f32 + 0.5) * STUD_SIZE, cell[1] as f32 * PLATE_HEIGHT, (cell[2] as f32 + 0.5) * STUD_SIZE];
+    let vertex = |angle: f32, y: f32, normal: [f32; 3]| {
+        let (sin, cos) = angle.sin_cos();
+        let position = [center[0] + cos * STUD_RADIUS, center[1] + y, center[2] + sin * STUD_RADIUS];
+        ChunkVertex {
+            position,
+            normal,
+            color,
+            tex_coords: [position[0] / STUD_SIZE, position[2] / STUD_SIZE],
+            stud_normal: 0.0,
+        }
+    };
+    let angle = |i: usize| i as f32 / STUD_SEGMENTS as f32 * std::f32::consts::TAU;
+
+    //  Sides, smooth shaded so a dozen segments look round
+    let start = mesh.vertices.len() as u32;
+    for i in 0..=STUD_SEGMENTS {
+        let (sin, cos) = angle(i).sin_cos();
+        mesh.vertices.push(vertex(angle(i), 0.0, [cos, 0.0, sin]));
+        mesh.vertices.push(vertex(angle(i), STUD_HEIGHT, [cos, 0.0, sin]));
+    }
+    for i in 0..STUD_SEGMENTS as u32 {
+        let (bottom, top) = (start + i * 2, start + i * 2 + 1);
+        let (next_bottom, next_top) = (bottom + 2, top + 2);
+        mesh.indices.extend_from_slice(&[bottom, top, next_top, bottom, next_top, next_bottom]);
+    }
+
+    //  Top, a fan around the rim
+    let start = mesh.vertices.len() as u32;
+    mesh.vertices.extend((0..STUD_SEGMENTS).map(|i| vertex(angle(i), STUD_HEIGHT, [0.0, 1.0, 0.0])));
+    for i in 1..STUD_SEGMENTS as u32 - 1 {
+        mesh.indices.extend_from_slice(&[start, start + i + 1, start + i]);
+    }
+}
+
+//  Hands snapshots to the worker thread and collects the finished meshes. A chunk that's edited again before its
+//  mesh comes back is just meshed again, the stale result is dropped when it arrives.
+pub struct ChunkMesher {
+    job_sender: Option<mpsc::Sender<(u64, ChunkSnapshot)>>,
+    result_receiver: mpsc::Receiver<(u64, ChunkLods)>,
+    //  Only used when there's no worker thread (wasm), jobs are meshed inline in poll() instead
+    inline_jobs: Vec<(u64, ChunkSnapshot)>,
+    //  The newest job handed out for each chunk, results from older ones are ignored
+    latest: HashMap<ChunkCoord, u64>,
+    next_job: u64,
+}
+
+impl ChunkMesher {
+    pub fn new() -> Self {
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_sender = if cfg!(target_arch = "wasm32") {
+            None
+        } else {
+            let (job_sender, job_receiver) = mpsc::channel::<(u64, ChunkSnapshot)>();
+            std::thread::Builder::new()
+                .name("chunk-mesher".to_string())
+                .spawn(move || {
+                    for (job, snapshot) in job_receiver {
+                        if result_sender.send((job, mesh_chunk_lods(&snapshot))).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("Failed to spawn chunk mesher");
+            Some(job_sender)
+        };
+        Self {
+            job_sender,
+            result_receiver,
+            inline_jobs: Vec::new(),
+            latest: HashMap::new(),
+            next_job: 0,
+        }
+    }
+
+    pub fn submit(&mut self, snapshot: ChunkSnapshot) {
+        let job = self.next_job;
+        self.next_job += 1;
+        self.latest.insert(snapshot.coord, job);
+        match &self.job_sender {
+            Some(sender) => sender.send((job, snapshot)).expect("Chunk mesher thread died"),
+            None => self.inline_jobs.push((job, snapshot)),
+        }
+    }
+
+    //  Chunks submitted but not back yet
+    pub fn pending(&self) -> usize {
+        self.latest.len()
+    }
+
+    //  The meshes finished since the last call, only the newest for each chunk. Never blocks.
+    pub fn poll(&mut self) -> Vec<ChunkLods> {
+        let mut finished = self.inline_jobs
+            .drain(..)
+            .map(|(job, snapshot)| (job, mesh_chunk_lods(&snapshot)))
+            .collect::<Vec<_>>();
+        finished.extend(self.result_receiver.try_iter());
+        finished
+            .into_iter()
+            .filter_map(|(job, lods)| {
+                if self.latest.get(&lods.coord) != Some(&job) {
+                    return None;
+                }
+                self.latest.remove(&lods.coord);
+                Some(lods)
+            })
+            .collect()
+    }
+}
+
+impl Default for ChunkMesher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//  One LOD level of a chunk on the GPU
+pub struct ChunkLevel {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+}
+
+//  A chunk's meshes on the GPU, every level plus the one it was last drawn at
+pub struct ChunkMesh {
+    pub levels: Vec<ChunkLevel>,
+    pub aabb: Aabb,
+    pub lod: Lod,
+}
+
+impl ChunkMesh {
+    pub fn upload(device: &wgpu::Device, lods: &ChunkLods) -> Self {
+        let label = format!("Chunk ({}, {})", lods.coord.x, lods.coord.z);
+        let levels = lods
+            .levels
+            .iter()
+            .map(|data| {
+                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} {:?} Vertex Buffer", label, data.lod)),
+                    contents: bytemuck::cast_slice(&data.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} {:?} Index Buffer", label, data.lod)),
+                    contents: bytemuck::cast_slice(&data.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+                ChunkLevel {
+                    vertex_buffer,
+                    index_buffer,
+                    num_elements: data.indices.len() as u32,
+                }
+            })
+            .collect();
+        Self {
+            levels,
+            aabb: lods.aabb(),
+            lod: Lod::Full,
+        }
+    }
+
+    pub fn level(&self, lod: Lod) -> &ChunkLevel {
+        &self.levels[lod.index()]
+    }
+}
+
+pub trait DrawChunk<'a> {
+    fn draw_chunk(
+        &mut self,
+        chunk: &'a ChunkMesh,
+        lod: Lod,
+        stud_material: &'a Material,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawChunk<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_chunk(
+        &mut self,
+        chunk: &'b ChunkMesh,
+        lod: Lod,
+        stud_material: &'b Material,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        let level = chunk.level(lod);
+        self.set_vertex_buffer(0, level.vertex_buffer.slice(..));
+        self.set_index_buffer(level.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_bind_group(0, camera_bind_group, &[]);
+        self.set_bind_group(1, light_bind_group, &[]);
+        self.set_bind_group(2, &stud_material.bind_group, &[]);
+        self.draw_indexed(0..level.num_elements, 0, 0..1);
+    }
+}
@@ -0,0 +1,216 @@
+//  CULLING - Works out which instances the camera can see so only those get drawn.
+//  Instances are kept in a bounding volume hierarchy so whole groups of them can be rejected (or accepted) with one test.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::engine::bounds::{Aabb, BoundingSphere, Ray};
+
+//  Instances per leaf, below this it's cheaper to just test them than to split further
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+//  Points with dot(normal, p) + distance >= 0 are on the inside
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    fn from_row(row: Vector4<f32>) -> Self {
+        let length = row.truncate().magnitude();
+        Self {
+            normal: row.truncate() / length,
+            distance: row.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, p: Point3<f32>) -> f32 {
+        self.normal.dot(p.to_vec()) + self.distance
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    //  Left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    //  Gribb/Hartmann plane extraction, for wgpu's 0..1 clip space depth
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let m = view_proj.transpose();
+        let (r0, r1, r2, r3) = (m.x, m.y, m.z, m.w);
+        Self {
+            planes: [
+                Plane::from_row(r3 + r0),
+                Plane::from_row(r3 - r0),
+                Plane::from_row(r3 + r1),
+                Plane::from_row(r3 - r1),
+                Plane::from_row(r2),
+                Plane::from_row(r3 - r2),
+            ],
+        }
+    }
+
+    pub fn test_aabb(&self, aabb: &Aabb) -> Containment {
+        let mut result = Containment::Inside;
+        for plane in &self.planes {
+            //  The corner furthest along the plane normal, and the one furthest against it
+            let pick = |towards: bool| Point3::new(
+                if (plane.normal.x >= 0.0) == towards { aabb.max.x } else { aabb.min.x },
+                if (plane.normal.y >= 0.0) == towards { aabb.max.y } else { aabb.min.y },
+                if (plane.normal.z >= 0.0) == towards { aabb.max.z } else { aabb.min.z },
+            );
+            if plane.signed_distance(pick(true)) < 0.0 {
+                return Containment::Outside;
+            }
+            if plane.signed_distance(pick(false)) < 0.0 {
+                result = Containment::Intersecting;
+            }
+        }
+        result
+    }
+
+    pub fn test_sphere(&self, sphere: &BoundingSphere) -> Containment {
+        let mut result = Containment::Inside;
+        for plane in &self.planes {
+            let d = plane.signed_distance(sphere.center);
+            if d < -sphere.radius {
+                return Containment::Outside;
+            }
+            if d < sphere.radius {
+                result = Containment::Intersecting;
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    Leaf,
+    Branch { left: usize, right: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    aabb: Aabb,
+    //  Every node covers a contiguous range of `items`, so a node that's entirely visible can be added in one go
+    start: usize,
+    end: usize,
+    kind: NodeKind,
+}
+
+//  Bounding volume hierarchy over instance bounds, split at the median along the longest axis
+#[derive(Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    //  (world bounds, instance index), reordered while building
+    items: Vec<(Aabb, usize)>,
+}
+
+impl Bvh {
+    //  `bounds` is the world space bounds of each instance, the indices are what queries return
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() / LEAF_SIZE * 2 + 1),
+            items: bounds.iter().copied().zip(0..).collect(),
+        };
+        if !bvh.items.is_empty() {
+            bvh.build_node(0, bvh.items.len());
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, start: usize, end: usize) -> usize {
+        let items = &mut self.items[start..end];
+        let aabb = items.iter().fold(Aabb::empty(), |aabb, (b, _)| aabb.union(b));
+        let index = self.nodes.len();
+        self.nodes.push(Node { aabb, start, end, kind: NodeKind::Leaf });
+        if items.len() <= LEAF_SIZE {
+            return index;
+        }
+
+        //  Split along whichever axis the centres are most spread out on
+        let centers = Aabb::from_points(items.iter().map(|(b, _)| b.center()));
+        let extent = centers.max - centers.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |(a, _), (b, _)| a.center()[axis].total_cmp(&b.center()[axis]));
+
+        let left = self.build_node(start, start + mid);
+        let right = self.build_node(start + mid, end);
+        self.nodes[index].kind = NodeKind::Branch { left, right };
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    //  Appends the index of every instance that's at least partly inside the frustum. Order isn't preserved.
+    pub fn query(&self, frustum: &Frustum, visible: &mut Vec<usize>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            match frustum.test_aabb(&node.aabb) {
+                Containment::Outside => {}
+                Containment::Inside => visible.extend(self.items[node.start..node.end].iter().map(|&(_, i)| i)),
+                Containment::Intersecting => match node.kind {
+                    NodeKind::Branch { left, right } => {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                    NodeKind::Leaf => visible.extend(
+                        self.items[node.start..node.end]
+                            .iter()
+                            .filter(|(aabb, _)| frustum.test_aabb(aabb) != Containment::Outside)
+                            .map(|&(_, i)| i),
+                    ),
+                },
+            }
+        }
+    }
+
+    //  Appends the index of every instance whose bounds the ray passes through, for an exact test on just those.
+    //  Order isn't preserved.
+    pub fn query_ray(&self, ray: &Ray, hits: &mut Vec<usize>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if node.aabb.intersect_ray(ray).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Branch { left, right } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                NodeKind::Leaf => hits.extend(
+                    self.items[node.start..node.end]
+                        .iter()
+                        .filter(|(aabb, _)| aabb.intersect_ray(ray).is_some())
+                        .map(|&(_, i)| i),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//  DEBUG DRAW - Immediate mode lines for seeing what game code is doing. Anything can queue shapes on the global
+//  DebugDraw during a frame, the renderer draws them all as one line list at the end of the scene pass and then
+//  clears the queue, so shapes have to be queued again every frame to stay up.
+
+use std::sync::{Mutex, OnceLock};
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3};
+
+use crate::engine::bounds::Aabb;
+use crate::engine::model::Vertex;
+use crate::engine::texture;
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    //  Linear RGBA
+    pub color: [f32; 4],
+}
+
+impl Vertex for LineVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DebugDraw {
+    //  Pairs of vertices, one line each
+    depth_tested: Vec<LineVertex>,
+    on_top: Vec<LineVertex>,
+    depth_test: bool,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self { depth_tested: Vec::new(), on_top: Vec::new(), depth_test: true }
+    }
+}
+
+//  The queue the renderer draws from
+pub fn global() -> &'static Mutex<DebugDraw> {
+    static DEBUG_DRAW: OnceLock<Mutex<DebugDraw>> = OnceLock::new();
+    DEBUG_DRAW.get_or_init(Default::default)
+}
+
+impl DebugDraw {
+    //  Whether shapes queued after this are hidden behind the scene, or drawn over it. On again after every clear.
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test;
+    }
+
+    pub fn depth_tested_lines(&self) -> &[LineVertex] {
+        &self.depth_tested
+    }
+
+    pub fn on_top_lines(&self) -> &[LineVertex] {
+        &self.on_top
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.on_top.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.depth_tested.clear();
+        self.on_top.clear();
+        self.depth_test = true;
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        let lines = if self.depth_test { &mut self.depth_tested } else { &mut self.on_top };
+        lines.push(LineVertex { position: from.into(), color });
+        lines.push(LineVertex { position: to.into(), color });
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        if aabb.is_empty() {
+            return;
+        }
+        //  Corners are numbered by which of x, y and z are at the max, so edges join corners one bit apart
+        let corners = aabb.corners();
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    self.line(corners[a], corners[a | bit], color);
+                }
+            }
+        }
+    }
+
+    //  A circle around each axis
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        self.circle(center, Vector3::unit_x(), radius, color);
+        self.circle(center, Vector3::unit_y(), radius, color);
+        self.circle(center, Vector3::unit_z(), radius, color);
+    }
+
+    pub fn circle(&mut self, center: Point3<f32>, normal: Vector3<f32>, radius: f32, color: [f32; 4]) {
+        let (u, v) = perpendiculars(normal);
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    //  A line with a four pronged head at `to`, a fifth of its length
+    pub fn arrow(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        self.line(from, to, color);
+        let direction = to - from;
+        let length = direction.magnitude();
+        if length <= 0.0 {
+            return;
+        }
+        let back = direction / length * (length * 0.2);
+        let (u, v) = perpendiculars(direction);
+        for side in [u, -u, v, -v] {
+            self.line(to, to - back + side * (length * 0.1), color);
+        }
+    }
+
+    //  Lines every `spacing` across a square on the XZ plane, `half_size` out from the centre each way
+    pub fn grid(&mut self, center: Point3<f32>, half_size: f32, spacing: f32, color: [f32; 4]) {
+        if spacing <= 0.0 {
+            return;
+        }
+        let steps = (half_size / spacing).floor() as i32;
+        for i in -steps..=steps {
+            let offset = i as f32 * spacing;
+            self.line(center + Vector3::new(offset, 0.0, -half_size), center + Vector3::new(offset, 0.0, half_size), color);
+            self.line(center + Vector3::new(-half_size, 0.0, offset), center + Vector3::new(half_size, 0.0, offset), color);
+        }
+    }
+
+    //  The x, y and z axes of `transform` in red, green and blue, `length` long
+    pub fn axes(&mut self, transform: &Matrix4<f32>, length: f32) {
+        let origin = transform.transform_point(Point3::origin());
+        let axes = [
+            (Vector3::unit_x(), [1.0, 0.0, 0.0, 1.0]),
+            (Vector3::unit_y(), [0.0, 1.0, 0.0, 1.0]),
+            (Vector3::unit_z(), [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for (axis, color) in axes {
+            let direction = transform.transform_vector(axis);
+            if direction.magnitude2() > 0.0 {
+                self.line(origin, origin + direction.normalize_to(length), color);
+            }
+        }
+    }
+}
+
+//  Two unit vectors at right angles to `normal` and each other
+fn perpendiculars(normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let normal = normal.normalize();
+    let other = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let u = normal.cross(other).normalize();
+    (u, normal.cross(u))
+}
+
+//  Draws a DebugDraw's lines in the scene pass. The vertex buffer grows to fit and is reused between frames.
+pub struct DebugDrawRenderer {
+    depth_tested_pipeline: wgpu::RenderPipeline,
+    on_top_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    depth_tested_count: u32,
+    on_top_count: u32,
+}
+
+impl DebugDrawRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let (depth_tested_pipeline, on_top_pipeline) =
+            Self::create_pipelines(device, camera_layout, color_format, sample_count);
+        let capacity = 1024;
+        Self {
+            depth_tested_pipeline,
+            on_top_pipeline,
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            depth_tested_count: 0,
+            on_top_count: 0,
+        }
+    }
+
+    //  The pipelines have to match the scene pass's sample count
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        (self.depth_tested_pipeline, self.on_top_pipeline) =
+            Self::create_pipelines(device, camera_layout, color_format, sample_count);
+    }
+
+    //  Copies the lines queued so far for this frame's draw
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, debug_draw: &DebugDraw) {
+        let (depth_tested, on_top) = (debug_draw.depth_tested_lines(), debug_draw.on_top_lines());
+        let len = depth_tested.len() + on_top.len();
+        if len > self.capacity {
+            self.capacity = len.next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        let on_top_offset = std::mem::size_of_val(depth_tested) as wgpu::BufferAddress;
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(depth_tested));
+        queue.write_buffer(&self.buffer, on_top_offset, bytemuck::cast_slice(on_top));
+        self.depth_tested_count = depth_tested.len() as u32;
+        self.on_top_count = on_top.len() as u32;
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        if self.depth_tested_count + self.on_top_count == 0 {
+            return;
+        }
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        if self.depth_tested_count > 0 {
+            render_pass.set_pipeline(&self.depth_tested_pipeline);
+            render_pass.draw(0..self.depth_tested_count, 0..1);
+        }
+        if self.on_top_count > 0 {
+            render_pass.set_pipeline(&self.on_top_pipeline);
+            render_pass.draw(self.depth_tested_count..self.depth_tested_count + self.on_top_count, 0..1);
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Draw Buffer"),
+            size: (capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Draw Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/debug_draw.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[LineVertex::desc()] },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                //  Lines never write depth, so they don't hide each other or anything drawn after them
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState { count: sample_count, ..Default::default() },
+                multiview: None,
+            })
+        };
+        (
+            pipeline("Debug Draw Pipeline", wgpu::CompareFunction::LessEqual),
+            pipeline("Debug Draw On Top Pipeline", wgpu::CompareFunction::Always),
+        )
+    }
+}
@@ -0,0 +1,324 @@
+//  DEBUG VIEW - Render modes for checking assets: wireframe, tangent frames, a UV checker, world normals and depth.
+//  Most swap the scene shaders' fragment stage for a debug one. Wireframe uses PolygonMode::Line where the adapter
+//  has it, otherwise it and the tangent frames draw geometry built on the CPU from the model's kept meshes.
+
+use std::ops::Range;
+
+use cgmath::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::engine::chunk_mesh::ChunkVertex;
+use crate::engine::model::{MeshData, Model, ModelVertex, Vertex};
+use crate::engine::texture;
+use crate::game::instance::InstanceRaw;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Off,
+    Wireframe,
+    //  Normals, tangents and bitangents as blue, red and green lines over the normal scene
+    TangentFrames,
+    UvChecker,
+    //  World space normals after the normal map
+    Normals,
+    //  Distance from the camera
+    Depth,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Off,
+        DebugView::Wireframe,
+        DebugView::TangentFrames,
+        DebugView::UvChecker,
+        DebugView::Normals,
+        DebugView::Depth,
+    ];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    //  Everything but the tangent frames is drawn instead of the lit scene rather than over it
+    pub fn replaces_shading(self) -> bool {
+        !matches!(self, DebugView::Off | DebugView::TangentFrames)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    //  The colour for lines, barycentric coordinates for wireframes
+    pub data: [f32; 3],
+}
+
+impl Vertex for DebugVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+//  Every triangle with its own three corners, so each can be given barycentric coordinates
+pub fn wireframe_vertices(mesh: &MeshData) -> Vec<DebugVertex> {
+    const CORNERS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    mesh.indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            triangle.iter().zip(CORNERS).map(|(&index, data)| DebugVertex {
+                position: mesh.vertices[index as usize].position,
+                data,
+            })
+        })
+        .collect()
+}
+
+//  A line list of `length` long lines out of every vertex along its tangent (red), bitangent (green) and normal (blue)
+pub fn tangent_frame_vertices(mesh: &MeshData, length: f32) -> Vec<DebugVertex> {
+    let axes = |v: &ModelVertex| [(v.tangent, [1.0, 0.0, 0.0]), (v.bitangent, [0.0, 1.0, 0.0]), (v.normal, [0.0, 0.0, 1.0])];
+    mesh.vertices
+        .iter()
+        .flat_map(|v| {
+            axes(v).into_iter().flat_map(move |(axis, color)| {
+                let start = cgmath::Vector3::from(v.position);
+                let end = start + cgmath::Vector3::from(axis).normalize_to(length);
+                [DebugVertex { position: start.into(), data: color }, DebugVertex { position: end.into(), data: color }]
+            })
+        })
+        .collect()
+}
+
+struct DebugMesh {
+    wireframe: wgpu::Buffer,
+    wireframe_count: u32,
+    tangent_frames: wgpu::Buffer,
+    tangent_frame_count: u32,
+}
+
+//  [model, chunk] pipelines for each view that replaces the scene shading
+struct Pipelines {
+    wireframe: Option<[wgpu::RenderPipeline; 2]>,
+    uv_checker: [wgpu::RenderPipeline; 2],
+    normals: [wgpu::RenderPipeline; 2],
+    depth: [wgpu::RenderPipeline; 2],
+    mesh_wireframe: wgpu::RenderPipeline,
+    mesh_lines: wgpu::RenderPipeline,
+}
+
+pub struct DebugViews {
+    //  Whether the device was created with Features::POLYGON_MODE_LINE
+    polygon_mode_line: bool,
+    pipelines: Pipelines,
+    //  Built from the model last passed to set_model, empty if it didn't keep its geometry
+    meshes: Vec<DebugMesh>,
+}
+
+impl DebugViews {
+    //  `layouts` are the texture, camera and light bind group layouts, as for the scene pipelines
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let polygon_mode_line = device.features().contains(wgpu::Features::POLYGON_MODE_LINE);
+        Self {
+            polygon_mode_line,
+            pipelines: Pipelines::new(device, layouts, color_format, sample_count, polygon_mode_line),
+            meshes: Vec::new(),
+        }
+    }
+
+    //  The pipelines have to match the scene pass's sample count
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.pipelines = Pipelines::new(device, layouts, color_format, sample_count, self.polygon_mode_line);
+    }
+
+    //  Views drawn from the CPU built meshes, which can't use the GPU culler's indirect draws
+    pub fn draws_meshes(&self, view: DebugView) -> bool {
+        match view {
+            DebugView::Wireframe => !self.polygon_mode_line,
+            DebugView::TangentFrames => true,
+            _ => false,
+        }
+    }
+
+    //  The [model, chunk] pipelines to draw the scene with, None for the normal shading
+    pub fn scene_pipelines(&self, view: DebugView) -> Option<[&wgpu::RenderPipeline; 2]> {
+        let [model, chunk] = match view {
+            DebugView::Wireframe => self.pipelines.wireframe.as_ref()?,
+            DebugView::UvChecker => &self.pipelines.uv_checker,
+            DebugView::Normals => &self.pipelines.normals,
+            DebugView::Depth => &self.pipelines.depth,
+            DebugView::Off | DebugView::TangentFrames => return None,
+        };
+        Some([model, chunk])
+    }
+
+    //  Rebuilds the CPU side meshes, needs calling whenever the model changes
+    pub fn set_model(&mut self, device: &wgpu::Device, model: &Model) {
+        let Some(meshes) = model.mesh_data() else {
+            self.meshes.clear();
+            return;
+        };
+        //  Long enough to see, short enough not to cross the whole brick
+        let length = model.bounding_sphere.radius * 0.15;
+        let buffer = |label: &str, vertices: &[DebugVertex]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        };
+        self.meshes = meshes
+            .iter()
+            .map(|mesh| {
+                let wireframe = wireframe_vertices(mesh);
+                let tangent_frames = tangent_frame_vertices(mesh, length);
+                DebugMesh {
+                    wireframe: buffer("Debug Wireframe Buffer", &wireframe),
+                    wireframe_count: wireframe.len() as u32,
+                    tangent_frames: buffer("Debug Tangent Frame Buffer", &tangent_frames),
+                    tangent_frame_count: tangent_frames.len() as u32,
+                }
+            })
+            .collect();
+    }
+
+    //  The instances in `instance_buffer` at `ranges` with the CPU built meshes, for views where draws_meshes is true
+    pub fn draw_meshes<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        view: DebugView,
+        camera_bind_group: &'a wgpu::BindGroup,
+        instance_buffer: &'a wgpu::Buffer,
+        ranges: &[Range<u32>],
+    ) {
+        let pipeline = match view {
+            DebugView::TangentFrames => &self.pipelines.mesh_lines,
+            DebugView::Wireframe => &self.pipelines.mesh_wireframe,
+            _ => return,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        for mesh in &self.meshes {
+            let (buffer, count) = match view {
+                DebugView::TangentFrames => (&mesh.tangent_frames, mesh.tangent_frame_count),
+                _ => (&mesh.wireframe, mesh.wireframe_count),
+            };
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+            for range in ranges.iter().filter(|range| !range.is_empty()) {
+                render_pass.draw(0..count, range.clone());
+            }
+        }
+    }
+}
+
+impl Pipelines {
+    fn new(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        polygon_mode_line: bool,
+    ) -> Self {
+        let [texture_layout, camera_layout, light_layout] = *layouts;
+        let model_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Model Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/shader.wgsl").into()),
+        });
+        let chunk_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Chunk Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/chunk.wgsl").into()),
+        });
+        let mesh_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Mesh Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/debug.wgsl").into()),
+        });
+        let model_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Model Pipeline Layout"),
+            bind_group_layouts: &[texture_layout, camera_layout, light_layout],
+            push_constant_ranges: &[],
+        });
+        let chunk_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Chunk Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, light_layout, texture_layout],
+            push_constant_ranges: &[],
+        });
+        let mesh_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Mesh Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |layout: &wgpu::PipelineLayout,
+                        shader: &wgpu::ShaderModule,
+                        vertex_layouts: &[wgpu::VertexBufferLayout],
+                        entry_point: &str,
+                        primitive: wgpu::PrimitiveState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Debug View Pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState { module: shader, entry_point: "vs_main", buffers: vertex_layouts },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive,
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    //  So the tangent frames still show where they start on the surface
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState { count: sample_count, ..Default::default() },
+                multiview: None,
+            })
+        };
+        let filled = wgpu::PrimitiveState { cull_mode: Some(wgpu::Face::Back), ..Default::default() };
+        let scene = |entry_point: &str, primitive: wgpu::PrimitiveState| {
+            [
+                pipeline(&model_layout, &model_shader, &[ModelVertex::desc(), InstanceRaw::desc()], entry_point, primitive),
+                pipeline(&chunk_layout, &chunk_shader, &[ChunkVertex::desc()], entry_point, primitive),
+            ]
+        };
+        let mesh_layouts = [DebugVertex::desc(), InstanceRaw::desc()];
+        Self {
+            //  Both sides, so the back of the mesh shows through
+            wireframe: polygon_mode_line.then(|| {
+                scene("fs_debug_wireframe", wgpu::PrimitiveState { polygon_mode: wgpu::PolygonMode::Line, ..Default::default() })
+            }),
+            uv_checker: scene("fs_debug_uv", filled),
+            normals: scene("fs_debug_normal", filled),
+            depth: scene("fs_debug_depth", filled),
+            mesh_wireframe: pipeline(&mesh_layout, &mesh_shader, &mesh_layouts, "fs_wireframe", wgpu::PrimitiveState::default()),
+            mesh_lines: pipeline(
+                &mesh_layout,
+                &mesh_shader,
+                &mesh_layouts,
+                "fs_lines",
+                wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::LineList, ..Default::default() },
+            ),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//  DEFERRED - The deferred render path. Opaque bricks are drawn into a G-buffer of albedo, world normal, material and
+//  emissive, which one full screen pass then lights with every light at once. Transparent bricks can't go in the
+//  G-buffer, so they're drawn over the result by the same transparent pipeline the forward path uses.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3};
+
+use crate::engine::chunk_mesh::ChunkVertex;
+use crate::engine::model::{ModelVertex, Vertex};
+use crate::engine::post_process::Target;
+use crate::engine::texture;
+use crate::game::instance::InstanceRaw;
+
+pub const MAX_LIGHTS: usize = 64;
+const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const EMISSIVE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    //  How far the light reaches, 0 for everywhere without falling off
+    pub range: f32,
+    pub color: [f32; 3],
+    pub _padding: u32,
+}
+
+impl PointLight {
+    pub fn new(position: [f32; 3], color: [f32; 3], range: f32) -> Self {
+        Self { position, range, color, _padding: 0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    view_pos: [f32; 4],
+    light_count: u32,
+    _padding: [u32; 3],
+    lights: [PointLight; MAX_LIGHTS],
+}
+
+//  Everything that depends on the size of the screen
+struct GBuffer {
+    albedo: Target,
+    normal: Target,
+    material: Target,
+    emissive: Target,
+    bind_group: wgpu::BindGroup,
+}
+
+pub struct Deferred {
+    gbuffer_layout: wgpu::BindGroupLayout,
+    lighting_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    gbuffer: GBuffer,
+    //  Draw opaque bricks into the G-buffer
+    pub geometry_model: wgpu::RenderPipeline,
+    pub geometry_chunk: wgpu::RenderPipeline,
+}
+
+impl Deferred {
+    //  `layouts` are the texture, camera, light and ambient occlusion bind group layouts, as for the forward pipelines
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        layouts: &[&wgpu::BindGroupLayout; 4],
+    ) -> Self {
+        let [texture_layout, camera_layout, light_layout, ao_layout] = *layouts;
+        let texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let gbuffer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("G-Buffer Bind Group Layout"),
+            entries: &[
+                texture(0),
+                texture(1),
+                texture(2),
+                texture(3),
+                //  The depth buffer
+                texture(4),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let lighting_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Deferred Lighting Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/deferred.wgsl").into()),
+            });
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Deferred Lighting Pipeline Layout"),
+                bind_group_layouts: &[&gbuffer_layout, ao_layout, light_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Deferred Lighting Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(config.format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let model_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Normal Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/shader.wgsl").into()),
+        });
+        let chunk_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Chunk Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/chunk.wgsl").into()),
+        });
+        let pipeline_layout = |label, layouts: &[&wgpu::BindGroupLayout]| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            })
+        };
+        let model_buffers = [ModelVertex::desc(), InstanceRaw::desc()];
+        let chunk_buffers = [ChunkVertex::desc()];
+        let gbuffer_targets = [ALBEDO_FORMAT, NORMAL_FORMAT, MATERIAL_FORMAT, EMISSIVE_FORMAT]
+            .map(|format| Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::ALL }));
+        let geometry_model = scene_pipeline(
+            device,
+            &pipeline_layout("G-Buffer Model Pipeline Layout", &[texture_layout, camera_layout, light_layout]),
+            &model_buffers,
+            &model_shader,
+            "fs_gbuffer",
+            &gbuffer_targets,
+        );
+        let geometry_chunk = scene_pipeline(
+            device,
+            &pipeline_layout("G-Buffer Chunk Pipeline Layout", &[camera_layout, light_layout, texture_layout]),
+            &chunk_buffers,
+            &chunk_shader,
+            "fs_gbuffer",
+            &gbuffer_targets,
+        );
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Deferred Lighting Buffer"),
+            size: std::mem::size_of::<LightingUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let gbuffer = Self::create_gbuffer(device, config, depth_view, &gbuffer_layout, &buffer);
+
+        Self {
+            gbuffer_layout,
+            lighting_pipeline,
+            buffer,
+            gbuffer,
+            geometry_model,
+            geometry_chunk,
+        }
+    }
+
+    fn create_gbuffer(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> GBuffer {
+        let (width, height) = (config.width, config.height);
+        let albedo = Target::new(device, "G-Buffer Albedo", ALBEDO_FORMAT, width, height);
+        let normal = Target::new(device, "G-Buffer Normal", NORMAL_FORMAT, width, height);
+        let material = Target::new(device, "G-Buffer Material", MATERIAL_FORMAT, width, height);
+        let emissive = Target::new(device, "G-Buffer Emissive", EMISSIVE_FORMAT, width, height);
+
+        let view = |binding, view| wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("G-Buffer Bind Group"),
+            layout,
+            entries: &[
+                view(0, &albedo.view),
+                view(1, &normal.view),
+                view(2, &material.view),
+                view(3, &emissive.view),
+                view(4, depth_view),
+                wgpu::BindGroupEntry { binding: 5, resource: buffer.as_entire_binding() },
+            ],
+        });
+        GBuffer { albedo, normal, material, emissive, bind_group }
+    }
+
+    //  Needs to be called with the new depth texture whenever it's recreated
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_view: &wgpu::TextureView) {
+        self.gbuffer = Self::create_gbuffer(device, config, depth_view, &self.gbuffer_layout, &self.buffer);
+    }
+
+    //  The pass the geometry pipelines draw opaque bricks in, into the G-buffer and `depth_view`
+    pub fn begin_geometry_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let gbuffer = &self.gbuffer;
+        let attachment = |target: &'a Target| Some(wgpu::RenderPassColorAttachment {
+            view: &target.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: true,
+            },
+        });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("G-Buffer Pass"),
+            color_attachments: &[
+                attachment(&gbuffer.albedo),
+                attachment(&gbuffer.normal),
+                attachment(&gbuffer.material),
+                attachment(&gbuffer.emissive),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    //  Lights the G-buffer into `output`, cleared to `clear_color` wherever nothing was drawn. Only the first
+    //  MAX_LIGHTS lights are used.
+    #[allow(clippy::too_many_arguments)]
+    pub fn light(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        output: &wgpu::TextureView,
+        clear_color: wgpu::Color,
+        ao_bind_group: &wgpu::BindGroup,
+        //  Only its fog is used, the lights themselves come from `lights`
+        light_bind_group: &wgpu::BindGroup,
+        view_proj: &Matrix4<f32>,
+        view_position: Point3<f32>,
+        lights: &[PointLight],
+    ) {
+        let light_count = lights.len().min(MAX_LIGHTS);
+        let mut packed = [bytemuck::Zeroable::zeroed(); MAX_LIGHTS];
+        packed[..light_count].copy_from_slice(&lights[..light_count]);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[LightingUniform {
+            inv_view_proj: view_proj.invert().unwrap_or_else(Matrix4::identity).into(),
+            view_pos: view_position.to_homogeneous().into(),
+            light_count: light_count as u32,
+            _padding: [0; 3],
+            lights: packed,
+        }]));
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Deferred Lighting Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear_color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.lighting_pipeline);
+        pass.set_bind_group(0, &self.gbuffer.bind_group, &[]);
+        pass.set_bind_group(1, ao_bind_group, &[]);
+        pass.set_bind_group(2, light_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn scene_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    targets: &[Option<wgpu::ColorTargetState>],
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Deferred Scene Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState { module: shader, entry_point, targets }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,329 @@
+//  FONT - OpenType fonts read with ttf-parser, .ttf or .otf with either TrueType or CFF outlines. Only what drawing text
+//  needs: the character map, horizontal metrics, pair kerning from GPOS (or the legacy kern table when there's no GPOS
+//  kerning), and the outlines, which rasterize() turns into coverage bitmaps.
+
+use anyhow::Context;
+use owned_ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+use owned_ttf_parser::opentype_layout::Lookup;
+use owned_ttf_parser::{AsFaceRef, Face, OutlineBuilder, OwnedFace, Tag};
+
+pub type GlyphId = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    //  In pixels, ascent up from the baseline and descent down from it (so usually negative)
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    //  Baseline to baseline
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    //  From the pen position on the baseline to the bitmap's top left corner, y down
+    pub left: i32,
+    pub top: i32,
+    //  Row by row from the top, 0 to 255
+    pub coverage: Vec<u8>,
+}
+
+//  From one point in pixels to another
+type Line = ((f32, f32), (f32, f32));
+
+pub struct Font {
+    face: OwnedFace,
+    units_per_em: f32,
+    //  The GPOS lookups of the kern feature, in the order they apply
+    kern_lookups: Vec<u16>,
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        let face = OwnedFace::from_vec(data, 0).context("Not a TrueType or OpenType font")?;
+        let (units_per_em, kern_lookups) = {
+            let face = face.as_face_ref();
+            (face.units_per_em() as f32, kern_lookups(face))
+        };
+        Ok(Self { face, units_per_em, kern_lookups })
+    }
+
+    fn face(&self) -> &Face<'_> {
+        self.face.as_face_ref()
+    }
+
+    pub fn glyph_count(&self) -> u16 {
+        self.face().number_of_glyphs()
+    }
+
+    pub fn line_metrics(&self, size: f32) -> LineMetrics {
+        let scale = size / self.units_per_em;
+        let face = self.face();
+        LineMetrics {
+            ascent: face.ascender() as f32 * scale,
+            descent: face.descender() as f32 * scale,
+            line_gap: face.line_gap() as f32 * scale,
+        }
+    }
+
+    //  0 is the font's missing glyph box
+    pub fn glyph_index(&self, c: char) -> GlyphId {
+        self.face().glyph_index(c).map_or(0, |glyph| glyph.0)
+    }
+
+    //  How far the pen moves after the glyph, in pixels
+    pub fn advance(&self, glyph: GlyphId, size: f32) -> f32 {
+        let advance = self.face().glyph_hor_advance(owned_ttf_parser::GlyphId(glyph)).unwrap_or(0);
+        advance as f32 * size / self.units_per_em
+    }
+
+    //  Added to the advance of `left` when `right` comes straight after it, in pixels
+    pub fn kerning(&self, left: GlyphId, right: GlyphId, size: f32) -> f32 {
+        let face = self.face();
+        let (left, right) = (owned_ttf_parser::GlyphId(left), owned_ttf_parser::GlyphId(right));
+        let units = match face.tables().gpos {
+            Some(gpos) if !self.kern_lookups.is_empty() => self.kern_lookups
+                .iter()
+                .filter_map(|&lookup| gpos.lookups.get(lookup))
+                .filter_map(|lookup| pair_adjustment(&lookup, left, right))
+                .map(i32::from)
+                .sum(),
+            _ => face.tables().kern
+                .and_then(|kern| {
+                    kern.subtables
+                        .into_iter()
+                        .filter(|subtable| subtable.horizontal && !subtable.variable && !subtable.has_cross_stream)
+                        .find_map(|subtable| subtable.glyphs_kerning(left, right))
+                })
+                .map_or(0, i32::from),
+        };
+        units as f32 * size / self.units_per_em
+    }
+
+    //  Antialiased coverage of the glyph at `size` pixels per em. Glyphs without an outline, like spaces, come back
+    //  0 by 0.
+    pub fn rasterize(&self, glyph: GlyphId, size: f32) -> GlyphBitmap {
+        let mut flattener = Flattener { scale: size / self.units_per_em, start: (0.0, 0.0), current: (0.0, 0.0), lines: Vec::new() };
+        self.face().outline_glyph(owned_ttf_parser::GlyphId(glyph), &mut flattener);
+        let lines = flattener.lines;
+        if lines.is_empty() {
+            return GlyphBitmap::default();
+        }
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for &(x, y) in lines.iter().flat_map(|(a, b)| [a, b]) {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let (left, top) = (min_x.floor() as i32, min_y.floor() as i32);
+        let width = (max_x.ceil() as i32 - left).max(1) as u32;
+        let height = (max_y.ceil() as i32 - top).max(1) as u32;
+
+        let mut rasterizer = Rasterizer::new(width as usize, height as usize);
+        for ((x0, y0), (x1, y1)) in lines {
+            rasterizer.line((x0 - left as f32, y0 - top as f32), (x1 - left as f32, y1 - top as f32));
+        }
+        GlyphBitmap { width, height, left, top, coverage: rasterizer.coverage() }
+    }
+}
+
+//  Every lookup the font's kern features use, whichever script they're for. Text isn't shaped per script here, and
+//  lookups apply in the order they're listed in, not the order features name them.
+fn kern_lookups(face: &Face) -> Vec<u16> {
+    let Some(gpos) = face.tables().gpos else {
+        return Vec::new();
+    };
+    let mut lookups = gpos.features
+        .into_iter()
+        .filter(|feature| feature.tag == Tag::from_bytes(b"kern"))
+        .flat_map(|feature| feature.lookup_indices)
+        .collect::<Vec<_>>();
+    lookups.sort_unstable();
+    lookups.dedup();
+    lookups
+}
+
+//  The change to the first glyph's advance from the first of the lookup's subtables that has the pair
+fn pair_adjustment(lookup: &Lookup, left: owned_ttf_parser::GlyphId, right: owned_ttf_parser::GlyphId) -> Option<i16> {
+    (0..lookup.subtables.len()).find_map(|i| match lookup.subtables.get::<PositioningSubtable>(i)? {
+        PositioningSubtable::Pair(PairAdjustment::Format1 { coverage, sets }) => {
+            let (first, _) = sets.get(coverage.get(left)?)?.get(right)?;
+            Some(first.x_advance)
+        }
+        PositioningSubtable::Pair(PairAdjustment::Format2 { coverage, classes, matrix }) => {
+            coverage.get(left)?;
+            let (first, _) = matrix.get((classes.0.get(left), classes.1.get(right)))?;
+            Some(first.x_advance)
+        }
+        _ => None,
+    })
+}
+
+//  Turns an outline in font units into lines in pixels, flipped so y goes down
+struct Flattener {
+    scale: f32,
+    start: (f32, f32),
+    current: (f32, f32),
+    lines: Vec<Line>,
+}
+
+impl Flattener {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (x * self.scale, -y * self.scale)
+    }
+}
+
+impl OutlineBuilder for Flattener {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.current = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.lines.push((self.current, p));
+        self.current = p;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let p = self.point(x, y);
+        flatten_quad(self.current, self.point(x1, y1), p, &mut self.lines);
+        self.current = p;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let p = self.point(x, y);
+        flatten_cubic(self.current, self.point(x1, y1), self.point(x2, y2), p, &mut self.lines);
+        self.current = p;
+    }
+
+    fn close(&mut self) {
+        if self.current != self.start {
+            self.lines.push((self.current, self.start));
+        }
+        self.current = self.start;
+    }
+}
+
+//  Enough segments that the curve stays within about a third of a pixel
+fn flatten_quad(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), lines: &mut Vec<Line>) {
+    let (ddx, ddy) = (p0.0 - 2.0 * p1.0 + p2.0, p0.1 - 2.0 * p1.1 + p2.1);
+    let deviation = ddx * ddx + ddy * ddy;
+    let segments = if deviation < 0.333 { 1 } else { 1 + (3.0 * deviation).sqrt().sqrt().floor() as usize };
+    let mut previous = p0;
+    for i in 1..=segments {
+        let t = i as f32 / segments as f32;
+        let u = 1.0 - t;
+        let point = (
+            u * u * p0.0 + 2.0 * u * t * p1.0 + t * t * p2.0,
+            u * u * p0.1 + 2.0 * u * t * p1.1 + t * t * p2.1,
+        );
+        lines.push((previous, point));
+        previous = point;
+    }
+}
+
+//  Same as flatten_quad for the cubic curves CFF outlines use
+fn flatten_cubic(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), lines: &mut Vec<Line>) {
+    let second_difference = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| {
+        let (ddx, ddy) = (a.0 - 2.0 * b.0 + c.0, a.1 - 2.0 * b.1 + c.1);
+        (ddx * ddx + ddy * ddy).sqrt()
+    };
+    let deviation = second_difference(p0, p1, p2).max(second_difference(p1, p2, p3));
+    let segments = 1 + (2.25 * deviation).sqrt().floor() as usize;
+    let mut previous = p0;
+    for i in 1..=segments {
+        let t = i as f32 / segments as f32;
+        let u = 1.0 - t;
+        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        let point = (
+            a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+            a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+        );
+        lines.push((previous, point));
+        previous = point;
+    }
+}
+
+//  Signed area coverage: each line adds how much of every pixel it passes is to its right, and a running sum along
+//  the rows fills the insides in. Windings cancel out the same way the non-zero fill rule does.
+struct Rasterizer {
+    width: usize,
+    height: usize,
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    fn new(width: usize, height: usize) -> Self {
+        //  Lines touching the right edge spill a little into the next row, where the sum carries it back to 0
+        Self { width, height, accumulation: vec![0.0; width * height + 4] }
+    }
+
+    fn line(&mut self, p0: (f32, f32), p1: (f32, f32)) {
+        if p0.1 == p1.1 {
+            return;
+        }
+        let (direction, p0, p1) = if p0.1 < p1.1 { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+        let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+        let mut x = p0.0;
+        let first_row = p0.1.max(0.0) as usize;
+        if p0.1 < 0.0 {
+            x -= p0.1 * dxdy;
+        }
+        for y in first_row..self.height.min(p1.1.ceil() as usize) {
+            let row = y * self.width;
+            let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+            let x_next = x + dxdy * dy;
+            let d = dy * direction;
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let x0_floor = x0.floor();
+            let x0i = x0_floor.max(0.0) as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil.max(0.0) as usize;
+            if x1i <= x0i + 1 {
+                let x_middle = 0.5 * (x + x_next) - x0_floor;
+                self.accumulation[row + x0i] += d - d * x_middle;
+                self.accumulation[row + x0i + 1] += d * x_middle;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0_fraction = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1_fraction * x1_fraction;
+                self.accumulation[row + x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    self.accumulation[row + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0_fraction);
+                    self.accumulation[row + x0i + 1] += d * (a1 - a0);
+                    for xi in x0i + 2..x1i - 1 {
+                        self.accumulation[row + xi] += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.accumulation[row + x1i - 1] += d * (1.0 - a2 - am);
+                }
+                self.accumulation[row + x1i] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    fn coverage(&self) -> Vec<u8> {
+        let mut sum = 0.0;
+        self.accumulation[..self.width * self.height]
+            .iter()
+            .map(|a| {
+                sum += a;
+                (sum.abs().min(1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,106 @@
+//  GAMEPAD - Controller events from gilrs, which knows the layouts of most pads on every desktop platform and the web.
+//  Polling it never blocks, so it's drained on the main thread once a frame. Its events are turned into our own so the
+//  input layer can be driven by synthetic ones in tests.
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use crate::game::input::{GamepadAxis, GamepadButton};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    Button(GamepadButton, bool),
+    //  Sticks -1 to 1 with y up, triggers 0 to 1, before any dead zone
+    Axis(GamepadAxis, f32),
+    //  Everything the pad was holding should be let go
+    Disconnected,
+}
+
+pub struct Gamepads {
+    //  None where gilrs has no backend or couldn't start
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    //  Pads plugged in later are picked up as they connect
+    pub fn open() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(gilrs::Error::NotImplemented(_)) => {
+                log::info!("No gamepad support on this platform");
+                None
+            }
+            Err(e) => {
+                log::warn!("No gamepad support: {}", e);
+                None
+            }
+        };
+        Self { gilrs }
+    }
+
+    //  Everything that happened since the last poll
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                match event.event {
+                    EventType::Connected => {
+                        log::info!("Gamepad connected: {}", gilrs.gamepad(event.id).name())
+                    }
+                    EventType::Disconnected => log::info!("Gamepad disconnected"),
+                    _ => {}
+                }
+                events.extend(translate(event.event));
+            }
+        }
+        events
+    }
+}
+
+fn translate(event: EventType) -> Option<GamepadEvent> {
+    match event {
+        EventType::ButtonPressed(button, _) => Some(GamepadEvent::Button(button_of(button)?, true)),
+        EventType::ButtonReleased(button, _) => {
+            Some(GamepadEvent::Button(button_of(button)?, false))
+        }
+        //  Analog triggers are buttons to gilrs, with a value as well as pressed and released
+        EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+            Some(GamepadEvent::Axis(GamepadAxis::LeftTrigger, value))
+        }
+        EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+            Some(GamepadEvent::Axis(GamepadAxis::RightTrigger, value))
+        }
+        EventType::AxisChanged(axis, value, _) => {
+            let axis = match axis {
+                Axis::LeftStickX => GamepadAxis::LeftStickX,
+                Axis::LeftStickY => GamepadAxis::LeftStickY,
+                Axis::RightStickX => GamepadAxis::RightStickX,
+                Axis::RightStickY => GamepadAxis::RightStickY,
+                _ => return None,
+            };
+            Some(GamepadEvent::Axis(axis, value.clamp(-1.0, 1.0)))
+        }
+        EventType::Disconnected => Some(GamepadEvent::Disconnected),
+        _ => None,
+    }
+}
+
+//  gilrs' triggers are our bumpers, its second triggers are the analog ones handled as axes
+fn button_of(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::West => GamepadButton::West,
+        Button::North => GamepadButton::North,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
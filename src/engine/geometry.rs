@@ -0,0 +1,153 @@
+//  GEOMETRY - CPU side mesh processing shared by the loaders: normal/UV generation and tangent frames.
+//  Everything here works on plain ModelVertex/index slices so it can run on worker threads and in tests without a GPU.
+
+use cgmath::prelude::*;
+use cgmath::{Vector2, Vector3};
+
+use crate::engine::model::ModelVertex;
+
+//  Below this a triangle (or its UV mapping) is treated as degenerate and doesn't contribute to normals/tangents
+const EPSILON: f32 = 1e-8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalGeneration {
+    //  Every triangle gets its own vertices and the face normal, hard edges everywhere (what bricks want)
+    Flat,
+    //  Shared vertices get the area weighted average of the faces around them
+    Smooth,
+}
+
+fn position(v: &ModelVertex) -> Vector3<f32> {
+    v.position.into()
+}
+
+fn face_normal(vertices: &[ModelVertex], tri: &[u32]) -> Vector3<f32> {
+    let p0 = position(&vertices[tri[0] as usize]);
+    let p1 = position(&vertices[tri[1] as usize]);
+    let p2 = position(&vertices[tri[2] as usize]);
+    //  Not normalised, the length is twice the triangle's area which gives us area weighting for free
+    (p1 - p0).cross(p2 - p0)
+}
+
+//  Some vector perpendicular to `n`, for when there's nothing better to go on
+fn any_perpendicular(n: Vector3<f32>) -> Vector3<f32> {
+    let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    n.cross(axis).normalize()
+}
+
+pub fn generate_normals(vertices: &mut Vec<ModelVertex>, indices: &mut Vec<u32>, mode: NormalGeneration) {
+    if mode == NormalGeneration::Flat {
+        //  Split every triangle off so it can have its own normal
+        let mut flat_vertices = Vec::with_capacity(indices.len());
+        for tri in indices.chunks_exact(3) {
+            let n = face_normal(vertices, tri);
+            let n = if n.magnitude2() > EPSILON { n.normalize() } else { Vector3::unit_y() };
+            for &i in tri {
+                let mut v = vertices[i as usize];
+                v.normal = n.into();
+                flat_vertices.push(v);
+            }
+        }
+        *indices = (0..flat_vertices.len() as u32).collect();
+        *vertices = flat_vertices;
+        return;
+    }
+
+    let mut normals = vec![Vector3::zero(); vertices.len()];
+    for tri in indices.chunks_exact(3) {
+        let n = face_normal(vertices, tri);
+        for &i in tri {
+            normals[i as usize] += n;
+        }
+    }
+    for (v, n) in vertices.iter_mut().zip(normals) {
+        //  Vertices only used by degenerate triangles (or none at all) get an arbitrary but valid normal
+        v.normal = if n.magnitude2() > EPSILON { n.normalize().into() } else { [0.0, 1.0, 0.0] };
+    }
+}
+
+//  Projects positions onto the plane of the mesh's two largest extents and fits them to 0..1
+pub fn planar_uvs(vertices: &mut [ModelVertex]) {
+    if vertices.is_empty() {
+        return;
+    }
+
+    let mut min = Vector3::from(vertices[0].position);
+    let mut max = min;
+    for v in vertices.iter() {
+        let p = position(v);
+        min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+        max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+    }
+    let extent = max - min;
+
+    //  Drop the axis the mesh is thinnest along
+    let (u_axis, v_axis) = if extent.x <= extent.y && extent.x <= extent.z {
+        (2, 1)
+    } else if extent.y <= extent.x && extent.y <= extent.z {
+        (0, 2)
+    } else {
+        (0, 1)
+    };
+
+    let scale = |axis: usize| if extent[axis] > EPSILON { 1.0 / extent[axis] } else { 0.0 };
+    let (u_scale, v_scale) = (scale(u_axis), scale(v_axis));
+    for v in vertices.iter_mut() {
+        let p = position(v);
+        //  wgpu's v axis points down
+        v.tex_coords = [(p[u_axis] - min[u_axis]) * u_scale, 1.0 - (p[v_axis] - min[v_axis]) * v_scale];
+    }
+}
+
+//  Accumulates per-triangle tangents/bitangents, skipping triangles with degenerate UVs, then Gram-Schmidt
+//  orthonormalises them against the vertex normal. Normals must already be filled in.
+pub fn compute_tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+    let mut tangents = vec![Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![Vector3::zero(); vertices.len()];
+
+    for c in indices.chunks_exact(3) {
+        let v0 = vertices[c[0] as usize];
+        let v1 = vertices[c[1] as usize];
+        let v2 = vertices[c[2] as usize];
+
+        //  Calculate tri edges
+        let delta_pos1 = position(&v1) - position(&v0);
+        let delta_pos2 = position(&v2) - position(&v0);
+
+        //  Get direction to calculate tangent & bitangent
+        let delta_uv1 = Vector2::from(v1.tex_coords) - Vector2::from(v0.tex_coords);
+        let delta_uv2 = Vector2::from(v2.tex_coords) - Vector2::from(v0.tex_coords);
+
+        //  A triangle whose UVs are collinear has no defined tangent, it would divide by zero
+        let det = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+        if det.abs() < EPSILON {
+            continue;
+        }
+
+        let r = 1.0 / det;
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        //  Flip the bitangent to enable right-handed normal maps with wgpu tex coord system
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        for &i in c {
+            tangents[i as usize] += tangent;
+            bitangents[i as usize] += bitangent;
+        }
+    }
+
+    for (i, v) in vertices.iter_mut().enumerate() {
+        let n = Vector3::from(v.normal);
+        let n = if n.magnitude2() > EPSILON { n.normalize() } else { Vector3::unit_y() };
+
+        //  Gram-Schmidt: remove the normal component so the frame is orthogonal
+        let t = tangents[i] - n * n.dot(tangents[i]);
+        let t = if t.magnitude2() > EPSILON { t.normalize() } else { any_perpendicular(n) };
+
+        //  Keep the handedness the UVs asked for, but make the bitangent exactly perpendicular
+        let b = n.cross(t);
+        let b = if b.dot(bitangents[i]) < 0.0 { -b } else { b };
+
+        v.tangent = t.into();
+        v.bitangent = b.into();
+    }
+}
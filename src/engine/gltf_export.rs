@@ -0,0 +1,324 @@
+//  GLTF EXPORT - Writes a build out as glTF 2.0 (.glb or .gltf + .bin) so it can be rendered elsewhere, e.g. Blender.
+//  Each brick type's geometry is written once and shared, every placed brick becomes a node pointing at it
+//  (or one node per brick type and colour with EXT_mesh_gpu_instancing). Colours become materials.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use cgmath::prelude::*;
+use cgmath::{Matrix3, Matrix4, Quaternion, Vector3};
+use serde_json::{json, Value};
+
+use crate::engine::model::MeshData;
+use crate::game::environment::Environment;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const GPU_INSTANCING: &str = "EXT_mesh_gpu_instancing";
+
+pub struct BrickType<'a> {
+    pub name: &'a str,
+    pub meshes: &'a [MeshData],
+}
+
+pub struct PlacedBrick {
+    //  Index into the brick types passed to the exporter
+    pub brick_type: usize,
+    pub transform: Matrix4<f32>,
+    pub color: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GltfExportOptions {
+    //  One node per brick type and colour using EXT_mesh_gpu_instancing instead of a node per brick.
+    //  Much smaller for big builds, but not every importer supports it.
+    pub gpu_instancing: bool,
+    //  Saved in the scene's extras, so the build can be shown in the same sky and fog again
+    pub environment: Option<Environment>,
+}
+
+//  Splits an instance matrix back into translation, rotation and scale, which is what glTF nodes want
+pub fn decompose(transform: &Matrix4<f32>) -> ([f32; 3], [f32; 4], [f32; 3]) {
+    let translation = transform.w.truncate();
+    let mut scale = Vector3::new(
+        transform.x.truncate().magnitude(),
+        transform.y.truncate().magnitude(),
+        transform.z.truncate().magnitude(),
+    );
+    //  A mirrored matrix can't be a rotation, push the mirror into the scale instead
+    let rotation_scale = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+    if rotation_scale.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+
+    let safe = |v: Vector3<f32>, s: f32| if s.abs() > f32::EPSILON { v / s } else { v };
+    let rotation = Matrix3::from_cols(
+        safe(rotation_scale.x, scale.x),
+        safe(rotation_scale.y, scale.y),
+        safe(rotation_scale.z, scale.z),
+    );
+    let q = Quaternion::from(rotation).normalize();
+
+    (translation.into(), [q.v.x, q.v.y, q.v.z, q.s], scale.into())
+}
+
+//  Accumulates the binary buffer along with the views and accessors that point into it
+#[derive(Default)]
+struct BufferBuilder {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BufferBuilder {
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        //  Every accessor we write is 4 byte aligned
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.data.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], target: Option<u32>, with_bounds: bool) -> usize {
+        let kind = match N {
+            2 => "VEC2",
+            3 => "VEC3",
+            4 => "VEC4",
+            _ => "SCALAR",
+        };
+        let bytes = values.iter().flatten().flat_map(|x| x.to_le_bytes()).collect::<Vec<_>>();
+        let view = self.push_view(&bytes, target);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": kind,
+        });
+        //  Required for POSITION
+        if with_bounds && !values.is_empty() {
+            let mut min = [f32::MAX; N];
+            let mut max = [f32::MIN; N];
+            for v in values {
+                for i in 0..N {
+                    min[i] = min[i].min(v[i]);
+                    max[i] = max[i].max(v[i]);
+                }
+            }
+            accessor["min"] = json!(min.to_vec());
+            accessor["max"] = json!(max.to_vec());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes = indices.iter().flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        let view = self.push_view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+fn color_key(color: [f32; 4]) -> [u32; 4] {
+    color.map(f32::to_bits)
+}
+
+//  Returns the glTF JSON and the contents of its binary buffer. The buffer's uri is left for the caller to fill in.
+pub fn build_gltf(
+    brick_types: &[BrickType],
+    bricks: &[PlacedBrick],
+    options: &GltfExportOptions,
+) -> anyhow::Result<(Value, Vec<u8>)> {
+    let mut buffer = BufferBuilder::default();
+
+    //  Geometry is written once per brick type, every (type, colour) mesh shares these accessors
+    let mut type_primitives: Vec<Vec<(usize, usize, usize, usize)>> = Vec::new();
+    for brick_type in brick_types {
+        let mut primitives = Vec::new();
+        for mesh in brick_type.meshes {
+            let positions = mesh.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+            let normals = mesh.vertices.iter().map(|v| v.normal).collect::<Vec<_>>();
+            let uvs = mesh.vertices.iter().map(|v| v.tex_coords).collect::<Vec<_>>();
+            primitives.push((
+                buffer.push_floats(&positions, Some(ARRAY_BUFFER), true),
+                buffer.push_floats(&normals, Some(ARRAY_BUFFER), false),
+                buffer.push_floats(&uvs, Some(ARRAY_BUFFER), false),
+                buffer.push_indices(&mesh.indices),
+            ));
+        }
+        type_primitives.push(primitives);
+    }
+
+    let mut materials = Vec::new();
+    let mut material_lookup: HashMap<[u32; 4], usize> = HashMap::new();
+    let mut meshes = Vec::new();
+    let mut mesh_lookup: HashMap<(usize, [u32; 4]), usize> = HashMap::new();
+    //  Bricks grouped by mesh, in the order the meshes were created
+    let mut mesh_bricks: Vec<Vec<&PlacedBrick>> = Vec::new();
+
+    for brick in bricks {
+        if brick.brick_type >= brick_types.len() {
+            bail!("Brick uses type {} but only {} types were given", brick.brick_type, brick_types.len());
+        }
+        let key = color_key(brick.color);
+        let material = *material_lookup.entry(key).or_insert_with(|| {
+            let [r, g, b, a] = brick.color;
+            materials.push(json!({
+                "name": format!("color_{:02x}{:02x}{:02x}{:02x}",
+                    (r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, (a * 255.0) as u8),
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": brick.color,
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 0.5,
+                },
+                "alphaMode": if a < 1.0 { "BLEND" } else { "OPAQUE" },
+            }));
+            materials.len() - 1
+        });
+
+        let mesh = *mesh_lookup.entry((brick.brick_type, key)).or_insert_with(|| {
+            let primitives = type_primitives[brick.brick_type]
+                .iter()
+                .map(|&(position, normal, uv, indices)| json!({
+                    "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv },
+                    "indices": indices,
+                    "material": material,
+                }))
+                .collect::<Vec<_>>();
+            meshes.push(json!({
+                "name": format!("{} {}", brick_types[brick.brick_type].name, materials[material]["name"].as_str().unwrap_or("")),
+                "primitives": primitives,
+            }));
+            mesh_bricks.push(Vec::new());
+            meshes.len() - 1
+        });
+        mesh_bricks[mesh].push(brick);
+    }
+
+    let mut nodes = Vec::new();
+    for (mesh, placed) in mesh_bricks.iter().enumerate() {
+        if options.gpu_instancing {
+            let (mut translations, mut rotations, mut scales) = (Vec::new(), Vec::new(), Vec::new());
+            for brick in placed {
+                let (t, r, s) = decompose(&brick.transform);
+                translations.push(t);
+                rotations.push(r);
+                scales.push(s);
+            }
+            nodes.push(json!({
+                "mesh": mesh,
+                "extensions": {
+                    GPU_INSTANCING: {
+                        "attributes": {
+                            "TRANSLATION": buffer.push_floats(&translations, None, false),
+                            "ROTATION": buffer.push_floats(&rotations, None, false),
+                            "SCALE": buffer.push_floats(&scales, None, false),
+                        }
+                    }
+                }
+            }));
+        } else {
+            for brick in placed {
+                let (translation, rotation, scale) = decompose(&brick.transform);
+                nodes.push(json!({
+                    "mesh": mesh,
+                    "translation": translation,
+                    "rotation": rotation,
+                    "scale": scale,
+                }));
+            }
+        }
+    }
+
+    let mut root = json!({
+        "asset": { "version": "2.0", "generator": "brickheaven" },
+        "scene": 0,
+        "scenes": [{ "name": "build", "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "accessors": buffer.accessors,
+        "bufferViews": buffer.views,
+        "buffers": [{ "byteLength": buffer.data.len() }],
+    });
+    if options.gpu_instancing {
+        root["extensionsUsed"] = json!([GPU_INSTANCING]);
+    }
+    if let Some(environment) = &options.environment {
+        root["scenes"][0]["extras"] = json!({ "environment": environment.to_json() });
+    }
+
+    Ok((root, buffer.data))
+}
+
+//  Packs the JSON and binary buffer into a single .glb
+pub fn to_glb(json: &Value, bin: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut json_bytes = serde_json::to_vec(json)?;
+    //  Chunks have to be 4 byte aligned, JSON is padded with spaces and binary with zeros
+    while !json_bytes.len().is_multiple_of(4) {
+        json_bytes.push(b' ');
+    }
+    let mut bin = bin.to_vec();
+    while !bin.len().is_multiple_of(4) {
+        bin.push(0);
+    }
+
+    let total = 12 + 8 + json_bytes.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(b"glTF");
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&(total as u32).to_le_bytes());
+    out.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"JSON");
+    out.extend_from_slice(&json_bytes);
+    out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"BIN\0");
+    out.extend_from_slice(&bin);
+
+    Ok(out)
+}
+
+//  .glb gets a single binary file, .gltf gets the JSON plus a .bin next to it
+pub fn export_gltf<P: AsRef<Path>>(
+    path: P,
+    brick_types: &[BrickType],
+    bricks: &[PlacedBrick],
+    options: &GltfExportOptions,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (mut json, bin) = build_gltf(brick_types, bricks, options)?;
+
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("glb") => {
+            std::fs::write(path, to_glb(&json, &bin)?).with_context(|| format!("Failed to write {:?}", path))?;
+        }
+        Some("gltf") => {
+            let bin_path = path.with_extension("bin");
+            let bin_name = bin_path.file_name().and_then(|n| n.to_str()).unwrap_or("build.bin").to_string();
+            json["buffers"][0]["uri"] = json!(bin_name);
+            std::fs::write(&bin_path, &bin).with_context(|| format!("Failed to write {:?}", bin_path))?;
+            std::fs::write(path, serde_json::to_vec_pretty(&json)?).with_context(|| format!("Failed to write {:?}", path))?;
+        }
+        _ => bail!("Don't know how to export {:?}, use .glb or .gltf", path),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,578 @@
+//  GPU CULLING - Frustum and Hi-Z occlusion culling in a compute pass, so the CPU never has to look at individual instances.
+//  Every LOD level of every brick type gets its own region of the culled instance buffer and one indirect draw per mesh,
+//  the counts are filled in on the GPU and drawn with draw_indexed_indirect.
+
+use wgpu::util::DeviceExt;
+
+use crate::engine::culling::Frustum;
+use crate::engine::lod::{Lod, LodSettings, LOD_COUNT};
+use crate::engine::model::Model;
+use crate::game::instance::InstanceRaw;
+
+//  Size of one wgpu::util::DrawIndexedIndirect
+pub const DRAW_ARGS_SIZE: wgpu::BufferAddress = std::mem::size_of::<DrawArgs>() as wgpu::BufferAddress;
+const WORKGROUP_SIZE: u32 = 64;
+const HIZ_WORKGROUP_SIZE: u32 = 8;
+const HIZ_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+//  Bound at once by the culling pass
+const STORAGE_BUFFERS: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    frustum: [[f32; 4]; 6],
+    hiz_view_proj: [[f32; 4]; 4],
+    hiz_size: [f32; 2],
+    instance_count: u32,
+    use_hiz: u32,
+    hiz_mip_count: u32,
+    arg_count: u32,
+    lod_boundaries: [f32; 2],
+    eye: [f32; 3],
+    pixels_per_unit: f32,
+    lod_hysteresis: f32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuBrickType {
+    sphere: [f32; 4],
+    instance_offset: u32,
+    _padding: [u32; 3],
+}
+
+//  Laid out like wgpu::util::DrawIndexedIndirect, which isn't Pod
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+//  Where one LOD level of a brick type's data ended up, for drawing it
+#[derive(Debug, Clone, Copy)]
+struct BrickTypeRange {
+    instance_offset: u32,
+    instance_count: u32,
+    first_arg: u32,
+}
+
+//  Everything that depends on the instances, rebuilt by set_instances()
+struct InstanceBuffers {
+    bind_group: wgpu::BindGroup,
+    culled_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    counts_buffer: wgpu::Buffer,
+    brick_types: Vec<BrickTypeRange>,
+    instance_count: u32,
+    arg_count: u32,
+    brick_type_count: u32,
+}
+
+struct HiZ {
+    size: [u32; 2],
+    mip_count: u32,
+    //  The whole pyramid, read by the culling pass
+    cull_bind_group: wgpu::BindGroup,
+    copy_bind_group: wgpu::BindGroup,
+    //  The depth buffer is multisampled, so copy_bind_group is for the MSAA copy pipeline
+    multisampled: bool,
+    //  One per mip after the first, each reads the one before
+    reduce_bind_groups: Vec<wgpu::BindGroup>,
+    //  The camera last frame's depth was rendered with
+    view_proj: cgmath::Matrix4<f32>,
+    //  Nothing has been written to it yet
+    valid: bool,
+}
+
+struct HiZPipelines {
+    copy_depth_pipeline: wgpu::ComputePipeline,
+    copy_depth_msaa_pipeline: wgpu::ComputePipeline,
+    reduce_pipeline: wgpu::ComputePipeline,
+    copy_depth_layout: wgpu::BindGroupLayout,
+    copy_depth_msaa_layout: wgpu::BindGroupLayout,
+    reduce_layout: wgpu::BindGroupLayout,
+}
+
+pub struct GpuCuller {
+    cull_pipeline: wgpu::ComputePipeline,
+    args_pipeline: wgpu::ComputePipeline,
+    cull_layout: wgpu::BindGroupLayout,
+    hiz_sample_layout: wgpu::BindGroupLayout,
+    //  None where the pyramid can't be built, only frustum culling is done then
+    hiz_pipelines: Option<HiZPipelines>,
+    params_buffer: wgpu::Buffer,
+    //  Bound instead of the Hi-Z pyramid until there is one
+    empty_hiz_bind_group: wgpu::BindGroup,
+    buffers: Option<InstanceBuffers>,
+    hiz: Option<HiZ>,
+    pub use_hiz: bool,
+}
+
+impl GpuCuller {
+    //  Compute shaders and indirect draws aren't available everywhere (WebGL2), the CPU culler has to be used there
+    pub fn is_supported(adapter: &wgpu::Adapter, device: &wgpu::Device) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+        ) && device.limits().max_storage_buffers_per_shader_stage >= STORAGE_BUFFERS
+    }
+
+    //  The GL backend can't write storage textures from compute shaders, so there's no Hi-Z there
+    pub fn supports_hiz(adapter: &wgpu::Adapter) -> bool {
+        adapter.get_info().backend != wgpu::Backend::Gl
+    }
+
+    pub fn new(device: &wgpu::Device, hiz_supported: bool) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let cull_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cull Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, false),
+                storage(5, false),
+                storage(6, false),
+                storage(7, true),
+                storage(8, false),
+            ],
+        });
+
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let multisampled_texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: true,
+            },
+            ..texture(binding, sample_type)
+        };
+        let storage_texture = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: HIZ_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let hiz_sample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Hi-Z Sample Bind Group Layout"),
+            entries: &[texture(0, unfilterable)],
+        });
+
+        let cull_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cull Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/cull.wgsl").into()),
+        });
+
+        let pipeline = |label, layouts: &[&wgpu::BindGroupLayout], module: &wgpu::ShaderModule, entry_point| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module,
+                entry_point,
+            })
+        };
+        let cull_pipeline = pipeline("Cull Pipeline", &[&cull_layout, &hiz_sample_layout], &cull_shader, "cull");
+        let args_pipeline = pipeline("Cull Args Pipeline", &[&cull_layout, &hiz_sample_layout], &cull_shader, "write_args");
+        let hiz_pipelines = hiz_supported.then(|| {
+            let copy_depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z Copy Bind Group Layout"),
+                entries: &[texture(0, unfilterable), storage_texture],
+            });
+            let copy_depth_msaa_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z MSAA Copy Bind Group Layout"),
+                entries: &[multisampled_texture(3, unfilterable), storage_texture],
+            });
+            let reduce_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z Reduce Bind Group Layout"),
+                entries: &[storage_texture, texture(2, unfilterable)],
+            });
+            let hiz_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Hi-Z Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/hiz.wgsl").into()),
+            });
+            HiZPipelines {
+                copy_depth_pipeline: pipeline("Hi-Z Copy Pipeline", &[&copy_depth_layout], &hiz_shader, "copy_depth"),
+                copy_depth_msaa_pipeline: pipeline(
+                    "Hi-Z MSAA Copy Pipeline",
+                    &[&copy_depth_msaa_layout],
+                    &hiz_shader,
+                    "copy_depth_msaa",
+                ),
+                reduce_pipeline: pipeline("Hi-Z Reduce Pipeline", &[&reduce_layout], &hiz_shader, "reduce"),
+                copy_depth_layout,
+                copy_depth_msaa_layout,
+                reduce_layout,
+            }
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Params Buffer"),
+            size: std::mem::size_of::<CullParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (_, empty_view) = create_hiz_texture(device, [1, 1], 1, wgpu::TextureUsages::TEXTURE_BINDING);
+        let empty_hiz_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Empty Hi-Z Bind Group"),
+            layout: &hiz_sample_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&empty_view) }],
+        });
+
+        Self {
+            cull_pipeline,
+            args_pipeline,
+            cull_layout,
+            hiz_sample_layout,
+            hiz_pipelines,
+            params_buffer,
+            empty_hiz_bind_group,
+            buffers: None,
+            hiz: None,
+            use_hiz: hiz_supported,
+        }
+    }
+
+    //  `brick_types` are the models instances can use, `instances` pairs an index into them with the instance data.
+    //  Call again whenever either changes.
+    pub fn set_instances(&mut self, device: &wgpu::Device, brick_types: &[&Model], instances: &[(u32, InstanceRaw)]) {
+        let mut counts = vec![0u32; brick_types.len()];
+        for &(brick_type, _) in instances {
+            counts[brick_type as usize] += 1;
+        }
+
+        //  Any instance can end up at any level, so each level gets room for all of the type's instances
+        let type_count = brick_types.len() * LOD_COUNT;
+        let mut ranges = Vec::with_capacity(type_count);
+        let mut gpu_brick_types = Vec::with_capacity(type_count);
+        let mut args = Vec::new();
+        let mut arg_types = Vec::new();
+        let mut instance_offset = 0;
+        for (i, model) in brick_types.iter().enumerate() {
+            for lod in Lod::ALL {
+                let model = model.lod(lod);
+                ranges.push(BrickTypeRange { instance_offset, instance_count: counts[i], first_arg: args.len() as u32 });
+                let sphere = model.bounding_sphere;
+                gpu_brick_types.push(GpuBrickType {
+                    sphere: [sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius],
+                    instance_offset,
+                    _padding: [0; 3],
+                });
+                for mesh in &model.meshes {
+                    args.push(DrawArgs { index_count: mesh.num_elements, instance_count: 0, first_index: 0, base_vertex: 0, first_instance: 0 });
+                    arg_types.push((i * LOD_COUNT + lod.index()) as u32);
+                }
+                instance_offset += counts[i];
+            }
+        }
+
+        let instance_data = instances.iter().map(|(_, raw)| *raw).collect::<Vec<_>>();
+        let instance_types = instances.iter().map(|&(brick_type, _)| brick_type).collect::<Vec<_>>();
+
+        //  Empty storage buffers aren't allowed, so everything gets at least one element
+        let init = |label, contents: &[u8], usage| device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: if contents.is_empty() { &[0; 16] } else { contents },
+            usage,
+        });
+        let instance_buffer = init("Cull Instance Buffer", bytemuck::cast_slice(&instance_data), wgpu::BufferUsages::STORAGE);
+        let types_buffer = init("Cull Instance Types Buffer", bytemuck::cast_slice(&instance_types), wgpu::BufferUsages::STORAGE);
+        let brick_types_buffer = init("Cull Brick Types Buffer", bytemuck::cast_slice(&gpu_brick_types), wgpu::BufferUsages::STORAGE);
+        let arg_types_buffer = init("Cull Arg Types Buffer", bytemuck::cast_slice(&arg_types), wgpu::BufferUsages::STORAGE);
+        let indirect_buffer = init(
+            "Indirect Draw Buffer",
+            bytemuck::cast_slice(&args),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC,
+        );
+        let counts_buffer = init(
+            "Cull Counts Buffer",
+            bytemuck::cast_slice(&vec![0u32; type_count]),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        );
+        //  Everything starts at full detail
+        let lod_state_buffer = init("Cull LOD State Buffer", bytemuck::cast_slice(&vec![0u32; instances.len()]), wgpu::BufferUsages::STORAGE);
+        let culled_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Culled Instance Buffer"),
+            size: (instance_offset.max(1) as usize * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cull Bind Group"),
+            layout: &self.cull_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: instance_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: types_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: brick_types_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: culled_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: counts_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 6, resource: indirect_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 7, resource: arg_types_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 8, resource: lod_state_buffer.as_entire_binding() },
+            ],
+        });
+
+        self.buffers = Some(InstanceBuffers {
+            bind_group,
+            culled_buffer,
+            indirect_buffer,
+            counts_buffer,
+            brick_types: ranges,
+            instance_count: instances.len() as u32,
+            arg_count: args.len() as u32,
+            brick_type_count: type_count as u32,
+        });
+    }
+
+    //  Needs to be called with the new depth texture whenever it's recreated, `sample_count` is the depth texture's
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        depth_view: &wgpu::TextureView,
+        sample_count: u32,
+        width: u32,
+        height: u32,
+    ) {
+        let Some(pipelines) = &self.hiz_pipelines else {
+            return;
+        };
+        let size = [width.max(1), height.max(1)];
+        let mip_count = 32 - size[0].max(size[1]).leading_zeros();
+        let (texture, full_view) = create_hiz_texture(
+            device,
+            size,
+            mip_count,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        );
+        let mip_views = (0..mip_count)
+            .map(|level| texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Hi-Z Mip View"),
+                base_mip_level: level,
+                mip_level_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            }))
+            .collect::<Vec<_>>();
+
+        let cull_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hi-Z Sample Bind Group"),
+            layout: &self.hiz_sample_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&full_view) }],
+        });
+        let multisampled = sample_count > 1;
+        let copy_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hi-Z Copy Bind Group"),
+            layout: if multisampled { &pipelines.copy_depth_msaa_layout } else { &pipelines.copy_depth_layout },
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: if multisampled { 3 } else { 0 },
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&mip_views[0]) },
+            ],
+        });
+        let reduce_bind_groups = mip_views
+            .windows(2)
+            .map(|views| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Hi-Z Reduce Bind Group"),
+                layout: &pipelines.reduce_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&views[1]) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&views[0]) },
+                ],
+            }))
+            .collect();
+
+        self.hiz = Some(HiZ {
+            size,
+            mip_count,
+            cull_bind_group,
+            copy_bind_group,
+            multisampled,
+            reduce_bind_groups,
+            view_proj: cgmath::SquareMatrix::identity(),
+            valid: false,
+        });
+    }
+
+    //  Records the culling passes, must come before the render pass that draws with the results.
+    //  `eye` and `pixels_per_unit` are for working out how big instances are on screen to pick their LOD level.
+    pub fn cull(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        view_proj: &cgmath::Matrix4<f32>,
+        eye: cgmath::Point3<f32>,
+        pixels_per_unit: f32,
+        lod: &LodSettings,
+    ) {
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
+
+        let frustum = Frustum::from_view_proj(view_proj);
+        let hiz = self.hiz.as_ref().filter(|hiz| self.use_hiz && hiz.valid);
+        let params = CullParams {
+            frustum: frustum.planes.map(|p| [p.normal.x, p.normal.y, p.normal.z, p.distance]),
+            hiz_view_proj: hiz.map(|hiz| hiz.view_proj).unwrap_or(*view_proj).into(),
+            hiz_size: hiz.map(|hiz| [hiz.size[0] as f32, hiz.size[1] as f32]).unwrap_or([1.0; 2]),
+            instance_count: buffers.instance_count,
+            use_hiz: hiz.is_some() as u32,
+            hiz_mip_count: hiz.map(|hiz| hiz.mip_count).unwrap_or(1),
+            arg_count: buffers.arg_count,
+            lod_boundaries: [lod.flat_top_below, lod.impostor_below],
+            eye: eye.into(),
+            pixels_per_unit,
+            lod_hysteresis: lod.hysteresis,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        queue.write_buffer(&buffers.counts_buffer, 0, bytemuck::cast_slice(&vec![0u32; buffers.brick_type_count.max(1) as usize]));
+
+        let hiz_bind_group = hiz.map(|hiz| &hiz.cull_bind_group).unwrap_or(&self.empty_hiz_bind_group);
+        //  Separate passes so every count is final before it's copied into the draw arguments
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Cull Pass") });
+            pass.set_pipeline(&self.cull_pipeline);
+            pass.set_bind_group(0, &buffers.bind_group, &[]);
+            pass.set_bind_group(1, hiz_bind_group, &[]);
+            pass.dispatch_workgroups(buffers.instance_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Cull Args Pass") });
+            pass.set_pipeline(&self.args_pipeline);
+            pass.set_bind_group(0, &buffers.bind_group, &[]);
+            pass.set_bind_group(1, hiz_bind_group, &[]);
+            pass.dispatch_workgroups(buffers.arg_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    //  Builds the Hi-Z pyramid from the depth buffer just rendered with `view_proj`, for next frame's cull
+    pub fn build_hiz(&mut self, encoder: &mut wgpu::CommandEncoder, view_proj: &cgmath::Matrix4<f32>) {
+        if !self.use_hiz {
+            self.reset_hiz();
+            return;
+        }
+        let (Some(hiz), Some(pipelines)) = (&mut self.hiz, &self.hiz_pipelines) else {
+            return;
+        };
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Hi-Z Pass") });
+        let groups = |level: u32| {
+            let size = |s: u32| (s >> level).max(1).div_ceil(HIZ_WORKGROUP_SIZE);
+            (size(hiz.size[0]), size(hiz.size[1]))
+        };
+        pass.set_pipeline(if hiz.multisampled {
+            &pipelines.copy_depth_msaa_pipeline
+        } else {
+            &pipelines.copy_depth_pipeline
+        });
+        pass.set_bind_group(0, &hiz.copy_bind_group, &[]);
+        let (x, y) = groups(0);
+        pass.dispatch_workgroups(x, y, 1);
+
+        pass.set_pipeline(&pipelines.reduce_pipeline);
+        for (i, bind_group) in hiz.reduce_bind_groups.iter().enumerate() {
+            pass.set_bind_group(0, bind_group, &[]);
+            let (x, y) = groups(i as u32 + 1);
+            pass.dispatch_workgroups(x, y, 1);
+        }
+
+        hiz.view_proj = *view_proj;
+        hiz.valid = true;
+    }
+
+    //  Stops the pyramid being used until it's rebuilt, for when it's gone stale (e.g. culling was switched off)
+    pub fn reset_hiz(&mut self) {
+        if let Some(hiz) = &mut self.hiz {
+            hiz.valid = false;
+        }
+    }
+
+    pub fn has_hiz(&self) -> bool {
+        self.hiz_pipelines.is_some()
+    }
+
+    pub fn indirect_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffers.as_ref().map(|b| &b.indirect_buffer)
+    }
+
+    //  The culled instances of one brick type at one LOD level, to bind as the instance vertex buffer
+    pub fn instance_slice(&self, brick_type: usize, lod: Lod) -> Option<wgpu::BufferSlice<'_>> {
+        let buffers = self.buffers.as_ref()?;
+        let range = buffers.brick_types.get(brick_type * LOD_COUNT + lod.index())?;
+        let stride = std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress;
+        let start = range.instance_offset as wgpu::BufferAddress * stride;
+        //  A type with no instances still needs a valid slice, its draws have an instance count of 0 anyway
+        let end = start + range.instance_count.max(1) as wgpu::BufferAddress * stride;
+        Some(buffers.culled_buffer.slice(start..end.min(buffers.culled_buffer.size())))
+    }
+
+    //  Byte offset of the first mesh's draw arguments for model.lod(lod), the rest of its meshes follow it
+    pub fn indirect_offset(&self, brick_type: usize, lod: Lod) -> Option<wgpu::BufferAddress> {
+        let range = self.buffers.as_ref()?.brick_types.get(brick_type * LOD_COUNT + lod.index())?;
+        Some(range.first_arg as wgpu::BufferAddress * DRAW_ARGS_SIZE)
+    }
+}
+
+fn create_hiz_texture(
+    device: &wgpu::Device,
+    size: [u32; 2],
+    mip_count: u32,
+    usage: wgpu::TextureUsages,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Hi-Z Texture"),
+        size: wgpu::Extent3d { width: size[0], height: size[1], depth_or_array_layers: 1 },
+        mip_level_count: mip_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HIZ_FORMAT,
+        usage,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
@@ -0,0 +1,738 @@
+//  GUI - Immediate mode widgets for menus and build tools, drawn over the finished frame. Every frame game code calls
+//  Gui::begin() and then the widget functions on the Ui it returns, which draw themselves and report what was clicked
+//  or changed straight away. Window events go to handle_event() first, and whatever lands on the GUI (or is typed
+//  into a focused text field) is reported as used so it doesn't reach the camera.
+//  Text goes through the TextRenderer and is drawn over all of the GUI's quads, so windows can't overlap yet.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use crate::engine::font::Font;
+use crate::engine::model::Vertex;
+use crate::engine::text::{self, TextStyle};
+
+pub type WidgetId = u64;
+//  From GuiRenderer::add_image
+pub type ImageId = usize;
+
+//  Pixel deltas from touchpads are turned into lines of about this many pixels
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    //  In pixels from the top left of the window
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.x && point[0] < self.x + self.width && point[1] >= self.y && point[1] < self.y + self.height
+    }
+
+    //  Shrunk by `amount` on every side
+    pub fn inset(&self, amount: f32) -> Rect {
+        Rect::new(
+            self.x + amount,
+            self.y + amount,
+            (self.width - 2.0 * amount).max(0.0),
+            (self.height - 2.0 * amount).max(0.0),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuiStyle {
+    pub text: TextStyle,
+    //  Linear RGBA
+    pub panel: [f32; 4],
+    pub widget: [f32; 4],
+    pub hovered: [f32; 4],
+    pub active: [f32; 4],
+    //  Slider fills, selected items and the focused text field's border
+    pub accent: [f32; 4],
+    //  Between a widget's edge and its contents
+    pub padding: f32,
+}
+
+impl Default for GuiStyle {
+    fn default() -> Self {
+        Self {
+            text: TextStyle::default(),
+            panel: [0.01, 0.01, 0.015, 0.85],
+            widget: [0.05, 0.05, 0.06, 1.0],
+            hovered: [0.1, 0.1, 0.12, 1.0],
+            active: [0.2, 0.2, 0.24, 1.0],
+            accent: [1.0, 0.4, 0.0, 1.0],
+            padding: 4.0,
+        }
+    }
+}
+
+//  What text fields are sent, in the order it was typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuiKey {
+    Character(char),
+    Backspace,
+    Enter,
+    Escape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuiQuad {
+    pub rect: Rect,
+    pub color: [f32; 4],
+    //  Drawn untextured without one
+    pub image: Option<ImageId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuiText {
+    pub text: String,
+    //  Top left, in pixels
+    pub position: [f32; 2],
+    pub style: TextStyle,
+}
+
+//  Everything the last frame's widgets drew, in order
+#[derive(Debug, Clone, Default)]
+pub struct DrawList {
+    pub quads: Vec<GuiQuad>,
+    pub texts: Vec<GuiText>,
+}
+
+//  Input that arrived since the last frame
+#[derive(Debug, Clone, Default)]
+struct FrameInput {
+    pressed: bool,
+    released: bool,
+    //  In lines, positive is up
+    scroll: f32,
+    keys: Vec<GuiKey>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WidgetState {
+    Idle,
+    Hovered,
+    Active,
+}
+
+#[derive(Default)]
+pub struct Gui {
+    style: GuiStyle,
+    mouse: Option<[f32; 2]>,
+    //  The left button went down over the GUI and hasn't come up yet
+    mouse_down: bool,
+    //  The left button went down over the scene, so the GUI leaves the mouse alone until it comes up
+    scene_drag: bool,
+    //  Other buttons that went down over the GUI, only their releases are the GUI's
+    other_buttons_down: Vec<MouseButton>,
+    pending: FrameInput,
+    input: FrameInput,
+    //  The widget being pressed or dragged
+    active: Option<WidgetId>,
+    //  The text field being typed in
+    focus: Option<WidgetId>,
+    //  First visible row of scroll lists and image grids
+    scroll_offsets: HashMap<WidgetId, usize>,
+    //  Where the last frame put widgets and panels, what counts as being over the GUI
+    rects: Vec<Rect>,
+    draw_list: DrawList,
+}
+
+impl Gui {
+    pub fn style(&self) -> GuiStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: GuiStyle) {
+        self.style = style;
+    }
+
+    pub fn draw_list(&self) -> &DrawList {
+        &self.draw_list
+    }
+
+    //  Whether the mouse is over the GUI or dragging one of its widgets
+    pub fn wants_mouse(&self) -> bool {
+        self.mouse_down
+            || (!self.scene_drag && self.mouse.is_some_and(|mouse| self.rects.iter().any(|rect| rect.contains(mouse))))
+    }
+
+    //  Whether a text field has focus
+    pub fn wants_keyboard(&self) -> bool {
+        self.focus.is_some()
+    }
+
+    //  True if the GUI used the event and nothing else should see it
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.mouse_moved(position.x as f32, position.y as f32),
+            WindowEvent::CursorLeft { .. } => {
+                self.mouse = None;
+                false
+            }
+            WindowEvent::MouseInput { button, state, .. } => self.mouse_input(*button, *state),
+            WindowEvent::MouseWheel { delta, .. } => self.mouse_wheel(match delta {
+                MouseScrollDelta::LineDelta(_, lines) => *lines,
+                MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+            }),
+            WindowEvent::KeyboardInput { input, .. } => match input.virtual_keycode {
+                Some(key) => self.key_input(key, input.state),
+                None => self.wants_keyboard() && input.state == ElementState::Pressed,
+            },
+            WindowEvent::ReceivedCharacter(c) => self.received_character(*c),
+            _ => false,
+        }
+    }
+
+    pub fn mouse_moved(&mut self, x: f32, y: f32) -> bool {
+        self.mouse = Some([x, y]);
+        self.wants_mouse()
+    }
+
+    pub fn mouse_input(&mut self, button: MouseButton, state: ElementState) -> bool {
+        match (button, state) {
+            (MouseButton::Left, ElementState::Pressed) => {
+                if self.wants_mouse() {
+                    self.pending.pressed = true;
+                    self.mouse_down = true;
+                    true
+                } else {
+                    self.scene_drag = true;
+                    self.focus = None;
+                    false
+                }
+            }
+            //  Only the releases of presses the GUI took
+            (MouseButton::Left, ElementState::Released) => {
+                let used = self.mouse_down;
+                self.pending.released |= used;
+                self.mouse_down = false;
+                self.scene_drag = false;
+                used
+            }
+            (button, ElementState::Pressed) => {
+                let used = self.wants_mouse();
+                if used {
+                    self.other_buttons_down.push(button);
+                }
+                used
+            }
+            (button, ElementState::Released) => {
+                let taken = self.other_buttons_down.iter().position(|&b| b == button);
+                if let Some(i) = taken {
+                    self.other_buttons_down.swap_remove(i);
+                }
+                taken.is_some()
+            }
+        }
+    }
+
+    pub fn mouse_wheel(&mut self, lines: f32) -> bool {
+        let used = self.wants_mouse();
+        if used {
+            self.pending.scroll += lines;
+        }
+        used
+    }
+
+    //  A focused text field takes every key press. Releases are let through, or keys held down before it was
+    //  focused would stay down for the camera.
+    pub fn key_input(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if !self.wants_keyboard() || state != ElementState::Pressed {
+            return false;
+        }
+        let key = match key {
+            VirtualKeyCode::Back => Some(GuiKey::Backspace),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Some(GuiKey::Enter),
+            VirtualKeyCode::Escape => Some(GuiKey::Escape),
+            _ => None,
+        };
+        self.pending.keys.extend(key);
+        true
+    }
+
+    pub fn received_character(&mut self, c: char) -> bool {
+        if !self.wants_keyboard() {
+            return false;
+        }
+        if !c.is_control() {
+            self.pending.keys.push(GuiKey::Character(c));
+        }
+        true
+    }
+
+    //  Starts a frame of widgets, `font` is the one `style.text` refers to and is used for measuring
+    pub fn begin<'a>(&'a mut self, font: &'a Font) -> Ui<'a> {
+        //  Whatever was held down last frame is let go once the release has been seen
+        if self.input.released {
+            self.active = None;
+        }
+        self.input = std::mem::take(&mut self.pending);
+        self.rects.clear();
+        self.draw_list.quads.clear();
+        self.draw_list.texts.clear();
+        Ui { gui: self, font }
+    }
+}
+
+pub fn widget_id(name: &str) -> WidgetId {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+//  Rows of lists and cells of grids
+fn child_id(parent: WidgetId, index: usize) -> WidgetId {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (parent, index).hash(&mut hasher);
+    hasher.finish()
+}
+
+pub struct Ui<'a> {
+    gui: &'a mut Gui,
+    font: &'a Font,
+}
+
+impl Ui<'_> {
+    pub fn style(&self) -> GuiStyle {
+        self.gui.style
+    }
+
+    pub fn text_width(&self, text: &str) -> f32 {
+        text::layout(self.font, text, self.gui.style.text.size, None).width
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.font.line_metrics(self.gui.style.text.size).line_height()
+    }
+
+    //  A background for a group of widgets, which also keeps the mouse off the scene behind it
+    pub fn panel(&mut self, rect: Rect) {
+        self.gui.rects.push(rect);
+        self.quad(rect, self.gui.style.panel, None);
+    }
+
+    pub fn label(&mut self, position: [f32; 2], text: &str) {
+        let style = self.gui.style.text;
+        self.gui.draw_list.texts.push(GuiText { text: text.to_string(), position, style });
+    }
+
+    pub fn button(&mut self, id: &str, rect: Rect, label: &str) -> bool {
+        let (state, clicked) = self.interact(widget_id(id), rect);
+        self.quad(rect, self.state_color(state), None);
+        self.centered_label(rect, label);
+        clicked
+    }
+
+    //  A colour to pick, like in a palette, outlined when it's the current one
+    pub fn swatch(&mut self, id: &str, rect: Rect, color: [f32; 4], selected: bool) -> bool {
+        let (state, clicked) = self.interact(widget_id(id), rect);
+        let border = if selected { self.gui.style.accent } else { self.state_color(state) };
+        self.quad(rect, border, None);
+        self.quad(rect.inset(2.0), color, None);
+        clicked
+    }
+
+    //  Dragged anywhere across its width. True if the value changed.
+    pub fn slider(&mut self, id: &str, rect: Rect, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+        let (state, _) = self.interact(widget_id(id), rect);
+        let (min, max) = (*range.start(), *range.end());
+        let old = *value;
+        if state == WidgetState::Active && max > min {
+            if let Some(mouse) = self.gui.mouse {
+                let t = ((mouse[0] - rect.x) / rect.width.max(1.0)).clamp(0.0, 1.0);
+                *value = min + t * (max - min);
+            }
+        }
+        let t = if max > min { ((*value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+        self.quad(rect, self.state_color(state), None);
+        self.quad(Rect::new(rect.x, rect.y, rect.width * t, rect.height), self.gui.style.accent, None);
+        self.centered_label(rect, &format!("{:.2}", value));
+        *value != old
+    }
+
+    //  Typed into once clicked on, until Enter, Escape or a click somewhere else. True when Enter is pressed.
+    pub fn text_input(&mut self, id: &str, rect: Rect, text: &mut String) -> bool {
+        let id = widget_id(id);
+        let (state, _) = self.interact(id, rect);
+        if self.gui.input.pressed {
+            if state == WidgetState::Active {
+                self.gui.focus = Some(id);
+            } else if self.gui.focus == Some(id) {
+                self.gui.focus = None;
+            }
+        }
+        let mut submitted = false;
+        if self.gui.focus == Some(id) {
+            for key in &self.gui.input.keys {
+                match *key {
+                    GuiKey::Character(c) => text.push(c),
+                    GuiKey::Backspace => {
+                        text.pop();
+                    }
+                    GuiKey::Enter => {
+                        submitted = true;
+                        self.gui.focus = None;
+                    }
+                    GuiKey::Escape => self.gui.focus = None,
+                }
+            }
+        }
+        let focused = self.gui.focus == Some(id);
+        let border = if focused { self.gui.style.accent } else { self.state_color(state) };
+        self.quad(rect, border, None);
+        self.quad(rect.inset(1.0), self.gui.style.widget, None);
+
+        //  The end of the text is what's being typed, so that's the part kept when it's too long
+        let caret = if focused { "|" } else { "" };
+        let room = rect.width - 2.0 * self.gui.style.padding;
+        let mut shown = text.as_str();
+        while !shown.is_empty() && self.text_width(&format!("{}{}", shown, caret)) > room {
+            let mut chars = shown.chars();
+            chars.next();
+            shown = chars.as_str();
+        }
+        let y = rect.y + 0.5 * (rect.height - self.line_height());
+        self.label([rect.x + self.gui.style.padding, y], &format!("{}{}", shown, caret));
+        submitted
+    }
+
+    //  One line per item, scrolled with the wheel. True if the selection changed.
+    pub fn scroll_list(&mut self, id: &str, rect: Rect, items: &[String], selected: &mut Option<usize>) -> bool {
+        let id = widget_id(id);
+        let row_height = self.line_height() + self.gui.style.padding;
+        let visible = ((rect.height / row_height).floor() as usize).max(1);
+        let offset = self.scroll(id, rect, items.len(), visible);
+        self.panel(rect);
+
+        let mut changed = false;
+        for (row, index) in (offset..items.len().min(offset + visible)).enumerate() {
+            let row_rect = Rect::new(rect.x, rect.y + row as f32 * row_height, rect.width, row_height);
+            let (state, clicked) = self.interact(child_id(id, index), row_rect);
+            if clicked && *selected != Some(index) {
+                *selected = Some(index);
+                changed = true;
+            }
+            if *selected == Some(index) {
+                self.quad(row_rect, self.gui.style.accent, None);
+            } else if state != WidgetState::Idle {
+                self.quad(row_rect, self.state_color(state), None);
+            }
+            let position = [row_rect.x + self.gui.style.padding, row_rect.y + 0.5 * self.gui.style.padding];
+            self.label(position, &items[index]);
+        }
+        self.scroll_bar(rect, offset, visible, items.len());
+        changed
+    }
+
+    //  Square cells `cell_size` across, as many columns as fit, scrolled with the wheel a row at a time. True if the
+    //  selection changed.
+    pub fn image_grid(
+        &mut self,
+        id: &str,
+        rect: Rect,
+        images: &[ImageId],
+        cell_size: f32,
+        selected: &mut Option<usize>,
+    ) -> bool {
+        let id = widget_id(id);
+        let columns = ((rect.width / cell_size).floor() as usize).max(1);
+        let visible_rows = ((rect.height / cell_size).floor() as usize).max(1);
+        let rows = images.len().div_ceil(columns);
+        let offset = self.scroll(id, rect, rows, visible_rows);
+        self.panel(rect);
+
+        let mut changed = false;
+        let first = offset * columns;
+        for (cell, index) in (first..images.len().min(first + visible_rows * columns)).enumerate() {
+            let (column, row) = (cell % columns, cell / columns);
+            let cell_rect =
+                Rect::new(rect.x + column as f32 * cell_size, rect.y + row as f32 * cell_size, cell_size, cell_size);
+            let (state, clicked) = self.interact(child_id(id, index), cell_rect);
+            if clicked && *selected != Some(index) {
+                *selected = Some(index);
+                changed = true;
+            }
+            if *selected == Some(index) {
+                self.quad(cell_rect, self.gui.style.accent, None);
+            } else if state != WidgetState::Idle {
+                self.quad(cell_rect, self.state_color(state), None);
+            }
+            self.quad(cell_rect.inset(self.gui.style.padding), [1.0; 4], Some(images[index]));
+        }
+        self.scroll_bar(rect, offset, visible_rows, rows);
+        changed
+    }
+
+    //  Marks the widget as being over the GUI and works out whether it's hovered, held, or was just clicked
+    fn interact(&mut self, id: WidgetId, rect: Rect) -> (WidgetState, bool) {
+        let gui = &mut *self.gui;
+        gui.rects.push(rect);
+        let hovered = !gui.scene_drag && gui.mouse.is_some_and(|mouse| rect.contains(mouse));
+        if hovered && gui.input.pressed {
+            gui.active = Some(id);
+        }
+        let active = gui.active == Some(id);
+        let clicked = active && hovered && gui.input.released;
+        let state = match (active, hovered) {
+            (true, _) => WidgetState::Active,
+            (false, true) => WidgetState::Hovered,
+            (false, false) => WidgetState::Idle,
+        };
+        (state, clicked)
+    }
+
+    //  Applies the wheel if it's over `rect` and returns the first visible row
+    fn scroll(&mut self, id: WidgetId, rect: Rect, rows: usize, visible: usize) -> usize {
+        let max_offset = rows.saturating_sub(visible);
+        let hovered = !self.gui.scene_drag && self.gui.mouse.is_some_and(|mouse| rect.contains(mouse));
+        let offset = self.gui.scroll_offsets.entry(id).or_default();
+        if hovered && self.gui.input.scroll != 0.0 {
+            let lines = self.gui.input.scroll.round() as isize;
+            *offset = offset.saturating_add_signed(-lines);
+        }
+        *offset = (*offset).min(max_offset);
+        *offset
+    }
+
+    fn scroll_bar(&mut self, rect: Rect, offset: usize, visible: usize, rows: usize) {
+        if rows <= visible {
+            return;
+        }
+        let width = 4.0;
+        let height = rect.height * visible as f32 / rows as f32;
+        let y = rect.y + rect.height * offset as f32 / rows as f32;
+        self.quad(Rect::new(rect.x + rect.width - width, y, width, height), self.gui.style.hovered, None);
+    }
+
+    fn centered_label(&mut self, rect: Rect, text: &str) {
+        let x = rect.x + 0.5 * (rect.width - self.text_width(text));
+        let y = rect.y + 0.5 * (rect.height - self.line_height());
+        self.label([x, y], text);
+    }
+
+    fn state_color(&self, state: WidgetState) -> [f32; 4] {
+        match state {
+            WidgetState::Idle => self.gui.style.widget,
+            WidgetState::Hovered => self.gui.style.hovered,
+            WidgetState::Active => self.gui.style.active,
+        }
+    }
+
+    fn quad(&mut self, rect: Rect, color: [f32; 4], image: Option<ImageId>) {
+        self.gui.draw_list.quads.push(GuiQuad { rect, color, image });
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GuiVertex {
+    //  Already in clip space
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex for GuiVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GuiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+//  Draws a DrawList's quads, batched by image. Untextured quads sample a white texel.
+pub struct GuiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    white: wgpu::BindGroup,
+    images: Vec<wgpu::BindGroup>,
+    vertices: Vec<GuiVertex>,
+    batches: Vec<(Option<ImageId>, std::ops::Range<u32>)>,
+    vertex_buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl GuiRenderer {
+    //  Draws to the single sampled surface, after everything else
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, color_format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GUI Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("GUI Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let white_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("GUI White Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &white_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &[255; 4],
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: std::num::NonZeroU32::new(4), rows_per_image: None },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        let white = Self::create_bind_group(
+            device,
+            &layout,
+            &sampler,
+            &white_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GUI Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/gui.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GUI Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("GUI Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[GuiVertex::desc()] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let capacity = 1024;
+        Self {
+            pipeline,
+            layout,
+            sampler,
+            white,
+            images: Vec::new(),
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            vertex_buffer: Self::create_buffer(device, capacity),
+            capacity,
+        }
+    }
+
+    //  For image grids, like brick thumbnails
+    pub fn add_image(&mut self, device: &wgpu::Device, view: &wgpu::TextureView) -> ImageId {
+        self.images.push(Self::create_bind_group(device, &self.layout, &self.sampler, view));
+        self.images.len() - 1
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, draw_list: &DrawList, width: u32, height: u32) {
+        self.vertices.clear();
+        self.batches.clear();
+        let to_clip = |x: f32, y: f32| [x / width as f32 * 2.0 - 1.0, 1.0 - y / height as f32 * 2.0];
+        for quad in &draw_list.quads {
+            let start = self.vertices.len() as u32;
+            let Rect { x, y, width, height } = quad.rect;
+            let (x1, y1) = (x + width, y + height);
+            for (x, y, u, v) in [(x, y, 0.0, 0.0), (x, y1, 0.0, 1.0), (x1, y1, 1.0, 1.0), (x, y, 0.0, 0.0), (x1, y1, 1.0, 1.0), (x1, y, 1.0, 0.0)] {
+                self.vertices.push(GuiVertex { position: to_clip(x, y), tex_coords: [u, v], color: quad.color });
+            }
+            let end = self.vertices.len() as u32;
+            match self.batches.last_mut() {
+                Some((image, range)) if *image == quad.image => range.end = end,
+                _ => self.batches.push((quad.image, start..end)),
+            }
+        }
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.batches.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for (image, vertices) in &self.batches {
+            let bind_group = image.and_then(|image| self.images.get(image)).unwrap_or(&self.white);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(vertices.clone(), 0..1);
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GUI Image Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+            ],
+        })
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GUI Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<GuiVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
@@ -0,0 +1,203 @@
+//  LOD - Cheaper versions of bricks for when they're only a few pixels on screen. Studs are most of a brick's triangles,
+//  so the first step down swaps them for a flat top wearing the stud normal map, and the last is just a box.
+//  Levels are picked from the projected size with some hysteresis, so something sat right on a boundary doesn't flicker.
+
+use cgmath::prelude::*;
+use cgmath::{Point3, Vector3};
+
+use crate::engine::asset_manager::cube_mesh;
+use crate::engine::bounds::{Aabb, BoundingSphere};
+use crate::engine::model::{MeshData, ModelVertex};
+use crate::game::world::STUD_SIZE;
+
+pub const LOD_COUNT: usize = 3;
+//  The material index generate_lods() gives flat top caps, whoever uploads them swaps it for a stud normal map material
+pub const STUD_CAP_MATERIAL: usize = usize::MAX;
+//  How much of the footprint an upward facing plane has to cover to count as the top of the brick rather than a stud
+const TOP_COVERAGE: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Lod {
+    #[default]
+    Full,
+    FlatTop,
+    Impostor,
+}
+
+impl Lod {
+    pub const ALL: [Lod; LOD_COUNT] = [Lod::Full, Lod::FlatTop, Lod::Impostor];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: usize) -> Lod {
+        Self::ALL[index.min(LOD_COUNT - 1)]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodSettings {
+    //  Projected sizes in pixels, below these the next level down is used
+    pub flat_top_below: f32,
+    pub impostor_below: f32,
+    //  Fraction past a boundary something has to get before it switches, in either direction
+    pub hysteresis: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            flat_top_below: 96.0,
+            impostor_below: 24.0,
+            hysteresis: 0.2,
+        }
+    }
+}
+
+impl LodSettings {
+    //  Boundaries nothing can get past, so everything ends up at `lod`. For checking what each level looks like.
+    pub fn forced(lod: Lod) -> Self {
+        let (flat_top_below, impostor_below) = match lod {
+            Lod::Full => (f32::NEG_INFINITY, f32::NEG_INFINITY),
+            Lod::FlatTop => (f32::INFINITY, f32::NEG_INFINITY),
+            Lod::Impostor => (f32::INFINITY, f32::INFINITY),
+        };
+        Self { flat_top_below, impostor_below, hysteresis: 0.0 }
+    }
+
+    //  Every object keeps the level it had last frame and moves off it only once it's clearly past a boundary
+    pub fn select(&self, current: Lod, pixels: f32) -> Lod {
+        let boundaries = [self.flat_top_below, self.impostor_below];
+        let mut lod = current.index();
+        while lod > 0 && pixels > boundaries[lod - 1] * (1.0 + self.hysteresis) {
+            lod -= 1;
+        }
+        while lod < LOD_COUNT - 1 && pixels < boundaries[lod] * (1.0 - self.hysteresis) {
+            lod += 1;
+        }
+        Lod::from_index(lod)
+    }
+}
+
+//  Diameter on screen in pixels. `pixels_per_unit` is how many pixels one unit covers one unit in front of the camera.
+pub fn projected_size(sphere: &BoundingSphere, eye: Point3<f32>, pixels_per_unit: f32) -> f32 {
+    let distance = sphere.center.distance(eye);
+    //  Inside it, as big as it gets. Not infinity, so forced() boundaries still hold, same as the cull shader.
+    if distance <= sphere.radius {
+        return f32::MAX;
+    }
+    2.0 * sphere.radius * pixels_per_unit / distance
+}
+
+//  The lower levels of a model, most detailed first. Models without studs skip the flat top level.
+pub fn generate_lods(meshes: &[MeshData]) -> Vec<(Lod, Vec<MeshData>)> {
+    let aabb = meshes.iter().fold(Aabb::empty(), |aabb, m| aabb.union(&m.bounds().0));
+    if aabb.is_empty() {
+        return Vec::new();
+    }
+    let mut lods = Vec::new();
+    if let Some(flat) = flat_top(meshes, &aabb) {
+        lods.push((Lod::FlatTop, flat));
+    }
+    lods.push((Lod::Impostor, vec![box_impostor(meshes, &aabb)]));
+    lods
+}
+
+fn triangle(mesh: &MeshData, tri: &[u32]) -> [Vector3<f32>; 3] {
+    [0, 1, 2].map(|i| Vector3::from(mesh.vertices[tri[i] as usize].position))
+}
+
+//  Finds the top surface of the brick, the highest upward facing plane covering a good part of its footprint, and
+//  replaces everything above it (the studs) and the surface itself with one quad. None if nothing sticks up.
+fn flat_top(meshes: &[MeshData], aabb: &Aabb) -> Option<Vec<MeshData>> {
+    let footprint = (aabb.max.x - aabb.min.x) * (aabb.max.z - aabb.min.z);
+    let epsilon = (aabb.max.y - aabb.min.y).max(f32::EPSILON) * 1e-3;
+    let is_up = |[a, b, c]: [Vector3<f32>; 3]| {
+        let n = (b - a).cross(c - a);
+        (n.magnitude2() > 0.0 && n.normalize().y > 0.99).then(|| n.magnitude() / 2.0)
+    };
+
+    //  (height, area) of every upward facing plane
+    let mut planes: Vec<(f32, f32)> = Vec::new();
+    for mesh in meshes {
+        for tri in mesh.indices.chunks_exact(3) {
+            let corners = triangle(mesh, tri);
+            if let Some(area) = is_up(corners) {
+                let y = corners[0].y;
+                match planes.iter_mut().find(|(py, _)| (py - y).abs() <= epsilon) {
+                    Some(plane) => plane.1 += area,
+                    None => planes.push((y, area)),
+                }
+            }
+        }
+    }
+    let top = planes
+        .iter()
+        .filter(|(_, area)| *area >= footprint * TOP_COVERAGE)
+        .map(|(y, _)| *y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    if !top.is_finite() || aabb.max.y <= top + epsilon {
+        return None;
+    }
+
+    let mut cap = Aabb::empty();
+    let mut flat = Vec::with_capacity(meshes.len() + 1);
+    for mesh in meshes {
+        let mut indices = Vec::with_capacity(mesh.indices.len());
+        for tri in mesh.indices.chunks_exact(3) {
+            let corners = triangle(mesh, tri);
+            if corners.iter().any(|p| p.y > top + epsilon) {
+                continue;
+            }
+            if is_up(corners).is_some() && (corners[0].y - top).abs() <= epsilon {
+                corners.iter().for_each(|p| cap.grow(Point3::from_vec(*p)));
+                continue;
+            }
+            indices.extend_from_slice(tri);
+        }
+        if !indices.is_empty() {
+            flat.push(MeshData {
+                name: format!("{} (flat top)", mesh.name),
+                vertices: mesh.vertices.clone(),
+                indices,
+                material: mesh.material,
+            });
+        }
+    }
+
+    let (min, max) = (cap.min, cap.max);
+    let corners = [[min.x, min.z], [min.x, max.z], [max.x, max.z], [max.x, min.z]];
+    flat.push(MeshData {
+        name: "stud cap".to_string(),
+        vertices: corners
+            .iter()
+            .map(|&[x, z]| ModelVertex {
+                position: [x, top, z],
+                //  In studs like chunk meshes, so the stud normal map repeats once per stud
+                tex_coords: [x / STUD_SIZE, z / STUD_SIZE],
+                normal: [0.0, 1.0, 0.0],
+                tangent: [1.0, 0.0, 0.0],
+                bitangent: [0.0, 0.0, -1.0],
+            })
+            .collect(),
+        indices: vec![0, 1, 2, 0, 2, 3],
+        material: STUD_CAP_MATERIAL,
+    });
+    Some(flat)
+}
+
+//  The unit cube stretched over the bounds, in the first mesh's material
+fn box_impostor(meshes: &[MeshData], aabb: &Aabb) -> MeshData {
+    let mut mesh = cube_mesh("impostor");
+    let (center, half) = (aabb.center(), aabb.half_extents());
+    for v in &mut mesh.vertices {
+        v.position = [
+            center.x + v.position[0] * half.x,
+            center.y + v.position[1] * half.y,
+            center.z + v.position[2] * half.z,
+        ];
+    }
+    mesh.material = meshes.first().map(|m| m.material).unwrap_or(0);
+    mesh
+}
@@ -1,3 +1,27 @@
 pub mod model;
 pub mod texture;
-pub mod resources;
\ No newline at end of file
+pub mod resources;
+pub mod vfs;
+pub mod geometry;
+pub mod obj_loading;
+pub mod assets;
+pub mod asset_manager;
+pub mod gltf_export;
+pub mod print_export;
+pub mod bounds;
+pub mod culling;
+pub mod gpu_culling;
+pub mod chunk_mesh;
+pub mod lod;
+pub mod settings;
+pub mod msaa;
+pub mod post_process;
+pub mod ssao;
+pub mod deferred;
+pub mod outline;
+pub mod debug_view;
+pub mod debug_draw;
+pub mod font;
+pub mod text;
+pub mod gui;
+pub mod gamepad;
@@ -1,6 +1,14 @@
 use std::ops::Range;
 
+use wgpu::util::DeviceExt;
+
+use cgmath::Point3;
+
 use crate::texture;
+use crate::game::uniform::MaterialUniform;
+use crate::engine::assets::{Assets, Handle};
+use crate::engine::bounds::{Aabb, BoundingSphere};
+use crate::engine::lod::Lod;
 
 pub trait Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -56,7 +64,48 @@ impl Vertex for ModelVertex {
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
-    pub materials: Vec<Material>,
+    //  Bounds of all the meshes together, in model space
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    //  Cheaper versions for drawing it far away, least detailed last. This is the Full level.
+    pub lods: Vec<(Lod, Model)>,
+}
+
+impl Model {
+    pub fn new(meshes: Vec<Mesh>) -> Self {
+        let aabb = meshes.iter().fold(Aabb::empty(), |aabb, m| aabb.union(&m.aabb));
+        let center = if aabb.is_empty() { Point3::new(0.0, 0.0, 0.0) } else { aabb.center() };
+        let spheres = meshes.iter().map(|m| m.bounding_sphere).collect::<Vec<_>>();
+        let bounding_sphere = BoundingSphere::enclosing(center, &spheres);
+        Self { meshes, aabb, bounding_sphere, lods: Vec::new() }
+    }
+
+    pub fn with_lods(mut self, mut lods: Vec<(Lod, Model)>) -> Self {
+        lods.sort_by_key(|(lod, _)| *lod);
+        self.lods = lods;
+        self
+    }
+
+    //  The version to draw at `lod`, or the closest more detailed one if the model doesn't have that level
+    pub fn lod(&self, lod: Lod) -> &Model {
+        self.lods.iter().rev().find(|(l, _)| *l <= lod).map(|(_, m)| m).unwrap_or(self)
+    }
+
+    //  None unless every mesh kept its geometry when it was uploaded
+    pub fn mesh_data(&self) -> Option<Vec<MeshData>> {
+        self.meshes
+            .iter()
+            .map(|m| {
+                let geometry = m.geometry.as_ref()?;
+                Some(MeshData {
+                    name: m.name.clone(),
+                    vertices: geometry.vertices.clone(),
+                    indices: geometry.indices.clone(),
+                    material: m.material_index,
+                })
+            })
+            .collect()
+    }
 }
 
 pub struct Mesh {
@@ -64,13 +113,80 @@ pub struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    //  Materials are shared between models through the asset store
+    pub material: Handle<Material>,
+    //  Which of its file's materials `material` was made from, see MeshData::material
+    pub material_index: usize,
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    //  Only kept when asked for at upload, for picking, collision and exporting
+    pub geometry: Option<MeshGeometry>,
+}
+
+//  CPU copy of what's in a mesh's vertex and index buffers
+#[derive(Debug, Clone)]
+pub struct MeshGeometry {
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+}
+
+//  CPU side geometry produced by the loaders, turned into a Mesh once it's uploaded
+#[derive(Debug)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    //  Index into the materials of the file this mesh came from
     pub material: usize,
 }
 
+impl MeshData {
+    pub fn upload(&self, device: &wgpu::Device, material: Handle<Material>, keep_geometry: bool) -> Mesh {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", self.name)),
+            contents: bytemuck::cast_slice(&self.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", self.name)),
+            contents: bytemuck::cast_slice(&self.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let (aabb, bounding_sphere) = self.bounds();
+        Mesh {
+            name: self.name.clone(),
+            vertex_buffer,
+            index_buffer,
+            num_elements: self.indices.len() as u32,
+            material,
+            material_index: self.material,
+            aabb,
+            bounding_sphere,
+            geometry: keep_geometry.then(|| MeshGeometry {
+                vertices: self.vertices.clone(),
+                indices: self.indices.clone(),
+            }),
+        }
+    }
+
+    pub fn bounds(&self) -> (Aabb, BoundingSphere) {
+        let points = self.vertices.iter().map(|v| Point3::from(v.position)).collect::<Vec<_>>();
+        let aabb = Aabb::from_points(points.iter().copied());
+        (aabb, BoundingSphere::from_points(&points, &aabb))
+    }
+
+    pub fn upload_size(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<ModelVertex>() + self.indices.len() * std::mem::size_of::<u32>()
+    }
+}
+
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: texture::Texture,
-    pub normal_texture: texture::Texture,
+    //  The bind group keeps the GPU textures alive, these keep them from being evicted from the asset store
+    pub textures: Vec<Handle<texture::Texture>>,
+    pub uniform: MaterialUniform,
+    pub uniform_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 
@@ -78,10 +194,17 @@ impl Material {
     pub fn new(
         device: &wgpu::Device,
         name: &str,
-        diffuse_texture: texture::Texture,
-        normal_texture: texture::Texture,
+        diffuse_texture: &texture::Texture,
+        normal_texture: &texture::Texture,
+        uniform: MaterialUniform,
         layout: &wgpu::BindGroupLayout,
     ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout,
             entries: &[
@@ -101,17 +224,53 @@ impl Material {
                     binding: 3,
                     resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
             ],
             label: Some(name),
         });
 
         Self {
         name: String::from(name),
-        diffuse_texture,
-        normal_texture,
+        textures: Vec::new(),
+        uniform,
+        uniform_buffer,
         bind_group,
         }
     }
+
+    pub fn with_textures(mut self, textures: Vec<Handle<texture::Texture>>) -> Self {
+        self.textures = textures;
+        self
+    }
+
+    //  Drawn after everything opaque, blended and back to front
+    pub fn is_transparent(&self) -> bool {
+        self.uniform.opacity < 1.0
+    }
+}
+
+//  How transparent meshes go over what's behind them, the same on the forward and deferred render paths
+pub const TRANSPARENT_BLEND: wgpu::BlendState = wgpu::BlendState::ALPHA_BLENDING;
+
+//  Which of a model's meshes to draw, going by their materials
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surfaces {
+    Opaque,
+    Transparent,
+    All,
+}
+
+impl Surfaces {
+    pub fn includes(self, material: &Material) -> bool {
+        match self {
+            Surfaces::Opaque => !material.is_transparent(),
+            Surfaces::Transparent => material.is_transparent(),
+            Surfaces::All => true,
+        }
+    }
 }
 
 pub trait DrawModel<'a> {
@@ -133,21 +292,39 @@ pub trait DrawModel<'a> {
     fn draw_model(
         &mut self, 
         model: &'a Model, 
+        materials: &'a Assets<Material>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    //  Each mesh with its own material, skipping the ones `surfaces` leaves out
     fn draw_model_instanced(
         &mut self,
         model: &'a Model,
+        materials: &'a Assets<Material>,
+        surfaces: Surfaces,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
-    fn draw_model_instanced_with_material(
+    //  The instance count comes from a DrawIndexedIndirect in `indirect_buffer` instead, filled in by the GPU culler
+    fn draw_mesh_indirect(
         &mut self,
-        model: &'a Model,
+        mesh: &'a Mesh,
         material: &'a Material,
-        instances: Range<u32>,
+        indirect_buffer: &'a wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+    //  One DrawIndexedIndirect per mesh, one after the other starting at `indirect_offset`, skipped meshes included
+    #[allow(clippy::too_many_arguments)]
+    fn draw_model_indirect(
+        &mut self,
+        model: &'a Model,
+        materials: &'a Assets<Material>,
+        surfaces: Surfaces,
+        indirect_buffer: &'a wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
@@ -186,114 +363,64 @@ where
     fn draw_model(
         &mut self, 
         model: &'b Model, 
+        materials: &'b Assets<Material>,
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
-        self.draw_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
+        self.draw_model_instanced(model, materials, Surfaces::All, 0..1, camera_bind_group, light_bind_group);
     }
 
     fn draw_model_instanced(
         &mut self,
         model: &'b Model,
+        materials: &'b Assets<Material>,
+        surfaces: Surfaces,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
         for mesh in &model.meshes {
-            let material = &model.materials[mesh.material];
-            self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
+            //  Only missing if the material failed to build, the asset manager fills in placeholders while loading
+            if let Some(material) = materials.get(&mesh.material).filter(|m| surfaces.includes(m)) {
+                self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
+            }
         }
     }
 
-    fn draw_model_instanced_with_material(
-        &mut self,
-        model: &'a Model,
-        material: &'a Material,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    ) {
-        for mesh in &model.meshes {
-            self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
-        }
-    }
-}
-
-pub trait DrawLight<'a> {
-    fn draw_light_mesh(
+    fn draw_mesh_indirect(
         &mut self,
-        mesh: &'a Mesh,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    fn draw_light_mesh_instanced(
-        &mut self,
-        mesh: &'a Mesh,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-
-    fn draw_light_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    fn draw_light_model_instanced(
-        &mut self,
-        model: &'a Model,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-}
-
-impl <'a, 'b>DrawLight<'b> for wgpu::RenderPass<'a>
-where
-    'b: 'a,
-{
-    fn draw_light_mesh(
-        &mut self,
-        mesh: &'a Mesh,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    ) {
-        self.draw_light_mesh_instanced(mesh, 0..1, camera_bind_group, light_bind_group);
-    }
-
-    fn draw_light_mesh_instanced(
-        &mut self,
-        mesh: &'a Mesh,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
+        mesh: &'b Mesh,
+        material: &'b Material,
+        indirect_buffer: &'b wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        self.set_bind_group(0, camera_bind_group, &[]);
-        self.set_bind_group(1, light_bind_group, &[]);
-        self.draw_indexed(0..mesh.num_elements, 0, instances);
-    }
-
-    fn draw_light_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    ) {
-        self.draw_light_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
+        self.set_bind_group(0, &material.bind_group, &[]);
+        self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(2, light_bind_group, &[]);
+        self.draw_indexed_indirect(indirect_buffer, indirect_offset);
     }
 
-    fn draw_light_model_instanced(
+    #[allow(clippy::too_many_arguments)]
+    fn draw_model_indirect(
         &mut self,
-        model: &'a Model,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
+        model: &'b Model,
+        materials: &'b Assets<Material>,
+        surfaces: Surfaces,
+        indirect_buffer: &'b wgpu::Buffer,
+        indirect_offset: wgpu::BufferAddress,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
     ) {
-        for mesh in &model.meshes {
-            self.draw_light_mesh_instanced(mesh, instances.clone(), camera_bind_group, light_bind_group);
+        let stride = std::mem::size_of::<wgpu::util::DrawIndexedIndirect>() as wgpu::BufferAddress;
+        for (i, mesh) in model.meshes.iter().enumerate() {
+            if let Some(material) = materials.get(&mesh.material).filter(|m| surfaces.includes(m)) {
+                let offset = indirect_offset + i as wgpu::BufferAddress * stride;
+                self.draw_mesh_indirect(mesh, material, indirect_buffer, offset, camera_bind_group, light_bind_group);
+            }
         }
     }
-}
\ No newline at end of file
+}
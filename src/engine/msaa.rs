@@ -0,0 +1,86 @@
+//  MSAA - The scene is drawn into a multisampled colour target, which is resolved into the surface at the end of the
+//  pass. With a sample count of 1 there's no extra target and everything is drawn straight into the surface.
+
+use crate::engine::texture;
+
+//  The only counts offered. wgpu 0.14 has no per-format flags for 2 or 8 samples and its render passes reject anything
+//  but 1 and 4, so asking for 2 or 8 ends up with the closest one below.
+pub const SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+//  The counts out of SAMPLE_COUNTS the adapter can draw into both formats with and resolve the colour from
+pub fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+) -> Vec<u32> {
+    use wgpu::TextureFormatFeatureFlags as Flags;
+    let color = adapter.get_texture_format_features(color_format).flags;
+    let depth = adapter.get_texture_format_features(depth_format).flags;
+    let multisampled = color.contains(Flags::MULTISAMPLE | Flags::MULTISAMPLE_RESOLVE) && depth.contains(Flags::MULTISAMPLE);
+    SAMPLE_COUNTS
+        .iter()
+        .copied()
+        .filter(|&count| count == 1 || multisampled)
+        .collect()
+}
+
+//  The most samples up to `requested` that `supported` allows, 1 if it doesn't allow any of them
+pub fn closest_supported(supported: &[u32], requested: u32) -> u32 {
+    supported.iter().copied().filter(|&count| count <= requested).max().unwrap_or(1)
+}
+
+//  The next count after `current`, wrapping back round to the first
+pub fn next_sample_count(supported: &[u32], current: u32) -> u32 {
+    supported
+        .iter()
+        .copied()
+        .find(|&count| count > current)
+        .or_else(|| supported.first().copied())
+        .unwrap_or(1)
+}
+
+pub struct MsaaTarget {
+    pub sample_count: u32,
+    //  None with a sample count of 1
+    color: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl MsaaTarget {
+    //  Needs recreating whenever the surface is resized or the sample count changes
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Self {
+        let color = (sample_count > 1).then(|| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Colour Target"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        });
+        Self { sample_count, color }
+    }
+
+    //  The depth buffer to go with the colour target, it has to have the same number of samples
+    pub fn create_depth_texture(&self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> texture::DepthTexture {
+        texture::Texture::create_depth_texture(device, config, self.sample_count, "depth_texture")
+    }
+
+    //  The view to draw into and the one to resolve it into, for a colour attachment that ends up in `target`
+    pub fn color_attachment<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+    ) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+        match &self.color {
+            Some((_, view)) => (view, Some(target)),
+            None => (target, None),
+        }
+    }
+}
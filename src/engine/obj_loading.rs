@@ -0,0 +1,233 @@
+//  OBJ IMPORT - Turns OBJ/MTL text into ModelData on the CPU. Missing normals and UVs are generated instead of
+//  panicking, and anything that can't be recovered from comes back as an ImportError naming the file.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{BufReader, Cursor};
+
+use crate::engine::geometry::{self, NormalGeneration};
+use crate::engine::model::{MeshData, ModelVertex};
+use crate::engine::resources::{MaterialData, ModelData};
+use crate::engine::vfs;
+use crate::game::uniform::MaterialUniform;
+
+#[derive(Debug)]
+pub enum ImportErrorKind {
+    //  tobj couldn't parse the file
+    Parse(tobj::LoadError),
+    MaterialLibrary { path: String, reason: String },
+    //  An attribute array doesn't have one entry per vertex
+    MalformedAttribute { mesh: String, attribute: &'static str, len: usize, expected: usize },
+    IndexOutOfRange { mesh: String, index: u32, vertex_count: usize },
+    NoGeometry,
+}
+
+#[derive(Debug)]
+pub struct ImportError {
+    pub file: String,
+    pub kind: ImportErrorKind,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: ", self.file)?;
+        match &self.kind {
+            ImportErrorKind::Parse(e) => write!(f, "failed to parse OBJ ({})", e),
+            ImportErrorKind::MaterialLibrary { path, reason } => {
+                write!(f, "failed to load material library {:?} ({})", path, reason)
+            }
+            ImportErrorKind::MalformedAttribute { mesh, attribute, len, expected } => write!(
+                f, "mesh {:?} has {} {} values, expected {}", mesh, len, attribute, expected,
+            ),
+            ImportErrorKind::IndexOutOfRange { mesh, index, vertex_count } => write!(
+                f, "mesh {:?} references vertex {} but only has {}", mesh, index, vertex_count,
+            ),
+            ImportErrorKind::NoGeometry => write!(f, "contains no triangles"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    //  Only used when the file has no normals
+    pub normals: NormalGeneration,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self { normals: NormalGeneration::Flat }
+    }
+}
+
+//  `load_file` is given paths relative to the resource root, it's how MTL files are found
+pub fn parse_obj<F>(file_name: &str, obj_text: &str, options: &ImportOptions, load_file: F) -> Result<ModelData, ImportError>
+where
+    F: Fn(&str) -> anyhow::Result<String>,
+{
+    let error = |kind| ImportError { file: file_name.to_string(), kind };
+
+    //  MTL files and textures are referenced relative to the .obj, not the resource root
+    let obj_dir = vfs::parent_dir(&vfs::normalize_path(file_name)).to_string();
+    let relative = |p: &str| vfs::normalize_path(&format!("{}/{}", obj_dir, p));
+
+    //  Remember which MTL file the materials came from so they can be shared with other models using it,
+    //  and why it failed since tobj only gives us a LoadError
+    let mtl_files = RefCell::new(Vec::new());
+    let mtl_error = RefCell::new(None);
+
+    let mut obj_reader = BufReader::new(Cursor::new(obj_text));
+    let (models, obj_materials) = tobj::load_obj_buf(
+        &mut obj_reader,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+        |p| {
+            let path = relative(&p.to_string_lossy());
+            mtl_files.borrow_mut().push(path.clone());
+            match load_file(&path) {
+                Ok(mat_text) => tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text))),
+                Err(e) => {
+                    *mtl_error.borrow_mut() = Some((path, format!("{:#}", e)));
+                    Err(tobj::LoadError::OpenFileFailed)
+                }
+            }
+        },
+    )
+    .map_err(|e| error(ImportErrorKind::Parse(e)))?;
+
+    let obj_materials = obj_materials.map_err(|e| {
+        let (path, reason) = mtl_error.take().unwrap_or_else(|| {
+            (mtl_files.borrow().first().cloned().unwrap_or_default(), e.to_string())
+        });
+        error(ImportErrorKind::MaterialLibrary { path, reason })
+    })?;
+
+    let mtl_file = mtl_files.into_inner().into_iter().next().unwrap_or_else(|| vfs::normalize_path(file_name));
+    let materials = obj_materials
+        .into_iter()
+        .map(|m| MaterialData {
+            key: format!("{}#{}", mtl_file, m.name),
+            diffuse_path: (!m.diffuse_texture.is_empty()).then(|| relative(&m.diffuse_texture)),
+            normal_path: (!m.normal_texture.is_empty()).then(|| relative(&m.normal_texture)),
+            uniform: material_uniform(&m),
+            name: m.name,
+        })
+        .collect();
+
+    let mut meshes = Vec::new();
+    for m in models {
+        let mesh = build_mesh(&m.name, m.mesh, options).map_err(error)?;
+        if !mesh.indices.is_empty() {
+            meshes.push(mesh);
+        }
+    }
+    if meshes.is_empty() {
+        return Err(error(ImportErrorKind::NoGeometry));
+    }
+
+    Ok(ModelData {
+        name: file_name.to_string(),
+        meshes,
+        materials,
+    })
+}
+
+//  tobj leaves Ke in unknown_param, everything else we need it parses for us
+pub fn material_uniform(m: &tobj::Material) -> MaterialUniform {
+    let emissive = m.unknown_param
+        .get("Ke")
+        .and_then(|value| {
+            let parts = value.split_whitespace().map(|x| x.parse::<f32>().ok()).collect::<Option<Vec<_>>>()?;
+            match parts[..] {
+                [r, g, b] => Some([r, g, b]),
+                //  A single value is a grey
+                [x] => Some([x; 3]),
+                _ => None,
+            }
+        })
+        .unwrap_or([0.0; 3]);
+
+    //  tobj can't tell us if Kd was missing, it just leaves it black. With a diffuse map that's never what was meant.
+    let diffuse = if m.diffuse == [0.0; 3] && !m.diffuse_texture.is_empty() { [1.0; 3] } else { m.diffuse };
+
+    MaterialUniform {
+        diffuse,
+        opacity: m.dissolve.clamp(0.0, 1.0),
+        specular: m.specular,
+        //  Ns of 0 would make pow() blow up in the shader
+        shininess: m.shininess.max(1.0),
+        emissive,
+        _padding: 0,
+    }
+}
+
+//  Checks a mesh tobj parsed hangs together before turning it into vertices. tobj already rejects faces pointing past
+//  the end of the file's vertices, this catches meshes put together some other way.
+pub fn build_mesh(name: &str, mesh: tobj::Mesh, options: &ImportOptions) -> Result<MeshData, ImportErrorKind> {
+    let malformed = |attribute, len, expected| ImportErrorKind::MalformedAttribute {
+        mesh: name.to_string(), attribute, len, expected,
+    };
+
+    if !mesh.positions.len().is_multiple_of(3) {
+        return Err(malformed("position", mesh.positions.len(), mesh.positions.len() / 3 * 3));
+    }
+    let vertex_count = mesh.positions.len() / 3;
+
+    let has_texcoords = !mesh.texcoords.is_empty();
+    if has_texcoords && mesh.texcoords.len() != vertex_count * 2 {
+        return Err(malformed("texcoord", mesh.texcoords.len(), vertex_count * 2));
+    }
+    let has_normals = !mesh.normals.is_empty();
+    if has_normals && mesh.normals.len() != vertex_count * 3 {
+        return Err(malformed("normal", mesh.normals.len(), vertex_count * 3));
+    }
+    if !mesh.indices.len().is_multiple_of(3) {
+        return Err(malformed("index", mesh.indices.len(), mesh.indices.len() / 3 * 3));
+    }
+    if let Some(&index) = mesh.indices.iter().find(|&&i| i as usize >= vertex_count) {
+        return Err(ImportErrorKind::IndexOutOfRange { mesh: name.to_string(), index, vertex_count });
+    }
+
+    let mut vertices = (0..vertex_count)
+        .map(|i| ModelVertex {
+            position: [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ],
+            tex_coords: if has_texcoords {
+                [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]]
+            } else {
+                [0.0; 2]
+            },
+            normal: if has_normals {
+                [mesh.normals[i * 3], mesh.normals[i * 3 + 1], mesh.normals[i * 3 + 2]]
+            } else {
+                [0.0; 3]
+            },
+            //  Calculate tangent & bitangent later
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
+        })
+        .collect::<Vec<_>>();
+    let mut indices = mesh.indices;
+
+    if !has_texcoords {
+        geometry::planar_uvs(&mut vertices);
+    }
+    if !has_normals {
+        geometry::generate_normals(&mut vertices, &mut indices, options.normals);
+    }
+    geometry::compute_tangents(&mut vertices, &indices);
+
+    Ok(MeshData {
+        name: name.to_string(),
+        vertices,
+        indices,
+        material: mesh.material_id.unwrap_or(0),
+    })
+}
@@ -0,0 +1,206 @@
+//  OUTLINE - Selection outlines and the hover outline, drawn at the end of the scene pass with the stencil buffer.
+//  The mask pipeline marks where flagged instances are, then the outline pipeline draws them slightly bigger wherever
+//  the stencil isn't marked, leaving a rim. Instances pick up their flags from InstanceRaw.
+
+use crate::engine::model::{ModelVertex, Vertex};
+use crate::engine::texture;
+use crate::game::instance::InstanceRaw;
+
+//  What the mask pipeline writes to the stencil buffer, set with RenderPass::set_stencil_reference
+pub const STENCIL_REFERENCE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlineStyle {
+    //  Linear RGBA, alpha blended over the scene
+    pub selected_color: [f32; 4],
+    pub hover_color: [f32; 4],
+    //  In pixels
+    pub width: f32,
+}
+
+impl Default for OutlineStyle {
+    fn default() -> Self {
+        Self {
+            selected_color: [1.0, 0.4, 0.0, 1.0],
+            hover_color: [1.0, 1.0, 1.0, 0.6],
+            width: 3.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    selected_color: [f32; 4],
+    hover_color: [f32; 4],
+    viewport: [f32; 2],
+    width: f32,
+    _padding: u32,
+}
+
+pub struct Outline {
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    style: OutlineStyle,
+    //  Both draw instances with the texture, camera and light groups like the model pipelines, plus bind_group at 3
+    pub mask: wgpu::RenderPipeline,
+    pub outline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+    //  `layouts` are the texture, camera and light bind group layouts, as for the model pipelines
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Buffer"),
+            size: std::mem::size_of::<OutlineUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let (mask, outline) = Self::create_pipelines(device, layouts, &layout, color_format, sample_count);
+        Self { layout, buffer, bind_group, style: OutlineStyle::default(), mask, outline }
+    }
+
+    //  The pipelines have to match the scene pass's sample count
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        (self.mask, self.outline) = Self::create_pipelines(device, layouts, &self.layout, color_format, sample_count);
+    }
+
+    pub fn style(&self) -> OutlineStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: OutlineStyle) {
+        self.style = style;
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    //  Needs the size of the target the outlines are drawn to, since their width is in pixels
+    pub fn update(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[OutlineUniform {
+            selected_color: self.style.selected_color,
+            hover_color: self.style.hover_color,
+            viewport: [width as f32, height as f32],
+            width: self.style.width,
+            _padding: 0,
+        }]));
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 3],
+        outline_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        let [texture_layout, camera_layout, light_layout] = *layouts;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/outline.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[texture_layout, camera_layout, light_layout, outline_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point, write_mask, stencil_face, stencil_write_mask| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point,
+                    buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask,
+                    })],
+                }),
+                //  No culling, the mask has to cover the whole silhouette
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState {
+                        front: stencil_face,
+                        back: stencil_face,
+                        read_mask: 0xff,
+                        write_mask: stencil_write_mask,
+                    },
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+        let mask = pipeline(
+            "Outline Mask Pipeline",
+            "vs_mask",
+            wgpu::ColorWrites::empty(),
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Always,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Replace,
+            },
+            0xff,
+        );
+        let outline = pipeline(
+            "Outline Pipeline",
+            "vs_outline",
+            wgpu::ColorWrites::ALL,
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::NotEqual,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            },
+            0,
+        );
+        (mask, outline)
+    }
+}
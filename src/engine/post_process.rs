@@ -0,0 +1,417 @@
+//  POST PROCESS - Screen space anti-aliasing. With one of the modes on, the scene is drawn into an offscreen colour
+//  target instead of the surface and these passes write the result into the surface.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Vector2};
+use wgpu::util::DeviceExt;
+
+use crate::engine::settings::AntiAliasing;
+
+const EDGES_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg8Unorm;
+const WEIGHTS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+//  Points in the jitter sequence before it repeats
+const JITTER_SAMPLES: u32 = 8;
+//  Share of each new frame in the history, lower is smoother but slower to catch up
+const TAA_BLEND: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    blend: f32,
+    history_valid: u32,
+    _padding: [u32; 2],
+}
+
+//  Element `index` of the Halton sequence in `base`, evenly spread points in [0, 1)
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+//  Sub-pixel offset for `frame` in NDC, for Projection::set_jitter. Within half a pixel of the centre either way.
+pub fn taa_jitter(frame: u32, width: u32, height: u32) -> Vector2<f32> {
+    //  Halton starts at 0, which would put the first sample in the corner
+    let index = frame % JITTER_SAMPLES + 1;
+    let pixel = Vector2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5);
+    Vector2::new(pixel.x * 2.0 / width.max(1) as f32, pixel.y * 2.0 / height.max(1) as f32)
+}
+
+//  A single sampled colour texture that's drawn to by one pass and read by the next
+pub(crate) struct Target {
+    _texture: wgpu::Texture,
+    pub(crate) view: wgpu::TextureView,
+}
+
+impl Target {
+    pub(crate) fn new(device: &wgpu::Device, label: &str, format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { _texture: texture, view }
+    }
+}
+
+//  Everything that depends on the size of the screen
+struct Targets {
+    scene: Target,
+    edges: Target,
+    weights: Target,
+    history: [Target; 2],
+    fxaa_bind_group: wgpu::BindGroup,
+    edges_bind_group: wgpu::BindGroup,
+    weights_bind_group: wgpu::BindGroup,
+    blend_bind_group: wgpu::BindGroup,
+    //  [i] reads history[i] and writes history[1 - i]. Only there with a single sampled depth buffer.
+    taa_bind_groups: Option<[wgpu::BindGroup; 2]>,
+}
+
+pub struct PostProcess {
+    format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    input_layout: wgpu::BindGroupLayout,
+    blend_layout: wgpu::BindGroupLayout,
+    taa_layout: wgpu::BindGroupLayout,
+    fxaa_pipeline: wgpu::RenderPipeline,
+    edges_pipeline: wgpu::RenderPipeline,
+    weights_pipeline: wgpu::RenderPipeline,
+    blend_pipeline: wgpu::RenderPipeline,
+    taa_pipeline: wgpu::RenderPipeline,
+    taa_buffer: wgpu::Buffer,
+    targets: Targets,
+    //  Which history texture holds the last frame
+    history_index: usize,
+    history_valid: bool,
+    prev_view_proj: Matrix4<f32>,
+    frame: u32,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        depth_sample_count: u32,
+    ) -> Self {
+        let texture = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            count: None,
+        };
+        let sampler = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let input_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Process Input Bind Group Layout"),
+            entries: &[texture(0, true), sampler],
+        });
+        let blend_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SMAA Blend Bind Group Layout"),
+            entries: &[texture(0, true), sampler, texture(2, true)],
+        });
+        let taa_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA Bind Group Layout"),
+            entries: &[
+                texture(0, true),
+                sampler,
+                texture(2, true),
+                //  The depth buffer, which can't be filtered
+                texture(3, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline = |label, layout: &wgpu::BindGroupLayout, module: &wgpu::ShaderModule, entry_point, formats: &[wgpu::TextureFormat]| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            let targets = formats
+                .iter()
+                .map(|&format| Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::ALL }))
+                .collect::<Vec<_>>();
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState { module, entry_point: "vs_main", buffers: &[] },
+                fragment: Some(wgpu::FragmentState { module, entry_point, targets: &targets }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let shader = |label, source: &str| device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let fxaa_shader = shader("FXAA Shader", load_file::load_str!("../../res/shaders/fxaa.wgsl"));
+        let smaa_shader = shader("SMAA Shader", load_file::load_str!("../../res/shaders/smaa.wgsl"));
+        let taa_shader = shader("TAA Shader", load_file::load_str!("../../res/shaders/taa.wgsl"));
+        let format = config.format;
+
+        let fxaa_pipeline = pipeline("FXAA Pipeline", &input_layout, &fxaa_shader, "fs_main", &[format]);
+        let edges_pipeline = pipeline("SMAA Edges Pipeline", &input_layout, &smaa_shader, "fs_edges", &[EDGES_FORMAT]);
+        let weights_pipeline = pipeline("SMAA Weights Pipeline", &input_layout, &smaa_shader, "fs_weights", &[WEIGHTS_FORMAT]);
+        let blend_pipeline = pipeline("SMAA Blend Pipeline", &blend_layout, &smaa_shader, "fs_blend", &[format]);
+        let taa_pipeline = pipeline("TAA Pipeline", &taa_layout, &taa_shader, "fs_main", &[format, format]);
+
+        let taa_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Buffer"),
+            contents: bytemuck::cast_slice(&[TaaUniform {
+                inv_view_proj: Matrix4::identity().into(),
+                prev_view_proj: Matrix4::identity().into(),
+                blend: TAA_BLEND,
+                history_valid: 0,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let targets = Self::create_targets(
+            device,
+            config,
+            (depth_view, depth_sample_count),
+            &sampler,
+            [&input_layout, &blend_layout, &taa_layout],
+            &taa_buffer,
+        );
+        Self {
+            format,
+            sampler,
+            input_layout,
+            blend_layout,
+            taa_layout,
+            fxaa_pipeline,
+            edges_pipeline,
+            weights_pipeline,
+            blend_pipeline,
+            taa_pipeline,
+            taa_buffer,
+            targets,
+            history_index: 0,
+            history_valid: false,
+            prev_view_proj: Matrix4::identity(),
+            frame: 0,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        (depth_view, depth_sample_count): (&wgpu::TextureView, u32),
+        sampler: &wgpu::Sampler,
+        [input_layout, blend_layout, taa_layout]: [&wgpu::BindGroupLayout; 3],
+        taa_buffer: &wgpu::Buffer,
+    ) -> Targets {
+        let (width, height) = (config.width, config.height);
+        let scene = Target::new(device, "Scene Colour Target", config.format, width, height);
+        let edges = Target::new(device, "SMAA Edges Target", EDGES_FORMAT, width, height);
+        let weights = Target::new(device, "SMAA Weights Target", WEIGHTS_FORMAT, width, height);
+        let history = [
+            Target::new(device, "TAA History Target", config.format, width, height),
+            Target::new(device, "TAA History Target", config.format, width, height),
+        ];
+
+        fn view(binding: u32, view: &wgpu::TextureView) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) }
+        }
+        let sampler = wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) };
+        let bind_group = |label, layout, entries: &[wgpu::BindGroupEntry]| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some(label), layout, entries })
+        };
+        let fxaa_bind_group = bind_group("FXAA Bind Group", input_layout, &[view(0, &scene.view), sampler.clone()]);
+        let edges_bind_group = bind_group("SMAA Edges Bind Group", input_layout, &[view(0, &scene.view), sampler.clone()]);
+        let weights_bind_group = bind_group("SMAA Weights Bind Group", input_layout, &[view(0, &edges.view), sampler.clone()]);
+        let blend_bind_group = bind_group(
+            "SMAA Blend Bind Group",
+            blend_layout,
+            &[view(0, &scene.view), sampler.clone(), view(2, &weights.view)],
+        );
+        let taa_bind_group = |history: &Target| bind_group(
+            "TAA Bind Group",
+            taa_layout,
+            &[
+                view(0, &scene.view),
+                sampler.clone(),
+                view(2, &history.view),
+                view(3, depth_view),
+                wgpu::BindGroupEntry { binding: 4, resource: taa_buffer.as_entire_binding() },
+            ],
+        );
+        let taa_bind_groups =
+            (depth_sample_count == 1).then(|| [taa_bind_group(&history[0]), taa_bind_group(&history[1])]);
+
+        Targets {
+            scene,
+            edges,
+            weights,
+            history,
+            fxaa_bind_group,
+            edges_bind_group,
+            weights_bind_group,
+            blend_bind_group,
+            taa_bind_groups,
+        }
+    }
+
+    //  Needs to be called with the new depth texture whenever it's recreated
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        depth_sample_count: u32,
+    ) {
+        debug_assert_eq!(config.format, self.format);
+        self.targets = Self::create_targets(
+            device,
+            config,
+            (depth_view, depth_sample_count),
+            &self.sampler,
+            [&self.input_layout, &self.blend_layout, &self.taa_layout],
+            &self.taa_buffer,
+        );
+        self.reset_history();
+    }
+
+    //  Where the scene has to be drawn (or resolved) to for the passes to pick it up
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets.scene.view
+    }
+
+    //  For when the history no longer has anything to do with what's on screen, e.g. TAA was just turned on
+    pub fn reset_history(&mut self) {
+        self.history_valid = false;
+    }
+
+    //  The jitter to draw the next frame with, moves on to the next point in the sequence every call
+    pub fn next_jitter(&mut self, width: u32, height: u32) -> Vector2<f32> {
+        self.frame = self.frame.wrapping_add(1);
+        taa_jitter(self.frame, width, height)
+    }
+
+    //  Records the passes for `mode` that take the scene target to `output`. `view_proj` is what the scene was drawn
+    //  with, `unjittered_view_proj` the same without the TAA jitter.
+    pub fn run(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        mode: AntiAliasing,
+        output: &wgpu::TextureView,
+        view_proj: &Matrix4<f32>,
+        unjittered_view_proj: &Matrix4<f32>,
+    ) {
+        let targets = &self.targets;
+        match mode {
+            AntiAliasing::Off => {}
+            AntiAliasing::Fxaa => fullscreen_pass(encoder, "FXAA Pass", &self.fxaa_pipeline, &targets.fxaa_bind_group, &[output]),
+            AntiAliasing::Smaa => {
+                fullscreen_pass(encoder, "SMAA Edges Pass", &self.edges_pipeline, &targets.edges_bind_group, &[&targets.edges.view]);
+                fullscreen_pass(encoder, "SMAA Weights Pass", &self.weights_pipeline, &targets.weights_bind_group, &[&targets.weights.view]);
+                fullscreen_pass(encoder, "SMAA Blend Pass", &self.blend_pipeline, &targets.blend_bind_group, &[output]);
+            }
+            //  TAA reads the depth buffer, which it can't do while that's multisampled
+            AntiAliasing::Taa if targets.taa_bind_groups.is_none() => {
+                fullscreen_pass(encoder, "FXAA Pass", &self.fxaa_pipeline, &targets.fxaa_bind_group, &[output])
+            }
+            AntiAliasing::Taa => {
+                let inv_view_proj = view_proj.invert().unwrap_or_else(Matrix4::identity);
+                queue.write_buffer(&self.taa_buffer, 0, bytemuck::cast_slice(&[TaaUniform {
+                    inv_view_proj: inv_view_proj.into(),
+                    prev_view_proj: self.prev_view_proj.into(),
+                    blend: TAA_BLEND,
+                    history_valid: self.history_valid as u32,
+                    _padding: [0; 2],
+                }]));
+                let next = 1 - self.history_index;
+                fullscreen_pass(
+                    encoder,
+                    "TAA Pass",
+                    &self.taa_pipeline,
+                    &targets.taa_bind_groups.as_ref().unwrap()[self.history_index],
+                    &[output, &targets.history[next].view],
+                );
+                self.history_index = next;
+                self.history_valid = true;
+            }
+        }
+        self.prev_view_proj = *unjittered_view_proj;
+    }
+}
+
+fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    outputs: &[&wgpu::TextureView],
+) {
+    let color_attachments = outputs
+        .iter()
+        .map(|view| Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                //  Every pixel gets written, but the SMAA passes rely on untouched pixels being 0
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: true,
+            },
+        }))
+        .collect::<Vec<_>>();
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &color_attachments,
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
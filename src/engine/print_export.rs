@@ -0,0 +1,372 @@
+//  PRINT EXPORT - Bakes a build into one merged triangle mesh for 3D printing and writes it as binary STL or OBJ+MTL.
+//  Works purely on CPU mesh data so it can run headless without a GPU.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use cgmath::prelude::*;
+use cgmath::{Point3, Vector3};
+
+use crate::engine::gltf_export::{BrickType, PlacedBrick};
+use crate::game::world::{PLATE_HEIGHT, STUD_SIZE};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BakeOptions {
+    //  Vertices closer than this are merged into one
+    pub weld_distance: f32,
+    //  Drops pairs of faces that overlap facing opposite ways, i.e. where two bricks touch. Axis aligned rectangles with
+    //  their corners on `grid` are first split into one quad per grid cell, so a big brick face against several small
+    //  ones loses just the covered cells and edges on the grid get the same vertices on both sides. Any other face is
+    //  only dropped against one with exactly matching corners.
+    pub remove_hidden_faces: bool,
+    //  Size of a grid cell along x, y and z
+    pub grid: [f32; 3],
+}
+
+impl Default for BakeOptions {
+    fn default() -> Self {
+        Self {
+            weld_distance: 1e-4,
+            remove_hidden_faces: true,
+            grid: [STUD_SIZE, PLATE_HEIGHT, STUD_SIZE],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BakedMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+    //  Index into colors for every triangle
+    pub triangle_colors: Vec<usize>,
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl BakedMesh {
+    pub fn triangle_normal(&self, tri: &[u32; 3]) -> Vector3<f32> {
+        let [a, b, c] = tri.map(|i| Vector3::from(self.positions[i as usize]));
+        let n = (b - a).cross(c - a);
+        if n.magnitude2() > 0.0 { n.normalize() } else { Vector3::zero() }
+    }
+}
+
+//  All the triangles of one brick lying in the same plane and facing the same way
+struct Face {
+    normal: [i64; 3],
+    vertices: Vec<u32>,
+    triangles: Vec<usize>,
+}
+
+pub fn bake(brick_types: &[BrickType], bricks: &[PlacedBrick], options: &BakeOptions) -> anyhow::Result<BakedMesh> {
+    let mut baked = BakedMesh::default();
+    //  Vertices by the weld_distance sized cell they're in
+    let mut welded: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut color_lookup: HashMap<[u32; 4], usize> = HashMap::new();
+    let mut faces = Vec::new();
+    let weld_distance = options.weld_distance.max(f32::EPSILON);
+    let cell = weld_distance as f64;
+
+    for brick in bricks {
+        let Some(brick_type) = brick_types.get(brick.brick_type) else {
+            bail!("Brick uses type {} but only {} types were given", brick.brick_type, brick_types.len());
+        };
+        let color = *color_lookup.entry(brick.color.map(f32::to_bits)).or_insert_with(|| {
+            baked.colors.push(brick.color);
+            baked.colors.len() - 1
+        });
+
+        let mut brick_faces: HashMap<([i64; 3], i64), usize> = HashMap::new();
+        for mesh in brick_type.meshes {
+            let mut remap = Vec::with_capacity(mesh.vertices.len());
+            for v in &mesh.vertices {
+                let p = brick.transform.transform_point(Point3::from(v.position));
+                let key = [p.x, p.y, p.z].map(|x| (x as f64 / cell).floor() as i64);
+                let index = match find_weld(&welded, &baked.positions, key, p, weld_distance) {
+                    Some(index) => index,
+                    None => {
+                        baked.positions.push(p.into());
+                        let index = baked.positions.len() as u32 - 1;
+                        welded.entry(key).or_default().push(index);
+                        index
+                    }
+                };
+                remap.push(index);
+            }
+
+            for tri in mesh.indices.chunks_exact(3) {
+                let tri = [remap[tri[0] as usize], remap[tri[1] as usize], remap[tri[2] as usize]];
+                //  Welding can collapse tiny triangles, they'd only confuse slicers
+                if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] {
+                    continue;
+                }
+                baked.triangles.push(tri);
+                baked.triangle_colors.push(color);
+
+                if options.remove_hidden_faces {
+                    let n = baked.triangle_normal(&tri);
+                    let normal = [n.x, n.y, n.z].map(|x| (x as f64 * 1000.0).round() as i64);
+                    let plane = (n.dot(Vector3::from(baked.positions[tri[0] as usize])) as f64 / cell).round() as i64;
+                    let face = *brick_faces.entry((normal, plane)).or_insert_with(|| {
+                        faces.push(Face { normal, vertices: Vec::new(), triangles: Vec::new() });
+                        faces.len() - 1
+                    });
+                    faces[face].vertices.extend_from_slice(&tri);
+                    faces[face].triangles.push(baked.triangles.len() - 1);
+                }
+            }
+        }
+    }
+
+    if options.remove_hidden_faces {
+        let replaced = split_on_grid(&mut baked, &mut faces, options.grid, weld_distance);
+        remove_hidden_faces(&mut baked, faces, &replaced);
+    }
+
+    Ok(baked)
+}
+
+//  An earlier vertex within `weld_distance` of `p`. Cells are that size, so it can only be in `key`'s cell or one next
+//  to it.
+fn find_weld(
+    welded: &HashMap<[i64; 3], Vec<u32>>,
+    positions: &[[f32; 3]],
+    key: [i64; 3],
+    p: Point3<f32>,
+    weld_distance: f32,
+) -> Option<u32> {
+    let mut nearest = None;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let Some(cell) = welded.get(&[key[0] + dx, key[1] + dy, key[2] + dz]) else {
+                    continue;
+                };
+                for &i in cell {
+                    let distance = Point3::from(positions[i as usize]).distance(p);
+                    if distance <= weld_distance && nearest.is_none_or(|(_, d)| distance < d) {
+                        nearest = Some((i, distance));
+                    }
+                }
+            }
+        }
+    }
+    nearest.map(|(i, _)| i)
+}
+
+//  Replaces every face that's an axis aligned rectangle on the grid with one quad per cell it covers. Grid points are
+//  shared between faces, so neighbouring faces end up with matching edges and a contact can be matched cell by cell.
+//  Returns the triangles that were replaced.
+fn split_on_grid(baked: &mut BakedMesh, faces: &mut Vec<Face>, grid: [f32; 3], tolerance: f32) -> Vec<usize> {
+    let cell_of = |p: f32, axis: usize| {
+        let k = (p / grid[axis]).round();
+        ((p - k * grid[axis]).abs() <= tolerance).then_some(k as i64)
+    };
+    let mut grid_points: HashMap<[i64; 3], u32> = HashMap::new();
+    for (i, p) in baked.positions.iter().enumerate() {
+        if let (Some(x), Some(y), Some(z)) = (cell_of(p[0], 0), cell_of(p[1], 1), cell_of(p[2], 2)) {
+            grid_points.entry([x, y, z]).or_insert(i as u32);
+        }
+    }
+
+    let mut split = Vec::new();
+    let mut replaced = Vec::new();
+    faces.retain(|face| {
+        //  Axis aligned normals are rounded to exactly one non-zero component
+        let Some(axis) = (0..3).find(|&a| face.normal[a] != 0 && face.normal[(a + 1) % 3] == 0 && face.normal[(a + 2) % 3] == 0) else {
+            return true;
+        };
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut corners = face.vertices.clone();
+        corners.sort_unstable();
+        corners.dedup();
+        let Some(cells) = corners.iter()
+            .map(|&i| {
+                let p = baked.positions[i as usize];
+                Some([cell_of(p[0], 0)?, cell_of(p[1], 1)?, cell_of(p[2], 2)?])
+            })
+            .collect::<Option<Vec<_>>>() else {
+            return true;
+        };
+        let (u0, u1) = (cells.iter().map(|c| c[u]).min().unwrap(), cells.iter().map(|c| c[u]).max().unwrap());
+        let (v0, v1) = (cells.iter().map(|c| c[v]).min().unwrap(), cells.iter().map(|c| c[v]).max().unwrap());
+        //  Four distinct corners of the bounding rectangle, filled by its triangles
+        let area = face.triangles.iter().map(|&t| {
+            let [a, b, c] = baked.triangles[t].map(|i| Vector3::from(baked.positions[i as usize]));
+            (b - a).cross(c - a).magnitude() as f64 * 0.5
+        }).sum::<f64>();
+        let rect_area = ((u1 - u0) as f64 * grid[u] as f64) * ((v1 - v0) as f64 * grid[v] as f64);
+        let is_rectangle = cells.len() == 4
+            && cells.iter().all(|c| (c[u] == u0 || c[u] == u1) && (c[v] == v0 || c[v] == v1))
+            && (area - rect_area).abs() <= rect_area * 1e-3;
+        if !is_rectangle || (u1 - u0 == 1 && v1 - v0 == 1) {
+            return true;
+        }
+
+        let color = baked.triangle_colors[face.triangles[0]];
+        let plane = cells[0][axis];
+        let mut point = |cu: i64, cv: i64| {
+            let mut cell = [0; 3];
+            (cell[axis], cell[u], cell[v]) = (plane, cu, cv);
+            *grid_points.entry(cell).or_insert_with(|| {
+                baked.positions.push([0, 1, 2].map(|a| cell[a] as f32 * grid[a]));
+                baked.positions.len() as u32 - 1
+            })
+        };
+        //  u then v winds counter-clockwise seen from +axis
+        let flip = face.normal[axis] < 0;
+        for cu in u0..u1 {
+            for cv in v0..v1 {
+                let mut quad = [point(cu, cv), point(cu + 1, cv), point(cu + 1, cv + 1), point(cu, cv + 1)];
+                if flip {
+                    quad.reverse();
+                }
+                split.push((face.normal, quad, color));
+            }
+        }
+        replaced.extend_from_slice(&face.triangles);
+        false
+    });
+
+    for (normal, [a, b, c, d], color) in split {
+        let first = baked.triangles.len();
+        baked.triangles.extend([[a, b, c], [a, c, d]]);
+        baked.triangle_colors.extend([color, color]);
+        faces.push(Face { normal, vertices: vec![a, b, c, d], triangles: vec![first, first + 1] });
+    }
+    replaced
+}
+
+//  Two touching bricks leave a face on each side of the contact covering the same vertices but facing opposite ways.
+//  Neither can be seen, and leaving them in makes the mesh non-manifold. Whole faces are compared rather than
+//  triangles since neighbours don't necessarily split a quad along the same diagonal.
+fn remove_hidden_faces(baked: &mut BakedMesh, mut faces: Vec<Face>, replaced: &[usize]) {
+    let mut open: HashMap<(Vec<u32>, [i64; 3]), Vec<usize>> = HashMap::new();
+    let mut hidden = vec![false; baked.triangles.len()];
+    for &t in replaced {
+        hidden[t] = true;
+    }
+    for i in 0..faces.len() {
+        faces[i].vertices.sort_unstable();
+        faces[i].vertices.dedup();
+        let opposite = (faces[i].vertices.clone(), faces[i].normal.map(|x| -x));
+        if let Some(other) = open.get_mut(&opposite).and_then(|matches| matches.pop()) {
+            for &t in faces[i].triangles.iter().chain(&faces[other].triangles) {
+                hidden[t] = true;
+            }
+        } else {
+            open.entry((faces[i].vertices.clone(), faces[i].normal)).or_default().push(i);
+        }
+    }
+
+    let (triangles, triangle_colors) = baked.triangles.iter()
+        .zip(&baked.triangle_colors)
+        .zip(hidden)
+        .filter(|(_, hidden)| !hidden)
+        .map(|((&tri, &color), _)| (tri, color))
+        .unzip();
+    baked.triangles = triangles;
+    baked.triangle_colors = triangle_colors;
+
+    //  Vertices only used by removed faces would be left floating
+    let mut used = vec![u32::MAX; baked.positions.len()];
+    let mut positions = Vec::new();
+    for tri in baked.triangles.iter_mut() {
+        for i in tri.iter_mut() {
+            if used[*i as usize] == u32::MAX {
+                used[*i as usize] = positions.len() as u32;
+                positions.push(baked.positions[*i as usize]);
+            }
+            *i = used[*i as usize];
+        }
+    }
+    baked.positions = positions;
+}
+
+pub fn write_stl<W: Write>(baked: &BakedMesh, mut out: W) -> anyhow::Result<()> {
+    let mut header = [0u8; 80];
+    let name = b"brickheaven";
+    header[..name.len()].copy_from_slice(name);
+    out.write_all(&header)?;
+    out.write_all(&(baked.triangles.len() as u32).to_le_bytes())?;
+
+    for tri in &baked.triangles {
+        let normal: [f32; 3] = baked.triangle_normal(tri).into();
+        for x in normal {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        for &i in tri {
+            for x in baked.positions[i as usize] {
+                out.write_all(&x.to_le_bytes())?;
+            }
+        }
+        //  Attribute byte count, unused
+        out.write_all(&0u16.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn material_name(color: usize) -> String {
+    format!("color_{}", color)
+}
+
+//  `mtl_name` is written as the mtllib so should be relative to the OBJ
+pub fn write_obj<W: Write>(baked: &BakedMesh, mtl_name: &str, mut out: W) -> anyhow::Result<()> {
+    writeln!(out, "# brickheaven")?;
+    writeln!(out, "mtllib {}", mtl_name)?;
+    for p in &baked.positions {
+        writeln!(out, "v {} {} {}", p[0], p[1], p[2])?;
+    }
+
+    //  Grouping by colour keeps the usemtl lines down to one per colour
+    let mut order = (0..baked.triangles.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| baked.triangle_colors[i]);
+    let mut current = None;
+    for i in order {
+        let color = baked.triangle_colors[i];
+        if current != Some(color) {
+            writeln!(out, "usemtl {}", material_name(color))?;
+            current = Some(color);
+        }
+        //  OBJ indices start at 1
+        let [a, b, c] = baked.triangles[i].map(|x| x + 1);
+        writeln!(out, "f {} {} {}", a, b, c)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_mtl<W: Write>(baked: &BakedMesh, mut out: W) -> anyhow::Result<()> {
+    for (i, [r, g, b, a]) in baked.colors.iter().enumerate() {
+        writeln!(out, "newmtl {}", material_name(i))?;
+        writeln!(out, "Kd {} {} {}", r, g, b)?;
+        writeln!(out, "d {}", a)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+//  .stl gets binary STL, .obj gets the OBJ plus a .mtl with the colours next to it
+pub fn export_print<P: AsRef<Path>>(path: P, baked: &BakedMesh) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let create = |p: &Path| {
+        std::fs::File::create(p)
+            .map(std::io::BufWriter::new)
+            .with_context(|| format!("Failed to create {:?}", p))
+    };
+
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("stl") => write_stl(baked, create(path)?)?,
+        Some("obj") => {
+            let mtl_path = path.with_extension("mtl");
+            let mtl_name = mtl_path.file_name().and_then(|n| n.to_str()).unwrap_or("build.mtl").to_string();
+            write_mtl(baked, create(&mtl_path)?)?;
+            write_obj(baked, &mtl_name, create(path)?)?;
+        }
+        _ => bail!("Don't know how to export {:?} for printing, use .stl or .obj", path),
+    }
+
+    Ok(())
+}
@@ -1,25 +1,45 @@
-use std::io::{BufReader, Cursor};
-
-use wgpu::util::DeviceExt;
-
 use crate::{texture, model};
+use crate::engine::{obj_loading, vfs};
+use crate::engine::assets::{Assets, Handle, LoadState};
+use crate::game::uniform::MaterialUniform;
 
+//  All loads go through the global VFS so mounted mod packs can override base assets
 pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
-    let txt = std::fs::read_to_string(path)?;
-
-    Ok(txt)
+    vfs::global().read().unwrap().read_to_string(file_name)
 }
 
 pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
-    let data = std::fs::read(path)?;
+    vfs::global().read().unwrap().read(file_name)
+}
 
-    Ok(data)
+//  Mounts every .zip/.bhpack (and sub-directory) in `dir` above the base resources.
+//  Sorted by name so "10-hd-bricks.zip" overrides "00-base-fixes.zip", like load order in most games.
+pub fn mount_mods<P: AsRef<std::path::Path>>(dir: P) -> anyhow::Result<Vec<String>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut vfs = vfs::global().write().unwrap();
+    let mut mounted = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        match vfs.mount_path(path, vfs::BASE_PRIORITY + 1 + i as i32) {
+            Ok(()) => mounted.push(path.to_string_lossy().into_owned()),
+            Err(e) => log::warn!("Skipping mod {:?}: {:#}", path, e),
+        }
+    }
+
+    Ok(mounted)
+}
+
+//  Every brick model across all mounted layers, e.g. for the brick selector
+pub fn list_bricks() -> Vec<String> {
+    vfs::global().read().unwrap().list_bricks()
 }
 
 pub async fn load_texture(
@@ -32,145 +52,149 @@ pub async fn load_texture(
     texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
 }
 
+//  Everything needed to build a Model, decoded on the CPU so it can be produced on a worker thread
+#[derive(Debug)]
+pub struct ModelData {
+    pub name: String,
+    pub meshes: Vec<model::MeshData>,
+    pub materials: Vec<MaterialData>,
+}
+
+//  Textures are referenced by path so the asset store can share them between materials
+#[derive(Debug)]
+pub struct MaterialData {
+    pub name: String,
+    //  Canonical key for the material store, "<mtl path>#<material name>"
+    pub key: String,
+    //  None when the MTL doesn't have the map, the builtin default texture is used instead
+    pub diffuse_path: Option<String>,
+    pub normal_path: Option<String>,
+    pub uniform: MaterialUniform,
+}
+
+impl ModelData {
+    //  `materials` lines up with self.materials, meshes without a material use `fallback`
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        materials: &[Handle<model::Material>],
+        fallback: &Handle<model::Material>,
+        keep_geometry: bool,
+    ) -> model::Model {
+        let meshes = self.meshes
+            .iter()
+            .map(|m| m.upload(device, materials.get(m.material).unwrap_or(fallback).clone(), keep_geometry))
+            .collect();
+
+        model::Model::new(meshes)
+    }
+
+    //  Rough number of bytes this will take up on the GPU, used to budget uploads per frame
+    pub fn upload_size(&self) -> usize {
+        self.meshes.iter().map(|m| m.upload_size()).sum()
+    }
+}
+
+//  Stand-ins for maps a material doesn't have, white leaves the diffuse colour alone and the normal points straight out
+pub const DEFAULT_DIFFUSE_TEXTURE: &str = "builtin/white";
+pub const DEFAULT_NORMAL_TEXTURE: &str = "builtin/flat_normal";
+
+pub fn builtin_texture(
+    path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    textures: &mut Assets<texture::Texture>,
+) -> Handle<texture::Texture> {
+    let (handle, is_new) = textures.reserve(path);
+    if is_new {
+        let (rgba, is_normal_map) = match path {
+            DEFAULT_NORMAL_TEXTURE => ([128, 128, 255, 255], true),
+            _ => ([255, 255, 255, 255], false),
+        };
+        //  Only fails if the image can't be read, and we made it ourselves
+        textures.insert(&handle, texture::Texture::solid_color(device, queue, rgba, path, is_normal_map).unwrap());
+    }
+    handle
+}
+
+//  Loads a texture through the store, so each file is only decoded and uploaded once
+pub async fn load_texture_cached(
+    file_name: &str,
+    is_normal_map: bool,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    textures: &mut Assets<texture::Texture>,
+) -> anyhow::Result<Handle<texture::Texture>> {
+    let (handle, is_new) = textures.reserve(file_name);
+    if is_new {
+        match load_texture(file_name, is_normal_map, device, queue).await {
+            Ok(texture) => textures.insert(&handle, texture),
+            Err(e) => {
+                textures.mark_failed(&handle);
+                return Err(e);
+            }
+        }
+    } else if textures.state(&handle) == LoadState::Failed {
+        anyhow::bail!("{:?} failed to load previously", file_name);
+    }
+    Ok(handle)
+}
+
+//  Loads a model on the spot, sharing textures and materials with anything already in the stores
 pub async fn load_model_obj(
     file_name: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
+    textures: &mut Assets<texture::Texture>,
+    materials: &mut Assets<model::Material>,
+    fallback: &Handle<model::Material>,
 ) -> anyhow::Result<model::Model> {
-    let obj_text = load_string(file_name).await?;
-    let obj_cursor = Cursor::new(obj_text);
-    let mut obj_reader = BufReader::new(obj_cursor);
-
-    let (models, obj_materials) = tobj::load_obj_buf_async(
-        &mut obj_reader,
-        &tobj::LoadOptions {
-            triangulate: true,
-            single_index: true,
-            ..Default::default()
-        },
-        |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
-        },
-    )
-    .await?;
-
-    let mut materials = Vec::new();
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture, false, device, queue).await?;
-        let normal_texture = load_texture(&m.normal_texture, true, device, queue).await?;
-
-        materials.push(model::Material::new(
-            device,
-            &m.name,
-            diffuse_texture,
-            normal_texture,
-            layout,
-        ))
+    let data = load_model_obj_data(file_name).await?;
+
+    let mut material_handles = Vec::new();
+    for m in &data.materials {
+        let handle = match materials.get_handle(&m.key) {
+            Some(handle) => handle,
+            None => {
+                let diffuse = match &m.diffuse_path {
+                    Some(path) => load_texture_cached(path, false, device, queue, textures).await?,
+                    None => builtin_texture(DEFAULT_DIFFUSE_TEXTURE, device, queue, textures),
+                };
+                let normal = match &m.normal_path {
+                    Some(path) => load_texture_cached(path, true, device, queue, textures).await?,
+                    None => builtin_texture(DEFAULT_NORMAL_TEXTURE, device, queue, textures),
+                };
+                let material = model::Material::new(
+                    device,
+                    &m.name,
+                    textures.get(&diffuse).unwrap(),
+                    textures.get(&normal).unwrap(),
+                    m.uniform,
+                    layout,
+                ).with_textures(vec![diffuse, normal]);
+                materials.add_with_path(&m.key, material)
+            }
+        };
+        material_handles.push(handle);
     }
 
-    let meshes = models
-        .into_iter()
-        .map(|m| {
-            let mut vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| model::ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    //  Calculate tangent & bitangent later
-                    tangent: [0.0; 3],
-                    bitangent: [0.0; 3],
-                })
-                .collect::<Vec<_>>();
-
-            let indices = &m.mesh.indices;
-            let mut triangles_included = vec![0; vertices.len()];
-
-            //  Calculate tangents and bitangents using the triangles
-            for c in indices.chunks(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
-
-                let pos0: cgmath::Vector3<_> = v0.position.into();
-                let pos1: cgmath::Vector3<_> = v1.position.into();
-                let pos2: cgmath::Vector3<_> = v2.position.into();
-
-                let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
-                let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
-                let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
-
-                //  Calculate tri edges
-                let delta_pos1 = pos1 - pos0;
-                let delta_pos2 = pos2 - pos0;
-
-                //  Get direction to calculate tangent & bitangent
-                let delta_uv1 = uv1 - uv0;
-                let delta_uv2 = uv2 - uv0;
-
-                //  Calculate tangent and bitangent
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-                //  Flip the bitangent to enable right-handed normal maps with wgpu tex coord system
-                let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-                // Use the same tangent/bitangent for each vertex in the tri
-                vertices[c[0] as usize].tangent =
-                    (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
-                vertices[c[1] as usize].tangent =
-                    (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
-                vertices[c[2] as usize].tangent =
-                    (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into();
-                vertices[c[0] as usize].bitangent =
-                    (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].bitangent)).into();
-                vertices[c[1] as usize].bitangent =
-                    (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].bitangent)).into();
-                vertices[c[2] as usize].bitangent =
-                    (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].bitangent)).into();
-
-                    //  Used to average the tangents/bitangents
-                    triangles_included[c[0] as usize] += 1;
-                    triangles_included[c[1] as usize] += 1;
-                    triangles_included[c[2] as usize] += 1;
-            }
+    Ok(data.upload(device, &material_handles, fallback, true))
+}
 
-            //  Average the tangents/bitangents
-            for (i, n) in triangles_included.into_iter().enumerate() {
-                let denom = 1.0 / n as f32;
-                let mut v = &mut vertices[i];
-                v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
-                v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
-            }
-            
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            model::Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
-            }
-        })
-        .collect::<Vec<_>>();
-    
-    Ok(model::Model { meshes, materials })
-}
\ No newline at end of file
+pub async fn load_image(file_name: &str) -> anyhow::Result<image::DynamicImage> {
+    let data = load_binary(file_name).await?;
+    Ok(image::load_from_memory(&data)?)
+}
+
+pub async fn load_model_obj_data(file_name: &str) -> anyhow::Result<ModelData> {
+    let obj_text = load_string(file_name).await?;
+    let data = obj_loading::parse_obj(
+        file_name,
+        &obj_text,
+        &obj_loading::ImportOptions::default(),
+        |p| vfs::global().read().unwrap().read_to_string(p),
+    )?;
+
+    Ok(data)
+}
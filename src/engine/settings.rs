@@ -0,0 +1,86 @@
+//  SETTINGS - Graphics options that can be changed while the game is running. Whoever applies them checks them against
+//  what the adapter can actually do and rebuilds whatever depends on them.
+
+//  Screen space anti-aliasing, run over the finished frame. Works on its own or on top of MSAA, except TAA which needs
+//  a single sampled depth buffer and turns MSAA off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntiAliasing {
+    #[default]
+    Off,
+    Fxaa,
+    Smaa,
+    Taa,
+}
+
+impl AntiAliasing {
+    pub const ALL: [AntiAliasing; 4] = [AntiAliasing::Off, AntiAliasing::Fxaa, AntiAliasing::Smaa, AntiAliasing::Taa];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+//  How opaque bricks are lit. Picked at startup, the deferred path's G-buffer isn't worth keeping around otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderPath {
+    //  Every brick is lit as it's drawn, by the one light
+    #[default]
+    Forward,
+    //  Opaque bricks are drawn into a G-buffer and lit by every light in one full screen pass, transparent ones are
+    //  still drawn forward on top. Single sampled only, so it turns MSAA off.
+    Deferred,
+}
+
+//  Screen space ambient occlusion, darkens the ambient light in creases and where bricks meet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    //  How far around each point to look for occluders, in world units
+    pub radius: f32,
+    //  0 leaves the ambient light alone, 1 takes all of it away from fully occluded points
+    pub intensity: f32,
+    //  Samples per pixel, clamped to ssao::MAX_SAMPLES
+    pub sample_count: u32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            //  A bit more than a plate is tall, so each brick darkens the one it sits on without smudging whole walls
+            radius: 0.5,
+            intensity: 1.0,
+            sample_count: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphicsSettings {
+    //  Samples per pixel, 1 turns MSAA off. Only 1 and 4 are offered (see msaa::SAMPLE_COUNTS), anything else drops to
+    //  the closest one below.
+    pub msaa_samples: u32,
+    pub anti_aliasing: AntiAliasing,
+    pub ssao: SsaoSettings,
+    pub render_path: RenderPath,
+}
+
+impl GraphicsSettings {
+    //  TAA and the deferred path both read the depth buffer after the scene is drawn, which they can't while it's
+    //  multisampled
+    pub fn needs_single_sample(&self) -> bool {
+        self.anti_aliasing == AntiAliasing::Taa || self.render_path == RenderPath::Deferred
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            //  The one count WebGPU guarantees besides 1
+            msaa_samples: 4,
+            anti_aliasing: AntiAliasing::Off,
+            ssao: SsaoSettings::default(),
+            render_path: RenderPath::Forward,
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//  SSAO - Screen space ambient occlusion. A prepass draws the scene's depth and normals, then a full screen pass works
+//  out how much ambient light reaches each pixel and blurs it. The scene shaders read the result from bind group 3.
+
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3};
+
+use crate::engine::post_process::{halton, Target};
+use crate::engine::settings::SsaoSettings;
+use crate::engine::texture;
+
+pub const MAX_SAMPLES: u32 = 64;
+pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    view_pos: [f32; 4],
+    radius: f32,
+    intensity: f32,
+    sample_count: u32,
+    _padding: u32,
+    kernel: [[f32; 4]; MAX_SAMPLES as usize],
+}
+
+//  `count` points in the unit hemisphere around +z, spread evenly by angle and bunched towards the middle so the
+//  occluders closest to each pixel count the most
+pub fn hemisphere_kernel(count: u32) -> Vec<[f32; 4]> {
+    (0..count)
+        .map(|i| {
+            let angle = halton(i + 1, 2) * std::f32::consts::TAU;
+            //  Cosine weighted, so there are fewer samples grazing the surface
+            let r2 = halton(i + 1, 3);
+            let (r, z) = (r2.sqrt(), (1.0 - r2).sqrt());
+            let t = i as f32 / count as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            [r * angle.cos() * scale, r * angle.sin() * scale, z * scale, 0.0]
+        })
+        .collect()
+}
+
+//  Everything that depends on the size of the screen
+struct Targets {
+    depth: texture::DepthTexture,
+    normal: Target,
+    //  The AO ends up back in here after the blur passes
+    ao: Target,
+    blurred: Target,
+    ssao_bind_group: wgpu::BindGroup,
+    blur_horizontal_bind_group: wgpu::BindGroup,
+    blur_vertical_bind_group: wgpu::BindGroup,
+    ao_bind_group: wgpu::BindGroup,
+}
+
+pub struct Ssao {
+    settings: SsaoSettings,
+    kernel: Vec<[f32; 4]>,
+    ssao_layout: wgpu::BindGroupLayout,
+    blur_layout: wgpu::BindGroupLayout,
+    ao_layout: wgpu::BindGroupLayout,
+    ssao_pipeline: wgpu::RenderPipeline,
+    blur_horizontal_pipeline: wgpu::RenderPipeline,
+    blur_vertical_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    targets: Targets,
+    //  Bound in place of the AO while SSAO is off
+    _white: texture::Texture,
+    white_bind_group: wgpu::BindGroup,
+}
+
+impl Ssao {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        settings: SsaoSettings,
+    ) -> anyhow::Result<Self> {
+        let texture = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            count: None,
+        };
+        let uniform = wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        //  Depth can't be filtered, and nothing here is sampled anyway
+        let ssao_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSAO Bind Group Layout"),
+            entries: &[texture(0, false), texture(1, false), uniform],
+        });
+        let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSAO Blur Bind Group Layout"),
+            entries: &[texture(0, false), texture(1, false), uniform, texture(3, false)],
+        });
+        let ao_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Bind Group Layout"),
+            entries: &[texture(0, false)],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSAO Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/ssao.wgsl").into()),
+        });
+        let pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: AO_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let ssao_pipeline = pipeline("SSAO Pipeline", &ssao_layout, "fs_ssao");
+        let blur_horizontal_pipeline = pipeline("SSAO Blur Pipeline", &blur_layout, "fs_blur_horizontal");
+        let blur_vertical_pipeline = pipeline("SSAO Blur Pipeline", &blur_layout, "fs_blur_vertical");
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSAO Buffer"),
+            size: std::mem::size_of::<SsaoUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let white = texture::Texture::solid_color(device, queue, [255; 4], "No Ambient Occlusion", false)?;
+        let white_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("No Ambient Occlusion Bind Group"),
+            layout: &ao_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&white.view) }],
+        });
+
+        let targets = Self::create_targets(device, config, [&ssao_layout, &blur_layout, &ao_layout], &buffer);
+        let mut ssao = Self {
+            settings,
+            kernel: Vec::new(),
+            ssao_layout,
+            blur_layout,
+            ao_layout,
+            ssao_pipeline,
+            blur_horizontal_pipeline,
+            blur_vertical_pipeline,
+            buffer,
+            targets,
+            _white: white,
+            white_bind_group,
+        };
+        ssao.set_settings(settings);
+        Ok(ssao)
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        [ssao_layout, blur_layout, ao_layout]: [&wgpu::BindGroupLayout; 3],
+        buffer: &wgpu::Buffer,
+    ) -> Targets {
+        let (width, height) = (config.width, config.height);
+        let depth = texture::Texture::create_depth_texture(device, config, 1, "SSAO Depth Texture");
+        let normal = Target::new(device, "SSAO Normal Target", NORMAL_FORMAT, width, height);
+        let ao = Target::new(device, "SSAO Target", AO_FORMAT, width, height);
+        let blurred = Target::new(device, "SSAO Blur Target", AO_FORMAT, width, height);
+
+        fn view(binding: u32, view: &wgpu::TextureView) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry { binding, resource: wgpu::BindingResource::TextureView(view) }
+        }
+        let uniform = wgpu::BindGroupEntry { binding: 2, resource: buffer.as_entire_binding() };
+        let bind_group = |label, layout, entries: &[wgpu::BindGroupEntry]| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some(label), layout, entries })
+        };
+        let ssao_bind_group = bind_group(
+            "SSAO Bind Group",
+            ssao_layout,
+            &[view(0, &depth.depth_view), view(1, &normal.view), uniform.clone()],
+        );
+        let blur_horizontal_bind_group = bind_group(
+            "SSAO Blur Bind Group",
+            blur_layout,
+            &[view(0, &depth.depth_view), view(1, &normal.view), uniform.clone(), view(3, &ao.view)],
+        );
+        let blur_vertical_bind_group = bind_group(
+            "SSAO Blur Bind Group",
+            blur_layout,
+            &[view(0, &depth.depth_view), view(1, &normal.view), uniform, view(3, &blurred.view)],
+        );
+        let ao_bind_group = bind_group("Ambient Occlusion Bind Group", ao_layout, &[view(0, &ao.view)]);
+
+        Targets {
+            depth,
+            normal,
+            ao,
+            blurred,
+            ssao_bind_group,
+            blur_horizontal_bind_group,
+            blur_vertical_bind_group,
+            ao_bind_group,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.targets = Self::create_targets(
+            device,
+            config,
+            [&self.ssao_layout, &self.blur_layout, &self.ao_layout],
+            &self.buffer,
+        );
+    }
+
+    pub fn set_settings(&mut self, settings: SsaoSettings) {
+        self.settings = settings;
+        self.kernel = hemisphere_kernel(settings.sample_count.clamp(1, MAX_SAMPLES));
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    //  Group 3 of the scene pipelines
+    pub fn ao_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.ao_layout
+    }
+
+    pub fn ao_bind_group(&self) -> &wgpu::BindGroup {
+        match self.settings.enabled {
+            true => &self.targets.ao_bind_group,
+            false => &self.white_bind_group,
+        }
+    }
+
+    //  The pass the normal prepass pipelines draw the scene in
+    pub fn begin_prepass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO Normal Prepass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.targets.normal.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.5, g: 0.5, b: 1.0, a: 0.0 }),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.targets.depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    //  Works out the AO from what the prepass drew, which has to be recorded first
+    pub fn run(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        view_proj: &Matrix4<f32>,
+        view_position: Point3<f32>,
+    ) {
+        let mut kernel = [[0.0; 4]; MAX_SAMPLES as usize];
+        kernel[..self.kernel.len()].copy_from_slice(&self.kernel);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SsaoUniform {
+            view_proj: (*view_proj).into(),
+            inv_view_proj: view_proj.invert().unwrap_or_else(Matrix4::identity).into(),
+            view_pos: view_position.to_homogeneous().into(),
+            radius: self.settings.radius,
+            intensity: self.settings.intensity,
+            sample_count: self.kernel.len() as u32,
+            _padding: 0,
+            kernel,
+        }]));
+
+        let targets = &self.targets;
+        let passes = [
+            ("SSAO Pass", &self.ssao_pipeline, &targets.ssao_bind_group, &targets.ao.view),
+            ("SSAO Blur Pass", &self.blur_horizontal_pipeline, &targets.blur_horizontal_bind_group, &targets.blurred.view),
+            ("SSAO Blur Pass", &self.blur_vertical_pipeline, &targets.blur_vertical_bind_group, &targets.ao.view),
+        ];
+        for (label, pipeline, bind_group, output) in passes {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,600 @@
+//  TEXT - Strings laid out with a Font and drawn as batches of textured quads. Glyphs are rasterized once per font
+//  and size into a shared atlas texture. Screen text is drawn over the finished frame in pixels from the top left
+//  (HUD, FPS counter, chat), world text in the scene pass as billboards facing the camera (name tags, signs).
+//  Like DebugDraw, text is queued every frame and dropped once it's drawn.
+
+use std::collections::HashMap;
+
+use cgmath::{Matrix4, Point3};
+
+use crate::engine::font::{Font, GlyphBitmap, GlyphId};
+use crate::engine::model::Vertex;
+use crate::engine::texture;
+
+pub type FontId = usize;
+
+pub const ATLAS_SIZE: u32 = 1024;
+//  Empty pixels around every glyph so filtering doesn't bleed neighbours in
+const ATLAS_PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub font: FontId,
+    //  Pixels per em, which world text is rasterized at too
+    pub size: f32,
+    //  Linear RGBA
+    pub color: [f32; 4],
+    //  Lines are wrapped between words to fit, in pixels
+    pub max_width: Option<f32>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self { font: 0, size: 16.0, color: [1.0; 4], max_width: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub glyph: GlyphId,
+    //  The pen position on the baseline, from the top left of the text, y down
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub width: f32,
+    pub height: f32,
+    pub lines: usize,
+}
+
+//  Lays `text` out at `size` pixels per em. Lines break at '\n', and between words when `max_width` would be passed.
+//  A word too long for a line of its own is broken wherever it runs out of room.
+pub fn layout(font: &Font, text: &str, size: f32, max_width: Option<f32>) -> TextLayout {
+    let metrics = font.line_metrics(size);
+    let max_width = max_width.unwrap_or(f32::INFINITY);
+    let space = font.advance(font.glyph_index(' '), size);
+    let mut layout = TextLayout::default();
+    let mut line = 0;
+    for paragraph in text.split('\n') {
+        let mut x = 0.0;
+        for (i, word) in paragraph.split(' ').enumerate() {
+            let glyphs = word.chars().filter(|c| !c.is_control()).map(|c| font.glyph_index(c)).collect::<Vec<_>>();
+            if i > 0 {
+                if x > 0.0 && x + space + word_width(font, &glyphs, size) > max_width {
+                    line += 1;
+                    x = 0.0;
+                } else {
+                    x += space;
+                }
+            }
+            let mut previous = None;
+            for glyph in glyphs {
+                if let Some(previous) = previous {
+                    x += font.kerning(previous, glyph, size);
+                }
+                let advance = font.advance(glyph, size);
+                if x > 0.0 && x + advance > max_width {
+                    line += 1;
+                    x = 0.0;
+                }
+                layout.glyphs.push(PositionedGlyph { glyph, x, y: metrics.ascent + line as f32 * metrics.line_height() });
+                x += advance;
+                layout.width = layout.width.max(x);
+                previous = Some(glyph);
+            }
+        }
+        line += 1;
+    }
+    layout.lines = line;
+    layout.height = line as f32 * metrics.line_height();
+    layout
+}
+
+fn word_width(font: &Font, glyphs: &[GlyphId], size: f32) -> f32 {
+    let kerning = glyphs.windows(2).map(|pair| font.kerning(pair[0], pair[1], size)).sum::<f32>();
+    glyphs.iter().map(|&glyph| font.advance(glyph, size)).sum::<f32>() + kerning
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub font: FontId,
+    pub glyph: GlyphId,
+    //  Pixels per em
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    //  Texels in the atlas
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    //  As in GlyphBitmap
+    pub left: i32,
+    pub top: i32,
+}
+
+//  Single channel coverage for every glyph drawn so far, packed in rows (shelves) as tall as their tallest glyph.
+//  Nothing is ever taken out, when it fills up it's cleared and whatever's still being drawn goes back in.
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    glyphs: HashMap<GlyphKey, AtlasGlyph>,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+            glyphs: HashMap::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn get(&self, key: &GlyphKey) -> Option<AtlasGlyph> {
+        self.glyphs.get(key).copied()
+    }
+
+    //  None if there's no room left
+    pub fn insert(&mut self, key: GlyphKey, bitmap: &GlyphBitmap) -> Option<AtlasGlyph> {
+        let (width, height) = (bitmap.width + ATLAS_PADDING, bitmap.height + ATLAS_PADDING);
+        if self.shelf_x + width > self.width {
+            self.shelf_y += self.shelf_height;
+            self.shelf_x = 0;
+            self.shelf_height = 0;
+        }
+        if width > self.width || self.shelf_y + height > self.height {
+            return None;
+        }
+        let glyph = AtlasGlyph {
+            x: self.shelf_x,
+            y: self.shelf_y,
+            width: bitmap.width,
+            height: bitmap.height,
+            left: bitmap.left,
+            top: bitmap.top,
+        };
+        for (row, coverage) in bitmap.coverage.chunks_exact(bitmap.width.max(1) as usize).enumerate() {
+            let start = ((glyph.y + row as u32) * self.width + glyph.x) as usize;
+            self.pixels[start..start + coverage.len()].copy_from_slice(coverage);
+        }
+        self.shelf_x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        self.glyphs.insert(key, glyph);
+        self.dirty = true;
+        Some(glyph)
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.shelf_x = 0;
+        self.shelf_y = 0;
+        self.shelf_height = 0;
+        self.dirty = true;
+    }
+
+    //  Whether the texture needs uploading again since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    //  In pixels for screen text, the anchor in world space for world text
+    pub position: [f32; 3],
+    //  From the anchor along the camera's right and down for world text, in world units
+    pub offset: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Vertex for TextVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x2, 3 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextUniform {
+    viewport: [f32; 2],
+    _padding: [u32; 2],
+    //  The camera's axes in world space, for turning billboards towards it
+    right: [f32; 4],
+    up: [f32; 4],
+}
+
+struct ScreenText {
+    text: String,
+    position: [f32; 2],
+    style: TextStyle,
+}
+
+struct WorldText {
+    text: String,
+    anchor: Point3<f32>,
+    em_height: f32,
+    style: TextStyle,
+}
+
+pub struct TextRenderer {
+    fonts: Vec<Font>,
+    atlas: GlyphAtlas,
+    atlas_texture: wgpu::Texture,
+    uniform_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    world_pipeline: wgpu::RenderPipeline,
+    screen_pipeline: wgpu::RenderPipeline,
+    screen_queue: Vec<ScreenText>,
+    world_queue: Vec<WorldText>,
+    vertices: Vec<TextVertex>,
+    vertex_buffer: wgpu::Buffer,
+    capacity: usize,
+    world_count: u32,
+    screen_count: u32,
+}
+
+impl TextRenderer {
+    //  `font` becomes font 0, the default in TextStyle. World text is drawn in the scene pass, so its pipeline
+    //  matches that pass's sample count, screen text is drawn straight to the single sampled surface.
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        font: Font,
+    ) -> Self {
+        let atlas = GlyphAtlas::new(ATLAS_SIZE, ATLAS_SIZE);
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d { width: ATLAS_SIZE, height: ATLAS_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph Atlas Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Uniform Buffer"),
+            size: std::mem::size_of::<TextUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &atlas_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let world_pipeline = Self::create_pipeline(device, camera_layout, &layout, color_format, Some(sample_count));
+        let screen_pipeline = Self::create_pipeline(device, camera_layout, &layout, color_format, None);
+        let capacity = 1024;
+        Self {
+            fonts: vec![font],
+            atlas,
+            atlas_texture,
+            uniform_buffer,
+            layout,
+            bind_group,
+            world_pipeline,
+            screen_pipeline,
+            screen_queue: Vec::new(),
+            world_queue: Vec::new(),
+            vertices: Vec::new(),
+            vertex_buffer: Self::create_buffer(device, capacity),
+            capacity,
+            world_count: 0,
+            screen_count: 0,
+        }
+    }
+
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.world_pipeline = Self::create_pipeline(device, camera_layout, &self.layout, color_format, Some(sample_count));
+    }
+
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        self.fonts.len() - 1
+    }
+
+    pub fn font(&self, font: FontId) -> &Font {
+        &self.fonts[font]
+    }
+
+    //  For sizing things around text before it's drawn
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        layout(&self.fonts[style.font], text, style.size, style.max_width)
+    }
+
+    //  `position` is the top left of the text in pixels
+    pub fn queue_screen(&mut self, text: &str, position: [f32; 2], style: TextStyle) {
+        self.screen_queue.push(ScreenText { text: text.to_string(), position, style });
+    }
+
+    //  Centred above `anchor`, with an em `em_height` world units tall
+    pub fn queue_world(&mut self, text: &str, anchor: Point3<f32>, em_height: f32, style: TextStyle) {
+        self.world_queue.push(WorldText { text: text.to_string(), anchor, em_height, style });
+    }
+
+    //  Lays out everything queued this frame, rasterizing any glyphs the atlas doesn't have yet, and uploads it
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: &Matrix4<f32>, width: u32, height: u32) {
+        if !self.build_vertices() {
+            log::info!("Glyph atlas is full, starting it again");
+            self.atlas.clear();
+            if !self.build_vertices() {
+                log::warn!("Too many glyphs for the atlas, some text is missing");
+            }
+        }
+        self.screen_queue.clear();
+        self.world_queue.clear();
+
+        if self.atlas.take_dirty() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &self.atlas_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                self.atlas.pixels(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(ATLAS_SIZE),
+                    rows_per_image: std::num::NonZeroU32::new(ATLAS_SIZE),
+                },
+                wgpu::Extent3d { width: ATLAS_SIZE, height: ATLAS_SIZE, depth_or_array_layers: 1 },
+            );
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[TextUniform {
+            viewport: [width as f32, height as f32],
+            _padding: [0; 2],
+            right: [view.x.x, view.y.x, view.z.x, 0.0],
+            up: [view.x.y, view.y.y, view.z.y, 0.0],
+        }]));
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+    }
+
+    //  Billboards, in the scene pass
+    pub fn draw_world<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        self.draw(render_pass, &self.world_pipeline, camera_bind_group, 0..self.world_count);
+    }
+
+    //  Over the finished frame, in a single sampled pass without depth
+    pub fn draw_screen<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        let start = self.world_count;
+        self.draw(render_pass, &self.screen_pipeline, camera_bind_group, start..start + self.screen_count);
+    }
+
+    fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        camera_bind_group: &'a wgpu::BindGroup,
+        vertices: std::ops::Range<u32>,
+    ) {
+        if vertices.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(vertices, 0..1);
+    }
+
+    //  World text first, then screen text. False if the atlas ran out of room part way.
+    fn build_vertices(&mut self) -> bool {
+        let mut vertices = std::mem::take(&mut self.vertices);
+        vertices.clear();
+        let (world_queue, screen_queue) = (std::mem::take(&mut self.world_queue), std::mem::take(&mut self.screen_queue));
+        let mut fits = true;
+        for text in &world_queue {
+            let layout = layout(&self.fonts[text.style.font], &text.text, text.style.size, text.style.max_width);
+            let scale = text.em_height / text.style.size;
+            let origin = [-0.5 * layout.width, -layout.height];
+            fits &= self.push_quads(&mut vertices, &layout, &text.style, |x, y| {
+                (text.anchor.into(), [(origin[0] + x) * scale, (origin[1] + y) * scale])
+            });
+        }
+        self.world_count = vertices.len() as u32;
+        for text in &screen_queue {
+            let layout = layout(&self.fonts[text.style.font], &text.text, text.style.size, text.style.max_width);
+            //  Whole pixels, so glyphs land on the texels they were rasterized for
+            let [left, top] = text.position.map(f32::round);
+            fits &= self.push_quads(&mut vertices, &layout, &text.style, |x, y| ([left + x, top + y, 0.0], [0.0; 2]));
+        }
+        self.screen_count = vertices.len() as u32 - self.world_count;
+        (self.vertices, self.world_queue, self.screen_queue) = (vertices, world_queue, screen_queue);
+        fits
+    }
+
+    //  `place` maps a corner in the layout's pixels to a vertex position and offset
+    fn push_quads(
+        &mut self,
+        vertices: &mut Vec<TextVertex>,
+        layout: &TextLayout,
+        style: &TextStyle,
+        place: impl Fn(f32, f32) -> ([f32; 3], [f32; 2]),
+    ) -> bool {
+        let font = &self.fonts[style.font];
+        let size = style.size.round().max(1.0) as u32;
+        let atlas_size = ATLAS_SIZE as f32;
+        for glyph in &layout.glyphs {
+            let key = GlyphKey { font: style.font, glyph: glyph.glyph, size };
+            let atlas_glyph = match self.atlas.get(&key) {
+                Some(atlas_glyph) => atlas_glyph,
+                None => match self.atlas.insert(key, &font.rasterize(glyph.glyph, size as f32)) {
+                    Some(atlas_glyph) => atlas_glyph,
+                    None => return false,
+                },
+            };
+            if atlas_glyph.width == 0 {
+                continue;
+            }
+            let x0 = glyph.x.round() + atlas_glyph.left as f32;
+            let y0 = glyph.y.round() + atlas_glyph.top as f32;
+            let (x1, y1) = (x0 + atlas_glyph.width as f32, y0 + atlas_glyph.height as f32);
+            let u0 = atlas_glyph.x as f32 / atlas_size;
+            let v0 = atlas_glyph.y as f32 / atlas_size;
+            let u1 = (atlas_glyph.x + atlas_glyph.width) as f32 / atlas_size;
+            let v1 = (atlas_glyph.y + atlas_glyph.height) as f32 / atlas_size;
+            for (x, y, u, v) in [(x0, y0, u0, v0), (x0, y1, u0, v1), (x1, y1, u1, v1), (x0, y0, u0, v0), (x1, y1, u1, v1), (x1, y0, u1, v0)] {
+                let (position, offset) = place(x, y);
+                vertices.push(TextVertex { position, offset, tex_coords: [u, v], color: style.color });
+            }
+        }
+        true
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    //  With a sample count for the world pipeline, in the scene pass and depth tested, or without for screen text
+    fn create_pipeline(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        text_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        world_sample_count: Option<u32>,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../../res/shaders/text.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, text_layout],
+            push_constant_ranges: &[],
+        });
+        let (label, entry_point) = match world_sample_count {
+            Some(_) => ("World Text Pipeline", "vs_world"),
+            None => ("Screen Text Pipeline", "vs_screen"),
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState { module: &shader, entry_point, buffers: &[TextVertex::desc()] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            //  Billboards are seen from both sides as the camera moves around them
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: world_sample_count.map(|_| wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: world_sample_count.unwrap_or(1), ..Default::default() },
+            multiview: None,
+        })
+    }
+}
@@ -7,7 +7,9 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;    //  Needed for when we create the depth stage of the render_pipeline and for creating the depth texture itself
+    //  Needed for when we create the depth stage of the render_pipeline and for creating the depth texture itself.
+    //  The stencil marks selected bricks for the outline pass.
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
 
     pub fn from_bytes(
         device: &wgpu::Device,
@@ -20,6 +22,18 @@ impl Texture {
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
+    //  1x1 texture of a single colour, used when a material doesn't have a map
+    pub fn solid_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: [u8; 4],
+        label: &str,
+        is_normal_map: bool,
+    ) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)));
+        Self::from_image(device, queue, &img, Some(label), is_normal_map)
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -71,9 +85,10 @@ impl Texture {
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(
             &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                //  Wrapped like OBJ and glTF expect, the stud normal map repeats once per stud across flat tops
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
                 mag_filter: wgpu::FilterMode::Linear,
                 min_filter: wgpu::FilterMode::Nearest,
                 mipmap_filter: wgpu::FilterMode::Nearest,
@@ -84,7 +99,12 @@ impl Texture {
         Ok(Self { texture, view, sampler })
     }
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> DepthTexture {
         let size = wgpu::Extent3d { //  Depth texture needs to be the same size as the screen if we want to render things correctly. We can use config to make sure the dimensions are correct.
             width: config.width,
             height: config.height,
@@ -94,7 +114,8 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            //  Has to match the colour target it's drawn with
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT   //  We are rendering to this texture so we need to add the RENDER_ATTACHMENT flag
@@ -103,21 +124,19 @@ impl Texture {
         let texture = device.create_texture(&desc);
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(
-            &wgpu::SamplerDescriptor {  //  Required if we ever want to sample our depth texture
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                compare: Some(wgpu::CompareFunction::LessEqual),    //  Required if we decide to render our depth texture. Due to how the sampler_comparison and textureSampleCompare() interacts with the texture() function in GLSL
-                lod_min_clamp: -100.0,
-                lod_max_clamp: 100.0,
-                ..Default::default()
-            }
-        );
+        let depth_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
 
-        Self { texture, view, sampler }
+        DepthTexture { texture, view, depth_view }
     }
+}
+
+pub struct DepthTexture {
+    pub texture: wgpu::Texture,
+    //  Depth and stencil, for attaching to render passes
+    pub view: wgpu::TextureView,
+    //  Just the depth, for reading in shaders. Bind groups can't take a view of both.
+    pub depth_view: wgpu::TextureView,
 }
\ No newline at end of file
@@ -0,0 +1,349 @@
+//  VIRTUAL FILESYSTEM - Stacks plain directories, zip archives and .bhpack files on top of each other so mods and add-ons
+//  can ship as a single file and override base assets. Every path is relative to the root of its layer, e.g. "bricks/cube.obj".
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use anyhow::{anyhow, bail, Context};
+
+//  Magic bytes at the start of every .bhpack file
+pub const PACK_MAGIC: &[u8; 4] = b"BHPK";
+pub const PACK_VERSION: u32 = 1;
+//  Priority of the base "res" directory, anything mounted above this overrides it
+pub const BASE_PRIORITY: i32 = 0;
+
+pub trait Layer: Send + Sync {
+    //  Returns None if the file isn't in this layer so the next layer down can be tried
+    fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>>;
+    fn list(&self) -> Vec<String>;
+    fn contains(&self, path: &str) -> bool {
+        self.list().iter().any(|p| p == path)
+    }
+}
+
+pub struct DirectoryLayer {
+    root: PathBuf,
+}
+
+impl DirectoryLayer {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn walk(dir: &Path, prefix: &str, out: &mut Vec<String>) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => Self::walk(&entry.path(), &path, out),
+                Ok(_) => out.push(path),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl Layer for DirectoryLayer {
+    fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        let full_path = self.root.join(path);
+        if !full_path.is_file() {
+            return None;
+        }
+        Some(std::fs::read(&full_path).with_context(|| format!("Failed to read {:?}", full_path)))
+    }
+
+    fn list(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        Self::walk(&self.root, "", &mut out);
+        out
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+}
+
+pub struct ZipLayer {
+    //  ZipArchive needs &mut to read an entry, so reads are serialised through the mutex
+    archive: Mutex<zip::ZipArchive<Cursor<Vec<u8>>>>,
+    //  Normalised path -> name inside the archive
+    names: HashMap<String, String>,
+}
+
+impl ZipLayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).with_context(|| format!("Failed to read zip {:?}", path))?;
+        Self::from_bytes(data).with_context(|| format!("Failed to open zip {:?}", path))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        let archive = zip::ZipArchive::new(Cursor::new(data))?;
+        let names = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| (normalize_path(name), name.to_string()))
+            .collect();
+
+        Ok(Self { archive: Mutex::new(archive), names })
+    }
+}
+
+impl Layer for ZipLayer {
+    fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        let name = self.names.get(path)?;
+        let mut archive = self.archive.lock().unwrap();
+        let result = archive.by_name(name).map_err(anyhow::Error::from).and_then(|mut file| {
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            Ok(data)
+        });
+        Some(result.with_context(|| format!("Failed to read {:?} from zip", name)))
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.names.keys().cloned().collect()
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.names.contains_key(path)
+    }
+}
+
+//  .bhpack layout (all integers little endian):
+//      magic "BHPK" | version u32 | entry count u32
+//      entry table: name length u16 | name (utf-8) | offset u64 | size u64
+//      file data, offsets are from the start of the pack
+pub struct PackLayer {
+    data: Vec<u8>,
+    entries: HashMap<String, (usize, usize)>,
+}
+
+impl PackLayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).with_context(|| format!("Failed to read pack {:?}", path))?;
+        Self::from_bytes(data).with_context(|| format!("Failed to open pack {:?}", path))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(&data[..]);
+        let mut magic = [0u8; 4];
+        cursor.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            bail!("Not a pack file (bad magic)");
+        }
+        let version = read_u32(&mut cursor)?;
+        if version != PACK_VERSION {
+            bail!("Unsupported pack version {}", version);
+        }
+
+        let count = read_u32(&mut cursor)?;
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let mut len = [0u8; 2];
+            cursor.read_exact(&mut len)?;
+            let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+            cursor.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| anyhow!("Pack entry name is not utf-8"))?;
+            let offset = read_u64(&mut cursor)? as usize;
+            let size = read_u64(&mut cursor)? as usize;
+            if offset.checked_add(size).is_none_or(|end| end > data.len()) {
+                bail!("Pack entry {:?} points outside of the pack", name);
+            }
+            entries.insert(normalize_path(&name), (offset, size));
+        }
+
+        Ok(Self { data, entries })
+    }
+}
+
+impl Layer for PackLayer {
+    fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        let &(offset, size) = self.entries.get(path)?;
+        Some(Ok(self.data[offset..offset + size].to_vec()))
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<u32> {
+    let mut bytes = [0u8; 4];
+    cursor.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<u64> {
+    let mut bytes = [0u8; 8];
+    cursor.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+//  Builds a .bhpack from (path, contents) pairs, used by tooling to package mods
+pub fn write_pack<P: AsRef<Path>>(path: P, files: &[(String, Vec<u8>)]) -> anyhow::Result<()> {
+    let names = files.iter().map(|(name, _)| normalize_path(name)).collect::<Vec<_>>();
+    let table_size: usize = names.iter().map(|name| 2 + name.len() + 16).sum();
+    let mut offset = (4 + 4 + 4 + table_size) as u64;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(PACK_MAGIC);
+    out.extend_from_slice(&PACK_VERSION.to_le_bytes());
+    out.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for (name, (_, contents)) in names.iter().zip(files) {
+        let len = u16::try_from(name.len()).map_err(|_| anyhow!("Pack entry name {:?} is too long", name))?;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+        offset += contents.len() as u64;
+    }
+    for (_, contents) in files {
+        out.extend_from_slice(contents);
+    }
+
+    let path = path.as_ref();
+    std::fs::write(path, out).with_context(|| format!("Failed to write pack {:?}", path))
+}
+
+//  Turns "./bricks\\..\\bricks/cube.obj" into "bricks/cube.obj" so every layer agrees on what a path looks like
+pub fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+//  Directory part of a normalised path, used to resolve files referenced relative to another file (e.g. mtllib in an .obj)
+pub fn parent_dir(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+pub struct Mount {
+    pub name: String,
+    pub priority: i32,
+    layer: Box<dyn Layer>,
+}
+
+#[derive(Default)]
+pub struct Vfs {
+    //  Sorted from highest to lowest priority, the first layer containing a file wins
+    mounts: Vec<Mount>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //  Layers with a higher priority override lower ones. With equal priority the most recently mounted layer wins.
+    pub fn mount<L: Layer + 'static>(&mut self, name: &str, priority: i32, layer: L) {
+        let index = self.mounts.iter().position(|m| m.priority <= priority).unwrap_or(self.mounts.len());
+        self.mounts.insert(index, Mount {
+            name: name.to_string(),
+            priority,
+            layer: Box::new(layer),
+        });
+    }
+
+    //  Picks the layer type from the path: directories, .zip archives or .bhpack files
+    pub fn mount_path<P: AsRef<Path>>(&mut self, path: P, priority: i32) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let name = path.to_string_lossy().into_owned();
+        if path.is_dir() {
+            self.mount(&name, priority, DirectoryLayer::new(path));
+            return Ok(());
+        }
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("zip") => self.mount(&name, priority, ZipLayer::open(path)?),
+            Some("bhpack") => self.mount(&name, priority, PackLayer::open(path)?),
+            _ => bail!("Don't know how to mount {:?}", path),
+        }
+        Ok(())
+    }
+
+    pub fn unmount(&mut self, name: &str) -> bool {
+        let len = self.mounts.len();
+        self.mounts.retain(|m| m.name != name);
+        self.mounts.len() != len
+    }
+
+    pub fn mounts(&self) -> impl Iterator<Item = &Mount> {
+        self.mounts.iter()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let path = normalize_path(path);
+        self.mounts.iter().any(|m| m.layer.contains(&path))
+    }
+
+    pub fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let path = normalize_path(path);
+        for mount in &self.mounts {
+            if let Some(result) = mount.layer.read(&path) {
+                return result.with_context(|| format!("Failed to load {:?} from {:?}", path, mount.name));
+            }
+        }
+        Err(anyhow!("{:?} was not found in any mounted layer", path))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> anyhow::Result<String> {
+        let data = self.read(path)?;
+        String::from_utf8(data).with_context(|| format!("{:?} is not valid utf-8", path))
+    }
+
+    //  Every file under `dir` across all layers, without duplicates
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let dir = normalize_path(dir);
+        let prefix = if dir.is_empty() { dir } else { format!("{}/", dir) };
+        self.mounts
+            .iter()
+            .flat_map(|m| m.layer.list())
+            .filter(|path| path.starts_with(&prefix))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    //  Brick definitions are the model files living under "bricks/" in any layer
+    pub fn list_bricks(&self) -> Vec<String> {
+        self.list("bricks")
+            .into_iter()
+            .filter(|path| {
+                let path = path.to_ascii_lowercase();
+                path.ends_with(".obj") || path.ends_with(".gltf") || path.ends_with(".glb")
+            })
+            .collect()
+    }
+}
+
+//  Global VFS used by engine::resources, starts out with just the base "res" directory mounted
+pub fn global() -> &'static RwLock<Vfs> {
+    static VFS: OnceLock<RwLock<Vfs>> = OnceLock::new();
+    VFS.get_or_init(|| {
+        let mut vfs = Vfs::new();
+        vfs.mount("base", BASE_PRIORITY, DirectoryLayer::new(Path::new(env!("OUT_DIR")).join("res")));
+        RwLock::new(vfs)
+    })
+}
@@ -3,6 +3,9 @@ use winit::{event::*, dpi::PhysicalPosition};
 use instant::Duration;
 use std::f32::consts::FRAC_PI_2;
 
+use crate::engine::bounds::Aabb;
+use crate::game::input::{Action, InputMap};
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -12,6 +15,18 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 );
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+//  Radians a second with a stick pushed all the way
+const STICK_LOOK_SPEED: f32 = 2.5;
+//  How long switching modes or framing takes to settle, in seconds
+const TRANSITION_TIME: f32 = 0.35;
+//  Orbit distance per pixel of scroll, as a fraction of the current distance so it feels the same close up or far out
+const DOLLY_RATE: f32 = 0.001;
+const MIN_ORBIT_DISTANCE: f32 = 0.5;
+const MAX_ORBIT_DISTANCE: f32 = 500.0;
+//  Panning moves this much of the orbit distance per pixel, so whatever's at the focus follows the cursor
+const PAN_RATE: f32 = 0.0015;
+//  Framing leaves a little room around the bounds
+const FRAME_MARGIN: f32 = 1.1;
 
 #[derive(Debug)]
 pub struct Camera {
@@ -38,26 +53,32 @@ impl Camera {
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
-        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
-
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(
-                cos_pitch * cos_yaw,
-                sin_pitch,
-                cos_pitch * sin_yaw
-            ).normalize(),
-            Vector3::unit_y(),
-        )
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        look_direction(self.yaw, self.pitch)
     }
 }
 
+fn look_direction(yaw: Rad<f32>, pitch: Rad<f32>) -> Vector3<f32> {
+    let (sin_pitch, cos_pitch) = pitch.0.sin_cos();
+    let (sin_yaw, cos_yaw) = yaw.0.sin_cos();
+    Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+}
+
+//  Keep the camera's angle from going too high/low
+fn clamp_pitch(pitch: Rad<f32>) -> Rad<f32> {
+    Rad(pitch.0.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2))
+}
+
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
     znear: f32,
     zfar: f32,
+    //  Sub-pixel offset in NDC for TAA, zero otherwise
+    jitter: Vector2<f32>,
 }
 
 impl Projection {
@@ -73,6 +94,7 @@ impl Projection {
             fovy: fovy.into(),
             znear,
             zfar,
+            jitter: Vector2::zero(),
         }
     }
 
@@ -80,11 +102,64 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    //  How many pixels tall something one unit high is one unit in front of the camera, for working out screen sizes
+    pub fn pixels_per_unit(&self, screen_height: u32) -> f32 {
+        screen_height as f32 / (2.0 * (self.fovy / 2.0).tan())
+    }
+
+    //  The smaller of the vertical and horizontal fields of view, for fitting things on screen
+    pub fn narrowest_fov(&self) -> Rad<f32> {
+        let horizontal = Rad(2.0 * ((self.fovy / 2.0).tan() * self.aspect).atan());
+        if horizontal < self.fovy { horizontal } else { self.fovy }
+    }
+
+    pub fn set_jitter(&mut self, jitter: Vector2<f32>) {
+        self.jitter = jitter;
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.jitter.extend(0.0)) * self.calc_unjittered_matrix()
+    }
+
+    //  What calc_matrix would give without the jitter, for reprojecting last frame's pixels
+    pub fn calc_unjittered_matrix(&self) -> Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    //  Moves and turns where it's looking
+    #[default]
+    Fly,
+    //  Turns around a focus point, pans the focus in the view plane and dollies towards it
+    Orbit,
+}
+
+//  The camera looks at `focus` from `distance` away, along the direction yaw and pitch give
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    pub focus: Point3<f32>,
+    pub distance: f32,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+}
+
+impl Orbit {
+    pub fn position(&self) -> Point3<f32> {
+        self.focus - look_direction(self.yaw, self.pitch) * self.distance
+    }
+}
+
+//  Where the camera was when it set off for a new orbit, blended away from over TRANSITION_TIME
+#[derive(Debug)]
+struct Transition {
+    position: Point3<f32>,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    elapsed: f32,
+}
+
 #[derive(Debug)]
 pub struct CameraController {
     amount_left: f32,
@@ -96,8 +171,13 @@ pub struct CameraController {
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
+    pan_horizontal: f32,
+    pan_vertical: f32,
     speed: f32,
     sensitivity: f32,
+    mode: CameraMode,
+    orbit: Orbit,
+    transition: Option<Transition>,
 }
 
 impl CameraController {
@@ -112,40 +192,72 @@ impl CameraController {
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
+            pan_horizontal: 0.0,
+            pan_vertical: 0.0,
             speed,
             sensitivity,
+            mode: CameraMode::Fly,
+            orbit: Orbit { focus: Point3::origin(), distance: 10.0, yaw: Rad(0.0), pitch: Rad(0.0) },
+            transition: None,
         }
     }
 
-    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed { 1.0 } else { 0.0 };
-        match key {
-            VirtualKeyCode::W | VirtualKeyCode::Up => {
-                self.amount_forward = amount;
-                true
-            }
-            VirtualKeyCode::S | VirtualKeyCode::Down => {
-                self.amount_backward = amount;
-                true
-            }
-            VirtualKeyCode::A | VirtualKeyCode::Left => {
-                self.amount_left = amount;
-                true
-            }
-            VirtualKeyCode::D | VirtualKeyCode::Right => {
-                self.amount_right = amount;
-                true
-            }
-            VirtualKeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            VirtualKeyCode::LShift => {
-                self.amount_down = amount;
-                true
-            }
-            _ => false,
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn orbit(&self) -> &Orbit {
+        &self.orbit
+    }
+
+    //  Turns to look at `focus` and starts orbiting it from where the camera is now
+    pub fn orbit_around(&mut self, camera: &Camera, focus: Point3<f32>) {
+        let offset = focus - camera.position;
+        let distance = offset.magnitude();
+        let (yaw, pitch) = if distance > f32::EPSILON {
+            (Rad(offset.z.atan2(offset.x)), clamp_pitch(Rad((offset.y / distance).asin())))
+        } else {
+            (camera.yaw, camera.pitch)
+        };
+        let distance = distance.clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+        self.start_orbit(camera, Orbit { focus, distance, yaw, pitch });
+    }
+
+    //  Orbits the middle of `bounds`, backed off far enough that all of it fits in `fov`, from the way the camera is
+    //  facing now
+    pub fn frame(&mut self, camera: &Camera, bounds: &Aabb, fov: Rad<f32>) {
+        if bounds.is_empty() {
+            return;
         }
+        let radius = bounds.half_extents().magnitude();
+        let distance = (radius / (fov / 2.0).sin() * FRAME_MARGIN).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+        let orbit = Orbit { focus: bounds.center(), distance, yaw: camera.yaw, pitch: camera.pitch };
+        self.start_orbit(camera, orbit);
+    }
+
+    //  Flies on from wherever the camera is, so there's nothing to blend
+    pub fn fly(&mut self) {
+        self.mode = CameraMode::Fly;
+        self.transition = None;
+    }
+
+    fn start_orbit(&mut self, camera: &Camera, orbit: Orbit) {
+        self.orbit = orbit;
+        self.mode = CameraMode::Orbit;
+        let (position, yaw, pitch) = (camera.position, camera.yaw, camera.pitch);
+        self.transition = Some(Transition { position, yaw, pitch, elapsed: 0.0 });
+    }
+
+    //  Movement comes from whatever's bound to the move actions, a stick turns the camera on top of the mouse
+    pub fn process_actions(&mut self, input: &InputMap) {
+        self.amount_forward = input.value(Action::MoveForward);
+        self.amount_backward = input.value(Action::MoveBackward);
+        self.amount_left = input.value(Action::MoveLeft);
+        self.amount_right = input.value(Action::MoveRight);
+        self.amount_up = input.value(Action::MoveUp);
+        self.amount_down = input.value(Action::MoveDown);
+        self.rotate_horizontal += (input.value(Action::LookRight) - input.value(Action::LookLeft)) * STICK_LOOK_SPEED;
+        self.rotate_vertical += (input.value(Action::LookDown) - input.value(Action::LookUp)) * STICK_LOOK_SPEED;
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
@@ -153,6 +265,11 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
+    pub fn process_pan(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.pan_horizontal += mouse_dx as f32;
+        self.pan_vertical += mouse_dy as f32;
+    }
+
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
         self.scroll = -match delta {
             //  Assuming a line is 100~ px
@@ -165,8 +282,20 @@ impl CameraController {
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
-        let dt = dt.as_secs_f32();
+        match self.mode {
+            CameraMode::Fly => self.update_fly(camera, dt.as_secs_f32()),
+            CameraMode::Orbit => self.update_orbit(camera, dt.as_secs_f32()),
+        }
+
+        //  If process_mouse isn't called every frame, these values will not be set to zero and the camera will rotate when moving in a non-cardinal direction.
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.pan_horizontal = 0.0;
+        self.pan_vertical = 0.0;
+        self.scroll = 0.0;
+    }
 
+    fn update_fly(&mut self, camera: &mut Camera, dt: f32) {
         //  Move forward/backward and left/right
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
@@ -176,27 +305,69 @@ impl CameraController {
 
         //  Move in/out aka "zoom"
         //  Not actual zoom, currently just changes the camera position.
-        let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
-        let scrollward = Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
+        let scrollward = camera.forward();
         camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
 
         //  Move up/down. Since no roll is used, we can modify the y coord directly
         camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
 
+        //  Panning slides the camera across the view, as it would the focus if there was one
+        camera.position += self.pan_offset(camera.yaw, camera.pitch) * self.orbit.distance;
+
         //  Rotate
         camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
         camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        camera.pitch = clamp_pitch(camera.pitch);
+    }
 
-        //  If process_mouse isn't called every frame, these values will not be set to zero and the camera will rotate when moving in a non-cardinal direction.
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+    fn update_orbit(&mut self, camera: &mut Camera, dt: f32) {
+        let pan = self.pan_offset(self.orbit.yaw, self.orbit.pitch);
+        let orbit = &mut self.orbit;
 
-        //  Keep the cameras angle from going too high/low
-        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = -Rad(SAFE_FRAC_PI_2);
-        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = Rad(SAFE_FRAC_PI_2);
+        //  Moving carries the focus along the ground with the camera
+        let (yaw_sin, yaw_cos) = orbit.yaw.0.sin_cos();
+        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin);
+        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos);
+        orbit.focus += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
+        orbit.focus += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        orbit.focus.y += (self.amount_up - self.amount_down) * self.speed * dt;
+
+        orbit.focus += pan * orbit.distance;
+        let distance = orbit.distance * (self.scroll * DOLLY_RATE).exp();
+        orbit.distance = distance.clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+
+        orbit.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
+        orbit.pitch = clamp_pitch(orbit.pitch + Rad(-self.rotate_vertical) * self.sensitivity * dt);
+
+        let (position, yaw, pitch) = (orbit.position(), orbit.yaw, orbit.pitch);
+        match &mut self.transition {
+            Some(transition) => {
+                transition.elapsed += dt;
+                let t = (transition.elapsed / TRANSITION_TIME).min(1.0);
+                //  Eased in and out, and the short way round
+                let t = t * t * (3.0 - 2.0 * t);
+                let turn = (yaw - transition.yaw).normalize_signed();
+                camera.position = transition.position + (position - transition.position) * t;
+                camera.yaw = transition.yaw + turn * t;
+                camera.pitch = transition.pitch + (pitch - transition.pitch) * t;
+                if t >= 1.0 {
+                    self.transition = None;
+                }
+            }
+            None => {
+                camera.position = position;
+                camera.yaw = yaw;
+                camera.pitch = pitch;
+            }
         }
     }
-}
\ No newline at end of file
+
+    //  How far the accumulated pan moves the view, per unit of distance from the camera. Dragging right moves the view
+    //  left so the scene follows the cursor.
+    fn pan_offset(&self, yaw: Rad<f32>, pitch: Rad<f32>) -> Vector3<f32> {
+        let forward = look_direction(yaw, pitch);
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        (up * self.pan_vertical - right * self.pan_horizontal) * PAN_RATE
+    }
+}
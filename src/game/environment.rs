@@ -0,0 +1,149 @@
+//  ENVIRONMENT - The sky and fog a build is seen in. Saved with the build, as JSON under the glTF scene's extras.
+
+use anyhow::{bail, Context};
+use serde_json::{json, Value};
+
+use crate::game::uniform::FogUniform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FogMode {
+    Off,
+    //  Fades in evenly from `start` to `end`
+    #[default]
+    Linear,
+    //  Thickens with distance past `start` by `density` per unit
+    Exponential,
+    //  Exponential, but thinning out above `height` by `height_falloff` per unit so it pools low to the ground
+    Height,
+}
+
+impl FogMode {
+    pub const ALL: [FogMode; 4] = [FogMode::Off, FogMode::Linear, FogMode::Exponential, FogMode::Height];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FogMode::Off => "off",
+            FogMode::Linear => "linear",
+            FogMode::Exponential => "exponential",
+            FogMode::Height => "height",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+    pub height: f32,
+    pub height_falloff: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Linear,
+            //  Fully fogged just before the far plane at 100, so nothing gets visibly clipped
+            start: 50.0,
+            end: 95.0,
+            density: 0.03,
+            height: 0.0,
+            height_falloff: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+    //  Linear RGB, what the screen is cleared to and what fog fades towards
+    pub sky_color: [f32; 3],
+    pub fog: Fog,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            sky_color: [0.1, 0.2, 0.3],
+            fog: Fog::default(),
+        }
+    }
+}
+
+impl Environment {
+    pub fn fog_uniform(&self) -> FogUniform {
+        FogUniform {
+            color: self.sky_color,
+            mode: self.fog.mode as u32,
+            start: self.fog.start,
+            end: self.fog.end,
+            density: self.fog.density,
+            height: self.fog.height,
+            height_falloff: self.fog.height_falloff,
+            _padding: [0; 3],
+        }
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b] = self.sky_color.map(f64::from);
+        wgpu::Color { r, g, b, a: 1.0 }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "skyColor": self.sky_color,
+            "fog": {
+                "mode": self.fog.mode.name(),
+                "start": self.fog.start,
+                "end": self.fog.end,
+                "density": self.fog.density,
+                "height": self.fog.height,
+                "heightFalloff": self.fog.height_falloff,
+            },
+        })
+    }
+
+    //  Anything missing keeps its default, so older builds still load
+    pub fn from_json(value: &Value) -> anyhow::Result<Self> {
+        let mut environment = Self::default();
+        if let Some(sky_color) = value.get("skyColor") {
+            let channels = sky_color
+                .as_array()
+                .filter(|channels| channels.len() == 3)
+                .context("skyColor should be an array of 3 numbers")?;
+            for (out, channel) in environment.sky_color.iter_mut().zip(channels) {
+                *out = channel.as_f64().context("skyColor should be an array of 3 numbers")? as f32;
+            }
+        }
+        if let Some(fog) = value.get("fog") {
+            if let Some(mode) = fog.get("mode") {
+                let name = mode.as_str().context("fog mode should be a string")?;
+                let Some(mode) = FogMode::from_name(name) else {
+                    bail!("Unknown fog mode {:?}", name);
+                };
+                environment.fog.mode = mode;
+            }
+            let fields = [
+                ("start", &mut environment.fog.start),
+                ("end", &mut environment.fog.end),
+                ("density", &mut environment.fog.density),
+                ("height", &mut environment.fog.height),
+                ("heightFalloff", &mut environment.fog.height_falloff),
+            ];
+            for (name, out) in fields {
+                if let Some(field) = fog.get(name) {
+                    *out = field.as_f64().with_context(|| format!("fog {} should be a number", name))? as f32;
+                }
+            }
+        }
+        Ok(environment)
+    }
+}
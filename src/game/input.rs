@@ -0,0 +1,794 @@
+//  INPUT - Named actions and the keys, mouse buttons and gamepad inputs bound to them. Loaded from input.json next to
+//  the executable, anything it leaves out keeps the default bindings.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde_json::{json, Map, Value};
+
+use crate::engine::gamepad::GamepadEvent;
+use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent};
+
+//  How far an axis has to be pushed to count as pressed
+pub const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    //  Turning the camera with a stick, the mouse turns it while DragLook is held
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    DragLook,
+    //  Slides the view while held, with the mouse
+    Pan,
+    ToggleOrbit,
+    FrameSelection,
+    Select,
+    RotateBrickCw,
+    RotateBrickCcw,
+    ToggleGui,
+    CycleDebugView,
+    Export,
+    ToggleGpuCulling,
+    ToggleHiZ,
+    CycleLod,
+    ToggleMsaa,
+    CycleAntiAliasing,
+    ToggleSsao,
+    CycleFog,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::LookUp,
+        Action::LookDown,
+        Action::DragLook,
+        Action::Pan,
+        Action::ToggleOrbit,
+        Action::FrameSelection,
+        Action::Select,
+        Action::RotateBrickCw,
+        Action::RotateBrickCcw,
+        Action::ToggleGui,
+        Action::CycleDebugView,
+        Action::Export,
+        Action::ToggleGpuCulling,
+        Action::ToggleHiZ,
+        Action::CycleLod,
+        Action::ToggleMsaa,
+        Action::CycleAntiAliasing,
+        Action::ToggleSsao,
+        Action::CycleFog,
+        Action::Quit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::LookLeft => "look_left",
+            Action::LookRight => "look_right",
+            Action::LookUp => "look_up",
+            Action::LookDown => "look_down",
+            Action::DragLook => "drag_look",
+            Action::Pan => "pan",
+            Action::ToggleOrbit => "toggle_orbit",
+            Action::FrameSelection => "frame_selection",
+            Action::Select => "select",
+            Action::RotateBrickCw => "rotate_brick_cw",
+            Action::RotateBrickCcw => "rotate_brick_ccw",
+            Action::ToggleGui => "toggle_gui",
+            Action::CycleDebugView => "cycle_debug_view",
+            Action::Export => "export",
+            Action::ToggleGpuCulling => "toggle_gpu_culling",
+            Action::ToggleHiZ => "toggle_hiz",
+            Action::CycleLod => "cycle_lod",
+            Action::ToggleMsaa => "toggle_msaa",
+            Action::CycleAntiAliasing => "cycle_anti_aliasing",
+            Action::ToggleSsao => "toggle_ssao",
+            Action::CycleFog => "cycle_fog",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_bindings(self) -> &'static [&'static str] {
+        match self {
+            Action::MoveForward => &["W", "Up", "Gamepad LeftStickY+"],
+            Action::MoveBackward => &["S", "Down", "Gamepad LeftStickY-"],
+            Action::MoveLeft => &["A", "Left", "Gamepad LeftStickX-"],
+            Action::MoveRight => &["D", "Right", "Gamepad LeftStickX+"],
+            Action::MoveUp => &["Space", "Gamepad RightTrigger+"],
+            Action::MoveDown => &["LShift", "Gamepad LeftTrigger+"],
+            Action::LookLeft => &["Gamepad RightStickX-"],
+            Action::LookRight => &["Gamepad RightStickX+"],
+            Action::LookUp => &["Gamepad RightStickY+"],
+            Action::LookDown => &["Gamepad RightStickY-"],
+            Action::DragLook => &["Mouse Left"],
+            Action::Pan => &["Mouse Middle"],
+            Action::ToggleOrbit => &["O", "Gamepad North"],
+            Action::FrameSelection => &["F", "Gamepad West"],
+            Action::Select => &["Mouse Right", "Gamepad South"],
+            Action::RotateBrickCw => &["R", "Gamepad DPadRight"],
+            Action::RotateBrickCcw => &["Q", "Gamepad DPadLeft"],
+            Action::ToggleGui => &["F1", "Gamepad Select"],
+            Action::CycleDebugView => &["F4"],
+            Action::Export => &["F5", "Ctrl+S"],
+            Action::ToggleGpuCulling => &["F6"],
+            Action::ToggleHiZ => &["F7"],
+            Action::CycleLod => &["F8"],
+            Action::ToggleMsaa => &["F9"],
+            Action::CycleAntiAliasing => &["F10"],
+            Action::ToggleSsao => &["F11"],
+            Action::CycleFog => &["F12"],
+            Action::Quit => &["Escape"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 14] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::West,
+        GamepadButton::North,
+        GamepadButton::LeftBumper,
+        GamepadButton::RightBumper,
+        GamepadButton::Select,
+        GamepadButton::Start,
+        GamepadButton::LeftStick,
+        GamepadButton::RightStick,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadButton::South => "South",
+            GamepadButton::East => "East",
+            GamepadButton::West => "West",
+            GamepadButton::North => "North",
+            GamepadButton::LeftBumper => "LeftBumper",
+            GamepadButton::RightBumper => "RightBumper",
+            GamepadButton::Select => "Select",
+            GamepadButton::Start => "Start",
+            GamepadButton::LeftStick => "LeftStick",
+            GamepadButton::RightStick => "RightStick",
+            GamepadButton::DPadUp => "DPadUp",
+            GamepadButton::DPadDown => "DPadDown",
+            GamepadButton::DPadLeft => "DPadLeft",
+            GamepadButton::DPadRight => "DPadRight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name() == name)
+    }
+}
+
+//  Sticks are -1 to 1 with y up, triggers 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftStickX,
+        GamepadAxis::LeftStickY,
+        GamepadAxis::RightStickX,
+        GamepadAxis::RightStickY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GamepadAxis::LeftStickX => "LeftStickX",
+            GamepadAxis::LeftStickY => "LeftStickY",
+            GamepadAxis::RightStickX => "RightStickX",
+            GamepadAxis::RightStickY => "RightStickY",
+            GamepadAxis::LeftTrigger => "LeftTrigger",
+            GamepadAxis::RightTrigger => "RightTrigger",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|axis| axis.name() == name)
+    }
+
+    //  The other axis of the same stick, triggers don't have one
+    pub fn partner(self) -> Option<Self> {
+        match self {
+            GamepadAxis::LeftStickX => Some(GamepadAxis::LeftStickY),
+            GamepadAxis::LeftStickY => Some(GamepadAxis::LeftStickX),
+            GamepadAxis::RightStickX => Some(GamepadAxis::RightStickY),
+            GamepadAxis::RightStickY => Some(GamepadAxis::RightStickX),
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => None,
+        }
+    }
+}
+
+//  Nothing inside `dead_zone`, then 0 to 1 out to the edge raised to the power of `curve`, so curves over 1 give finer
+//  control near the middle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisResponse {
+    pub dead_zone: f32,
+    pub curve: f32,
+}
+
+impl AxisResponse {
+    pub fn apply(&self, value: f32) -> f32 {
+        let t = ((value.abs() - self.dead_zone) / (1.0 - self.dead_zone)).clamp(0.0, 1.0);
+        t.powf(self.curve).copysign(value)
+    }
+}
+
+//  Sticks are shaped by how far they're pushed in any direction, so the dead zone is round and diagonals aren't slowed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadSettings {
+    pub stick: AxisResponse,
+    pub trigger: AxisResponse,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            stick: AxisResponse { dead_zone: 0.15, curve: 2.0 },
+            trigger: AxisResponse { dead_zone: 0.05, curve: 1.0 },
+        }
+    }
+}
+
+impl GamepadSettings {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "stickDeadZone": self.stick.dead_zone,
+            "stickCurve": self.stick.curve,
+            "triggerDeadZone": self.trigger.dead_zone,
+            "triggerCurve": self.trigger.curve,
+        })
+    }
+
+    //  Anything missing keeps its default
+    pub fn from_json(value: &Value) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        let fields = [
+            ("stickDeadZone", &mut settings.stick.dead_zone),
+            ("stickCurve", &mut settings.stick.curve),
+            ("triggerDeadZone", &mut settings.trigger.dead_zone),
+            ("triggerCurve", &mut settings.trigger.curve),
+        ];
+        for (name, out) in fields {
+            if let Some(field) = value.get(name) {
+                *out = field.as_f64().with_context(|| format!("gamepad {} should be a number", name))? as f32;
+            }
+        }
+        if !(0.0..1.0).contains(&settings.stick.dead_zone) || !(0.0..1.0).contains(&settings.trigger.dead_zone) {
+            bail!("Gamepad dead zones should be at least 0 and less than 1");
+        }
+        Ok(settings)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButton),
+    //  One direction of the axis, so a stick can drive two opposite actions
+    GamepadAxis(GamepadAxis, bool),
+}
+
+//  The keys a binding can name, by their VirtualKeyCode names
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down,
+        Back, Return, Space, Tab,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+        Apostrophe, Backslash, Comma, Equals, Grave, LBracket, Minus, Period, RBracket, Semicolon, Slash,
+        LAlt, LControl, LShift, LWin, RAlt, RControl, RShift, RWin,
+    ]
+};
+
+const MODIFIERS: [(ModifiersState, &str); 4] = [
+    (ModifiersState::CTRL, "Ctrl"),
+    (ModifiersState::SHIFT, "Shift"),
+    (ModifiersState::ALT, "Alt"),
+    (ModifiersState::LOGO, "Logo"),
+];
+
+impl Input {
+    pub fn name(self) -> String {
+        match self {
+            Input::Key(key) => format!("{:?}", key),
+            Input::Mouse(MouseButton::Left) => "Mouse Left".to_string(),
+            Input::Mouse(MouseButton::Right) => "Mouse Right".to_string(),
+            Input::Mouse(MouseButton::Middle) => "Mouse Middle".to_string(),
+            Input::Mouse(MouseButton::Other(button)) => format!("Mouse {}", button),
+            Input::GamepadButton(button) => format!("Gamepad {}", button.name()),
+            Input::GamepadAxis(axis, positive) => format!("Gamepad {}{}", axis.name(), if positive { '+' } else { '-' }),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(button) = name.strip_prefix("Mouse ") {
+            return Some(Input::Mouse(match button {
+                "Left" => MouseButton::Left,
+                "Right" => MouseButton::Right,
+                "Middle" => MouseButton::Middle,
+                other => MouseButton::Other(other.parse().ok()?),
+            }));
+        }
+        if let Some(input) = name.strip_prefix("Gamepad ") {
+            if let Some(axis) = input.strip_suffix('+') {
+                return GamepadAxis::from_name(axis).map(|axis| Input::GamepadAxis(axis, true));
+            }
+            if let Some(axis) = input.strip_suffix('-') {
+                return GamepadAxis::from_name(axis).map(|axis| Input::GamepadAxis(axis, false));
+            }
+            return GamepadButton::from_name(input).map(Input::GamepadButton);
+        }
+        KEYS.iter().find(|key| format!("{:?}", key) == name).map(|&key| Input::Key(key))
+    }
+
+    fn is_modifier(self) -> bool {
+        use VirtualKeyCode::*;
+        matches!(self, Input::Key(LAlt | LControl | LShift | LWin | RAlt | RControl | RShift | RWin))
+    }
+}
+
+//  An input, and the modifiers that have to be held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub input: Input,
+    pub modifiers: ModifiersState,
+}
+
+impl Binding {
+    pub fn new(input: Input) -> Self {
+        Self { input, modifiers: ModifiersState::empty() }
+    }
+
+    pub fn with_modifiers(self, modifiers: ModifiersState) -> Self {
+        Self { modifiers, ..self }
+    }
+
+    //  e.g. "Ctrl+Shift+S", "Mouse Right", "Gamepad LeftStickY+"
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for (modifier, prefix) in MODIFIERS {
+            if self.modifiers.contains(modifier) {
+                name.push_str(prefix);
+                name.push('+');
+            }
+        }
+        name + &self.input.name()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut rest = name.trim();
+        let mut modifiers = ModifiersState::empty();
+        'prefixes: loop {
+            for (modifier, prefix) in MODIFIERS {
+                if let Some(after) = rest.strip_prefix(prefix).and_then(|r| r.strip_prefix('+')) {
+                    modifiers |= modifier;
+                    rest = after;
+                    continue 'prefixes;
+                }
+            }
+            break;
+        }
+        Input::from_name(rest).map(|input| Binding { input, modifiers })
+    }
+}
+
+//  Where a rebind ended up, and the other actions now sharing its binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rebound {
+    pub action: Action,
+    pub binding: Binding,
+    pub conflicts: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingRebind {
+    action: Action,
+    //  Which of the action's bindings to replace, None adds another
+    slot: Option<usize>,
+    //  A modifier key went down, so letting go of it binds the modifier key itself
+    modifier_key: Option<Input>,
+}
+
+#[derive(Debug)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+    modifiers: ModifiersState,
+    down: HashSet<Input>,
+    //  As the pad reported them, shaped by gamepad_settings when they're read
+    axes: HashMap<GamepadAxis, f32>,
+    gamepad_settings: GamepadSettings,
+    //  Actions whose bindings were pressed since the last take_triggered
+    triggered: Vec<Action>,
+    rebinding: Option<PendingRebind>,
+    rebound: Option<Rebound>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let bindings = action.default_bindings().iter().filter_map(|name| Binding::from_name(name)).collect();
+                (action, bindings)
+            })
+            .collect();
+        Self {
+            bindings,
+            modifiers: ModifiersState::empty(),
+            down: HashSet::new(),
+            axes: HashMap::new(),
+            gamepad_settings: GamepadSettings::default(),
+            triggered: Vec::new(),
+            rebinding: None,
+            rebound: None,
+        }
+    }
+}
+
+impl InputMap {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let value = serde_json::from_str(&text).with_context(|| format!("{:?} isn't valid JSON", path))?;
+        Self::from_json(&value)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = serde_json::to_string_pretty(&self.to_json())?;
+        std::fs::write(path, text).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut actions = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let names = self.bindings(action).iter().map(|b| Value::from(b.name())).collect();
+                (action.name().to_string(), Value::Array(names))
+            })
+            .collect::<Map<_, _>>();
+        actions.insert("gamepad".to_string(), self.gamepad_settings.to_json());
+        Value::Object(actions)
+    }
+
+    //  Actions that aren't listed keep their default bindings, an empty list leaves one unbound
+    pub fn from_json(value: &Value) -> anyhow::Result<Self> {
+        let mut map = Self::default();
+        let actions = value.as_object().context("Input bindings should be an object of action names")?;
+        for (name, bindings) in actions {
+            if name == "gamepad" {
+                map.gamepad_settings = GamepadSettings::from_json(bindings)?;
+                continue;
+            }
+            let Some(action) = Action::from_name(name) else {
+                bail!("Unknown action {:?}", name);
+            };
+            let names = bindings.as_array().with_context(|| format!("{} should be an array of bindings", name))?;
+            let mut parsed = Vec::with_capacity(names.len());
+            for binding in names {
+                let binding = binding.as_str().with_context(|| format!("{} should be an array of bindings", name))?;
+                match Binding::from_name(binding) {
+                    Some(binding) => parsed.push(binding),
+                    None => bail!("Unknown binding {:?} for {}", binding, name),
+                }
+            }
+            map.bindings.insert(action, parsed);
+        }
+        Ok(map)
+    }
+
+    pub fn gamepad_settings(&self) -> GamepadSettings {
+        self.gamepad_settings
+    }
+
+    pub fn set_gamepad_settings(&mut self, settings: GamepadSettings) {
+        self.gamepad_settings = settings;
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set_bindings(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    //  Every action `binding` is bound to
+    pub fn actions_bound_to(&self, binding: Binding) -> Vec<Action> {
+        Action::ALL.into_iter().filter(|&a| self.bindings(a).contains(&binding)).collect()
+    }
+
+    //  Every binding shared by more than one action, in action order
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut conflicts: Vec<(Binding, Vec<Action>)> = Vec::new();
+        for action in Action::ALL {
+            for &binding in self.bindings(action) {
+                if conflicts.iter().any(|(b, _)| *b == binding) {
+                    continue;
+                }
+                let actions = self.actions_bound_to(binding);
+                if actions.len() > 1 {
+                    conflicts.push((binding, actions));
+                }
+            }
+        }
+        conflicts
+    }
+
+    //  The next input pressed becomes a binding for `action`, replacing its `slot`th binding or adding one. Escape
+    //  on its own cancels.
+    pub fn start_rebind(&mut self, action: Action, slot: Option<usize>) {
+        self.rebinding = Some(PendingRebind { action, slot, modifier_key: None });
+    }
+
+    pub fn cancel_rebind(&mut self) {
+        self.rebinding = None;
+    }
+
+    pub fn rebinding(&self) -> Option<Action> {
+        self.rebinding.map(|pending| pending.action)
+    }
+
+    pub fn take_rebound(&mut self) -> Option<Rebound> {
+        self.rebound.take()
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => match input.virtual_keycode {
+                Some(key) => self.key_input(key, input.state),
+                None => false,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers_changed(*modifiers);
+                false
+            }
+            WindowEvent::MouseInput { button, state, .. } => self.mouse_input(*button, *state),
+            //  Without focus the releases never arrive
+            WindowEvent::Focused(false) => {
+                self.release_all();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    pub fn handle_gamepad_event(&mut self, event: &GamepadEvent) -> bool {
+        match *event {
+            GamepadEvent::Button(button, pressed) => self.gamepad_button(button, pressed),
+            GamepadEvent::Axis(axis, value) => self.gamepad_axis(axis, value),
+            GamepadEvent::Disconnected => {
+                self.release_gamepad();
+                false
+            }
+        }
+    }
+
+    pub fn modifiers_changed(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn key_input(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        self.input(Input::Key(key), state == ElementState::Pressed)
+    }
+
+    pub fn mouse_input(&mut self, button: MouseButton, state: ElementState) -> bool {
+        self.input(Input::Mouse(button), state == ElementState::Pressed)
+    }
+
+    pub fn gamepad_button(&mut self, button: GamepadButton, pressed: bool) -> bool {
+        self.input(Input::GamepadButton(button), pressed)
+    }
+
+    //  Raw values, -1 to 1 for sticks and 0 to 1 for triggers. Each direction of the axis acts as a button once its
+    //  shaped value passes AXIS_PRESS_THRESHOLD, and moving one axis of a stick can change the other's shaped value.
+    pub fn gamepad_axis(&mut self, axis: GamepadAxis, value: f32) -> bool {
+        self.axes.insert(axis, value);
+        let mut used = false;
+        for axis in std::iter::once(axis).chain(axis.partner()) {
+            let shaped = self.axis_value(axis);
+            used |= self.input(Input::GamepadAxis(axis, true), shaped >= AXIS_PRESS_THRESHOLD);
+            used |= self.input(Input::GamepadAxis(axis, false), -shaped >= AXIS_PRESS_THRESHOLD);
+        }
+        used
+    }
+
+    //  An axis after its dead zone and response curve, -1 to 1
+    pub fn axis_value(&self, axis: GamepadAxis) -> f32 {
+        let raw = |axis| self.axes.get(&axis).copied().unwrap_or(0.0);
+        let value = raw(axis);
+        let Some(partner) = axis.partner() else {
+            return self.gamepad_settings.trigger.apply(value);
+        };
+        let length = value.hypot(raw(partner));
+        if length == 0.0 {
+            return 0.0;
+        }
+        value / length * self.gamepad_settings.stick.apply(length.min(1.0))
+    }
+
+    pub fn release_gamepad(&mut self) {
+        self.down.retain(|input| !matches!(input, Input::GamepadButton(_) | Input::GamepadAxis(..)));
+        self.axes.clear();
+    }
+
+    pub fn release_all(&mut self) {
+        self.down.clear();
+        self.axes.clear();
+        self.modifiers = ModifiersState::empty();
+    }
+
+    //  How far the action is pushed, 0 to 1. Buttons are all or nothing, axes are analog.
+    pub fn value(&self, action: Action) -> f32 {
+        self.bindings(action)
+            .iter()
+            .filter(|binding| self.binding_active(binding))
+            .map(|binding| self.input_value(binding.input))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.value(action) >= AXIS_PRESS_THRESHOLD
+    }
+
+    //  The actions pressed since last time, in the order they were pressed
+    pub fn take_triggered(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.triggered)
+    }
+
+    fn input(&mut self, input: Input, pressed: bool) -> bool {
+        let was_down = self.down.contains(&input);
+        if pressed {
+            self.down.insert(input);
+        } else {
+            self.down.remove(&input);
+        }
+        if self.rebinding.is_some() {
+            return self.capture(input, pressed, was_down);
+        }
+        //  Key repeat sends presses for keys that are already down
+        if pressed && !was_down {
+            let actions = self.most_specific(input).map(|(action, _)| action).collect::<Vec<_>>();
+            self.triggered.extend(actions);
+        }
+        Action::ALL.into_iter().any(|action| self.bindings(action).iter().any(|b| b.input == input))
+    }
+
+    fn capture(&mut self, input: Input, pressed: bool, was_down: bool) -> bool {
+        let Some(mut pending) = self.rebinding else {
+            return false;
+        };
+        let binding = if pressed && !was_down {
+            if input == Input::Key(VirtualKeyCode::Escape) && self.modifiers.is_empty() {
+                self.rebinding = None;
+                return true;
+            }
+            //  Wait to see if it's held for a combo or bound on its own
+            if input.is_modifier() {
+                pending.modifier_key = Some(input);
+                self.rebinding = Some(pending);
+                return true;
+            }
+            Binding::new(input).with_modifiers(self.modifiers)
+        } else if !pressed && pending.modifier_key == Some(input) {
+            Binding::new(input)
+        } else {
+            return true;
+        };
+        let bindings = self.bindings.entry(pending.action).or_default();
+        match pending.slot.filter(|&slot| slot < bindings.len()) {
+            Some(slot) => bindings[slot] = binding,
+            None if !bindings.contains(&binding) => bindings.push(binding),
+            None => {}
+        }
+        let conflicts = self.actions_bound_to(binding).into_iter().filter(|&a| a != pending.action).collect();
+        self.rebinding = None;
+        self.rebound = Some(Rebound { action: pending.action, binding, conflicts });
+        true
+    }
+
+    //  Bindings for `input` whose modifiers are all held, less the ones a binding needing more modifiers overrides,
+    //  so Ctrl+S doesn't also move backward
+    fn most_specific(&self, input: Input) -> impl Iterator<Item = (Action, Binding)> + '_ {
+        let matching = move || {
+            Action::ALL.into_iter().flat_map(move |action| {
+                self.bindings(action)
+                    .iter()
+                    .filter(move |b| b.input == input && self.modifiers.contains(b.modifiers))
+                    .map(move |&b| (action, b))
+            })
+        };
+        let most = matching().map(|(_, b)| b.modifiers.bits().count_ones()).max().unwrap_or(0);
+        matching().filter(move |(_, b)| b.modifiers.bits().count_ones() == most)
+    }
+
+    fn binding_active(&self, binding: &Binding) -> bool {
+        self.modifiers.contains(binding.modifiers) && self.most_specific(binding.input).any(|(_, b)| b == *binding)
+    }
+
+    fn input_value(&self, input: Input) -> f32 {
+        match input {
+            Input::GamepadAxis(axis, positive) => {
+                let value = self.axis_value(axis);
+                (if positive { value } else { -value }).clamp(0.0, 1.0)
+            }
+            input => if self.down.contains(&input) { 1.0 } else { 0.0 },
+        }
+    }
+}
@@ -1,8 +1,15 @@
+use cgmath::prelude::*;
+
 pub const NUM_INSTANCES_PER_ROW: u32 = 16;
+const SPACE_BETWEEN: f32 = 3.0;
+//  The brick every instance in the world uses for now
+pub const WORLD_BRICK: &str = "bricks/cube.obj";
 
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    //  Linear RGBA, multiplied with the material colour
+    pub color: [f32; 4],
 }
 
 #[repr(C)]
@@ -10,14 +17,35 @@ pub struct Instance {
 pub struct InstanceRaw {
     model: [[f32; 4]; 4],
     normal: [[f32; 3]; 3],
+    color: [f32; 4],
+    //  SELECTED and HOVERED, for the outline pass and the hover highlight
+    flags: u32,
+}
+
+impl InstanceRaw {
+    pub const SELECTED: u32 = 1;
+    pub const HOVERED: u32 = 2;
+
+    pub fn with_flags(self, flags: u32) -> Self {
+        Self { flags, ..self }
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
 }
 
 impl Instance {
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let model = cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation);
         InstanceRaw {
-            model: model.into(),
+            model: self.model_matrix().into(),
             normal: cgmath::Matrix3::from(self.rotation).into(),
+            color: self.color,
+            flags: 0,
         }
     }
 }
@@ -66,7 +94,38 @@ impl crate::engine::model::Vertex for InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 29]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
-}
\ No newline at end of file
+}
+//  The test world, a grid of bricks each tilted away from the centre. Doesn't touch the GPU so exporters can use it headless.
+pub fn grid_world() -> Vec<Instance> {
+    (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
+        (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+            let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+            let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+
+            let position = cgmath::Vector3 {x, y: 0.0, z};
+
+            let rotation = if position.is_zero() {
+                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+            } else {
+                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+            };
+
+            Instance {
+                position, rotation, color: [1.0; 4],
+            }
+        })
+    }).collect()
+}
@@ -1,3 +1,6 @@
 pub mod camera;
 pub mod instance;
-pub mod uniform;
\ No newline at end of file
+pub mod uniform;pub mod world;
+pub mod environment;
+pub mod selection;
+pub mod input;
@@ -0,0 +1,70 @@
+//  SELECTION - Which brick instances are selected and which one is under the cursor, by index into the instances.
+//  The renderer turns these into InstanceRaw flags for the outline pass and the hover highlight.
+
+use std::collections::BTreeSet;
+
+use crate::game::instance::InstanceRaw;
+
+#[derive(Debug, Default)]
+pub struct Selection {
+    selected: BTreeSet<usize>,
+    hovered: Option<usize>,
+    //  Set whenever the flags change, so the instances only get uploaded again when they need to
+    changed: bool,
+}
+
+impl Selection {
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selected.contains(&index)
+    }
+
+    pub fn selected(&self) -> impl Iterator<Item = usize> + '_ {
+        self.selected.iter().copied()
+    }
+
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+
+    //  Nothing to outline
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty() && self.hovered.is_none()
+    }
+
+    pub fn set_hovered(&mut self, hovered: Option<usize>) {
+        if self.hovered != hovered {
+            self.hovered = hovered;
+            self.changed = true;
+        }
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if !self.selected.remove(&index) {
+            self.selected.insert(index);
+        }
+        self.changed = true;
+    }
+
+    pub fn clear(&mut self) {
+        if !self.selected.is_empty() {
+            self.selected.clear();
+            self.changed = true;
+        }
+    }
+
+    pub fn flags(&self, index: usize) -> u32 {
+        let mut flags = 0;
+        if self.is_selected(index) {
+            flags |= InstanceRaw::SELECTED;
+        }
+        if self.hovered == Some(index) {
+            flags |= InstanceRaw::HOVERED;
+        }
+        flags
+    }
+
+    //  Whether anything changed since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
@@ -12,6 +12,48 @@ pub struct LightUniform {
     pub _padding2: u32,
 }
 
+//  Fog settings from the environment, see game::environment. Lives in the light bind group next to the light.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    //  The sky colour, which everything fades towards
+    pub color: [f32; 3],
+    //  FogMode as a number, 0 is off
+    pub mode: u32,
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+    pub height: f32,
+    pub height_falloff: f32,
+    pub _padding: [u32; 3],
+}
+
+//  Scalar material properties from the MTL file (Kd, d, Ks, Ns, Ke), multiplied with the material's textures
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub diffuse: [f32; 3],
+    pub opacity: f32,
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub emissive: [f32; 3],
+    pub _padding: u32,
+}
+
+impl Default for MaterialUniform {
+    //  Leaves the textures untouched, with the specular highlight the shader used to hardcode
+    fn default() -> Self {
+        Self {
+            diffuse: [1.0; 3],
+            opacity: 1.0,
+            specular: [1.0; 3],
+            shininess: 32.0,
+            emissive: [0.0; 3],
+            _padding: 0,
+        }
+    }
+}
+
 #[repr(C)]  //  needed for Rust to store the data for shaders correctly
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]    //  needed so we can store it in a buffer
 pub struct CameraUniform {
@@ -20,6 +62,12 @@ pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
@@ -28,6 +76,10 @@ impl CameraUniform {
         }
     }
 
+    pub fn view_proj(&self) -> cgmath::Matrix4<f32> {
+        self.view_proj.into()
+    }
+
     pub fn update_view_proj(&mut self, camera: &crate::game::camera::Camera, projection: &crate::game::camera::Projection) {
         self.view_position = camera.position.to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
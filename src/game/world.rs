@@ -0,0 +1,273 @@
+//  WORLD - Bricks snapped to the stud grid, stored in fixed size chunks so an edit only has to rebuild the meshes of the
+//  chunks around it. Positions and sizes are in grid cells: one stud across (x, z) and one plate high (y).
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::bail;
+
+//  Studs along each side of a chunk. Chunks are columns, they go on forever vertically.
+pub const CHUNK_SIZE: i32 = 32;
+//  World units per grid cell
+pub const STUD_SIZE: f32 = 1.0;
+pub const PLATE_HEIGHT: f32 = 0.4;
+//  A standard brick is three plates tall
+pub const BRICK_HEIGHT: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkCoord {
+    //  The chunk a grid cell falls in
+    pub fn containing(cell: [i32; 3]) -> Self {
+        Self {
+            x: cell[0].div_euclid(CHUNK_SIZE),
+            z: cell[2].div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    //  First cell of the chunk along x and z
+    pub fn origin(&self) -> [i32; 2] {
+        [self.x * CHUNK_SIZE, self.z * CHUNK_SIZE]
+    }
+
+    //  This chunk and the 8 around it
+    pub fn neighbourhood(self) -> impl Iterator<Item = ChunkCoord> {
+        (-1..=1).flat_map(move |dz| (-1..=1).map(move |dx| ChunkCoord { x: self.x + dx, z: self.z + dz }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brick {
+    //  Lowest corner
+    pub position: [i32; 3],
+    //  Studs wide, plates high, studs deep
+    pub size: [i32; 3],
+    //  Linear RGBA
+    pub color: [f32; 4],
+}
+
+impl Brick {
+    pub fn new(position: [i32; 3], size: [i32; 3], color: [f32; 4]) -> Self {
+        Self { position, size, color }
+    }
+
+    pub fn contains(&self, cell: [i32; 3]) -> bool {
+        (0..3).all(|i| cell[i] >= self.position[i] && cell[i] < self.position[i] + self.size[i])
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = [i32; 3]> + '_ {
+        let [x0, y0, z0] = self.position;
+        let [sx, sy, sz] = self.size;
+        (y0..y0 + sy).flat_map(move |y| (z0..z0 + sz).flat_map(move |z| (x0..x0 + sx).map(move |x| [x, y, z])))
+    }
+
+    //  Bricks belong to the chunk their lowest corner is in, even when they hang over into the next one
+    pub fn chunk(&self) -> ChunkCoord {
+        ChunkCoord::containing(self.position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BrickId(u64);
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub bricks: Vec<(BrickId, Brick)>,
+}
+
+//  Everything a worker needs to mesh one chunk without touching the world
+#[derive(Debug, Clone)]
+pub struct ChunkSnapshot {
+    pub coord: ChunkCoord,
+    pub bricks: Vec<Brick>,
+    //  Filled cells around the chunk's bricks, from this chunk and its neighbours
+    pub occupied: HashSet<[i32; 3]>,
+}
+
+#[derive(Debug, Default)]
+pub struct World {
+    chunks: HashMap<ChunkCoord, Chunk>,
+    //  Which brick fills each cell
+    cells: HashMap<[i32; 3], BrickId>,
+    brick_chunks: HashMap<BrickId, ChunkCoord>,
+    //  Chunks whose mesh no longer matches their bricks
+    dirty: HashSet<ChunkCoord>,
+    next_id: u64,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //  Fails if the brick overlaps another one. Bricks can't be wider than a chunk, so the only chunks that can see a
+    //  brick are its own and the ones next to it.
+    pub fn add_brick(&mut self, brick: Brick) -> anyhow::Result<BrickId> {
+        if brick.size.iter().any(|&s| s <= 0) {
+            bail!("Brick size {:?} has to be at least one cell in every direction", brick.size);
+        }
+        if brick.size[0] > CHUNK_SIZE || brick.size[2] > CHUNK_SIZE {
+            bail!("Brick size {:?} is wider than a chunk ({} studs)", brick.size, CHUNK_SIZE);
+        }
+        if let Some(cell) = brick.cells().find(|cell| self.cells.contains_key(cell)) {
+            bail!("Brick at {:?} overlaps another brick at {:?}", brick.position, cell);
+        }
+
+        let id = BrickId(self.next_id);
+        self.next_id += 1;
+        for cell in brick.cells() {
+            self.cells.insert(cell, id);
+        }
+        let coord = brick.chunk();
+        self.chunks.entry(coord).or_default().bricks.push((id, brick));
+        self.brick_chunks.insert(id, coord);
+        self.mark_dirty_around(&brick);
+        Ok(id)
+    }
+
+    pub fn remove_brick(&mut self, id: BrickId) -> Option<Brick> {
+        let coord = self.brick_chunks.remove(&id)?;
+        let chunk = self.chunks.get_mut(&coord)?;
+        let index = chunk.bricks.iter().position(|(b, _)| *b == id)?;
+        let (_, brick) = chunk.bricks.swap_remove(index);
+        //  Keep the empty chunk around so it's still dirty and its old mesh gets cleared
+        for cell in brick.cells() {
+            self.cells.remove(&cell);
+        }
+        self.mark_dirty_around(&brick);
+        Some(brick)
+    }
+
+    pub fn brick(&self, id: BrickId) -> Option<&Brick> {
+        let chunk = self.chunks.get(self.brick_chunks.get(&id)?)?;
+        chunk.bricks.iter().find(|(b, _)| *b == id).map(|(_, brick)| brick)
+    }
+
+    pub fn brick_at(&self, cell: [i32; 3]) -> Option<BrickId> {
+        self.cells.get(&cell).copied()
+    }
+
+    pub fn chunk(&self, coord: ChunkCoord) -> Option<&Chunk> {
+        self.chunks.get(&coord)
+    }
+
+    pub fn chunk_coords(&self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    //  Every brick, a chunk at a time in a fixed order
+    pub fn bricks(&self) -> impl Iterator<Item = (BrickId, &Brick)> + '_ {
+        let mut coords = self.chunks.keys().copied().collect::<Vec<_>>();
+        coords.sort_unstable();
+        coords.into_iter().flat_map(move |coord| self.chunks[&coord].bricks.iter().map(|(id, brick)| (*id, brick)))
+    }
+
+    //  The first brick along a ray in world units and how far along the ray it starts. Steps through the grid a cell at
+    //  a time, so the cost depends on `max_distance` rather than how many bricks there are.
+    pub fn raycast(&self, origin: [f32; 3], direction: [f32; 3], max_distance: f32) -> Option<(BrickId, f32)> {
+        let scale = [STUD_SIZE, PLATE_HEIGHT, STUD_SIZE];
+        let mut cell = [0, 1, 2].map(|a| (origin[a] / scale[a]).floor() as i32);
+        let step = direction.map(|d| if d > 0.0 { 1 } else { -1 });
+        //  How far along the ray the next boundary on each axis is, and how far apart boundaries are
+        let mut next = [0, 1, 2].map(|a| {
+            let boundary = (cell[a] + (step[a] > 0) as i32) as f32 * scale[a];
+            if direction[a] == 0.0 { f32::INFINITY } else { (boundary - origin[a]) / direction[a] }
+        });
+        let delta = [0, 1, 2].map(|a| if direction[a] == 0.0 { f32::INFINITY } else { scale[a] / direction[a].abs() });
+
+        let mut distance = 0.0;
+        while distance <= max_distance {
+            if let Some(id) = self.brick_at(cell) {
+                return Some((id, distance));
+            }
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b]))?;
+            distance = next[axis];
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+        None
+    }
+
+    pub fn brick_count(&self) -> usize {
+        self.brick_chunks.len()
+    }
+
+    //  Changing a brick can hide or uncover faces of anything touching it, which may be in a neighbouring chunk. Bricks
+    //  can hang up to a chunk over the edge of their own, so the chunk before the one it touches can be affected too.
+    fn mark_dirty_around(&mut self, brick: &Brick) {
+        let [x, _, z] = brick.position;
+        let [sx, _, sz] = brick.size;
+        let min = ChunkCoord::containing([x - CHUNK_SIZE, 0, z - CHUNK_SIZE]);
+        let max = ChunkCoord::containing([x + sx, 0, z + sz]);
+        for cz in min.z..=max.z {
+            for cx in min.x..=max.x {
+                let coord = ChunkCoord { x: cx, z: cz };
+                //  Chunks without bricks have no mesh that could change
+                if self.chunks.contains_key(&coord) {
+                    self.dirty.insert(coord);
+                }
+            }
+        }
+    }
+
+    pub fn is_dirty(&self, coord: ChunkCoord) -> bool {
+        self.dirty.contains(&coord)
+    }
+
+    //  The chunks that need remeshing since the last call, sorted so they're rebuilt in a predictable order
+    pub fn take_dirty(&mut self) -> Vec<ChunkCoord> {
+        let mut dirty = self.dirty.drain().collect::<Vec<_>>();
+        dirty.sort_unstable();
+        //  Chunks emptied by removals aren't needed once their (now empty) mesh has been handed out
+        for coord in &dirty {
+            if self.chunks.get(coord).is_some_and(|c| c.bricks.is_empty()) {
+                self.chunks.remove(coord);
+            }
+        }
+        dirty
+    }
+
+    pub fn snapshot(&self, coord: ChunkCoord) -> ChunkSnapshot {
+        let bricks = self.chunks.get(&coord).map(|c| c.bricks.iter().map(|(_, b)| *b).collect()).unwrap_or_default();
+        //  The chunk's bricks can reach almost to the end of the next chunk, everything around them is needed
+        let [x0, z0] = coord.origin();
+        let in_range = |cell: &[i32; 3]| {
+            (x0 - 1..x0 + 2 * CHUNK_SIZE).contains(&cell[0]) && (z0 - 1..z0 + 2 * CHUNK_SIZE).contains(&cell[2])
+        };
+        let occupied = coord
+            .neighbourhood()
+            .filter_map(|c| self.chunks.get(&c))
+            .flat_map(|c| c.bricks.iter())
+            .flat_map(|(_, b)| b.cells())
+            .filter(in_range)
+            .collect();
+        ChunkSnapshot { coord, bricks, occupied }
+    }
+}
+
+//  The demo world, a baseplate under the brick grid with a few bricks stacked on it
+pub fn demo_world() -> World {
+    let mut world = World::new();
+    let grey = [0.35, 0.38, 0.4, 1.0];
+    let base_y = -8;
+    for z in (-CHUNK_SIZE..CHUNK_SIZE).step_by(CHUNK_SIZE as usize) {
+        for x in (-CHUNK_SIZE..CHUNK_SIZE).step_by(CHUNK_SIZE as usize) {
+            world.add_brick(Brick::new([x, base_y, z], [CHUNK_SIZE, 1, CHUNK_SIZE], grey)).unwrap();
+        }
+    }
+
+    let colors = [[0.8, 0.1, 0.1, 1.0], [0.1, 0.3, 0.8, 1.0], [0.9, 0.7, 0.1, 1.0], [0.1, 0.6, 0.2, 1.0]];
+    //  A wall along the back in running bond, the offset rows keep the hidden faces from lining up
+    for row in 0..6 {
+        let offset = if row % 2 == 0 { 0 } else { 2 };
+        for i in 0..12 {
+            let x = -26 + offset + i * 4;
+            let brick = Brick::new([x, base_y + 1 + row * BRICK_HEIGHT, -30], [4, BRICK_HEIGHT, 2], colors[(i + row) as usize % colors.len()]);
+            world.add_brick(brick).unwrap();
+        }
+    }
+    world
+}
@@ -5,11 +5,11 @@ use winit::{
     window::{WindowBuilder, Window},
 };
 use cgmath::prelude::*;
-use engine::{model, model::{Vertex, Model, DrawModel}, texture};
-use game::{camera};
+use engine::{asset_manager, bounds, chunk_mesh, chunk_mesh::DrawChunk, culling, debug_draw, debug_view, debug_view::DebugView, font, gamepad, gpu_culling, gui, lod, lod::Lod, model, deferred, msaa, outline, post_process, settings, ssao, text, model::{Vertex, Model, DrawModel, Surfaces}, texture};
+use game::{camera, environment::Environment, input::{Action, InputMap}};
 
-mod engine;
-mod game;
+pub mod engine;
+pub mod game;
 
 struct State {
     surface: wgpu::Surface,
@@ -17,7 +17,35 @@ struct State {
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    render_pipeline: wgpu::RenderPipeline,
+    //  Rebuilt along with the MSAA target whenever the sample count changes
+    pipelines: ScenePipelines,
+    graphics_settings: settings::GraphicsSettings,
+    supported_sample_counts: Vec<u32>,
+    msaa_target: msaa::MsaaTarget,
+    post_process: post_process::PostProcess,
+    ssao: ssao::Ssao,
+    //  Only there on the deferred render path
+    deferred: Option<deferred::Deferred>,
+    outline: outline::Outline,
+    //  Cycled with F4
+    debug_view: DebugView,
+    debug_views: debug_view::DebugViews,
+    debug_draw: debug_draw::DebugDrawRenderer,
+    //  The model the debug views' meshes were last built from
+    debug_model: Option<(asset_manager::LoadState, bounds::Aabb)>,
+    text: text::TextRenderer,
+    //  Smoothed, for the FPS counter
+    frame_time: f32,
+    gui: gui::Gui,
+    gui_renderer: gui::GuiRenderer,
+    //  The build tools panel, toggled with F1
+    show_gui: bool,
+    brick_names: Vec<String>,
+    brick_images: Vec<gui::ImageId>,
+    selected_brick_image: Option<usize>,
+    export_path: String,
+    //  Instance colours were edited, so the GPU culler's copy is out of date
+    instances_changed: bool,
     //  TODO: add these to the "Game" struct
     camera: camera::Camera,
     projection: camera::Projection,
@@ -25,21 +53,60 @@ struct State {
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     camera_controller: camera::CameraController,
-    mouse_pressed: bool,
+    input_map: InputMap,
+    gamepads: gamepad::Gamepads,
+    //  The Quit action was pressed, run() exits after the event
+    quit_requested: bool,
+    //  The action picked in the controls panel
+    controls_selected: Option<usize>,
+    //  None while the cursor is outside the window
+    cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+    //  Right click selects the hovered brick, or clears the selection when there isn't one
+    selection: game::selection::Selection,
     instances: Vec<game::instance::Instance>,
     instance_buffer: wgpu::Buffer,
-    depth_texture: texture::Texture,
-    obj_model: Model,
+    //  Rebuilt whenever the model changes, e.g. when it finishes loading and replaces the placeholder
+    instance_bvh: culling::Bvh,
+    culled_model: Option<(asset_manager::LoadState, bounds::Aabb)>,
+    //  None when the adapter can't run compute shaders, gpu_culling picks between it and the BVH
+    gpu_culler: Option<gpu_culling::GpuCuller>,
+    gpu_culling: bool,
+    //  Scratch space for culling, kept around so it doesn't allocate every frame
+    visible_instances: Vec<usize>,
+    visible_instance_data: Vec<game::instance::InstanceRaw>,
+    //  The visible instances are packed grouped by LOD, this is where each level's run is
+    lod_ranges: [std::ops::Range<u32>; lod::LOD_COUNT],
+    //  Each instance's level last frame, for the hysteresis
+    instance_lods: Vec<Lod>,
+    //  The visible instances again, furthest first, for the model's transparent meshes. Drawn in runs that share a
+    //  level, and only filled in when the model has transparent meshes at all.
+    transparent_instance_buffer: wgpu::Buffer,
+    transparent_runs: Vec<(Lod, std::ops::Range<u32>)>,
+    lod_settings: lod::LodSettings,
+    //  Set to draw everything at one level, cycled with F8
+    lod_override: Option<Lod>,
+    depth_texture: texture::DepthTexture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    assets: asset_manager::AssetManager,
+    obj_model: asset_manager::Handle<Model>,
     light_uniform: game::uniform::LightUniform,
     light_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
-    light_render_pipeline: wgpu::RenderPipeline,
-    debug_material: model::Material,
+    //  Sky colour and fog, saved with exported builds. F12 cycles the fog mode.
+    environment: Environment,
+    fog_buffer: wgpu::Buffer,
+    //  What the chunks are drawn with
+    stud_material: asset_manager::Handle<model::Material>,
+    world: game::world::World,
+    chunk_mesher: chunk_mesh::ChunkMesher,
+    chunk_meshes: std::collections::HashMap<game::world::ChunkCoord, chunk_mesh::ChunkMesh>,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    light_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl State {
     //  Creating some wgpu types requires async code
-    async fn new(window: &Window) -> Self {
+    async fn new(window: &Window, mut graphics_settings: settings::GraphicsSettings) -> Self {
         let size = window.inner_size();
 
         //  The instance is a handle to the GPU
@@ -56,7 +123,8 @@ impl State {
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                //  Only for the wireframe debug view, which has a fallback without it
+                features: adapter.features() & wgpu::Features::POLYGON_MODE_LINE,
                 limits: if cfg!(target_arch = "wasm32") {
                     wgpu::Limits::downlevel_webgl2_defaults()
                 } else {
@@ -92,26 +160,54 @@ impl State {
             }
         );
 
+        let environment = Environment::default();
+        let fog_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Fog Buffer"),
+                contents: bytemuck::cast_slice(&[environment.fog_uniform()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        //  The fog shares the light's group, WebGL only allows 4 bind groups and the material shaders use them all
         let light_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
             label: None,
         });
 
         let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: fog_buffer.as_entire_binding(),
+                },
+            ],
             label: None,
         });
         
@@ -193,91 +289,93 @@ impl State {
                                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                                 count: None,
                             },
+                            wgpu::BindGroupLayoutEntry {    //  Binding 4 - material properties from the MTL file
+                                binding: 4,
+                                visibility: wgpu::ShaderStages::FRAGMENT,
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Uniform,
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                count: None,
+                            },
                         ],
                         label: Some("texture_bind_group_layout"),
                     });
 
-        let depth_texture = texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+        if graphics_settings.needs_single_sample() {
+            graphics_settings.msaa_samples = 1;
+        }
+        let supported_sample_counts =
+            msaa::supported_sample_counts(&adapter, config.format, texture::Texture::DEPTH_FORMAT);
+        let msaa_target = msaa::MsaaTarget::new(
+            &device,
+            &config,
+            msaa::closest_supported(&supported_sample_counts, graphics_settings.msaa_samples),
+        );
+        let depth_texture = msaa_target.create_depth_texture(&device, &config);
+        let gpu_culler = gpu_culling::GpuCuller::is_supported(&adapter, &device).then(|| {
+            let mut culler = gpu_culling::GpuCuller::new(&device, gpu_culling::GpuCuller::supports_hiz(&adapter));
+            culler.resize(&device, &depth_texture.depth_view, msaa_target.sample_count, config.width, config.height);
+            culler
+        });
+        let post_process =
+            post_process::PostProcess::new(&device, &config, &depth_texture.depth_view, msaa_target.sample_count);
         
         /*let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
         });*/
 
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &camera_bind_group_layout,
-                    &light_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/shader.wgsl").into()),
-            };
-            create_render_pipeline(
+        let ssao = ssao::Ssao::new(&device, &queue, &config, graphics_settings.ssao).unwrap();
+        let deferred = (graphics_settings.render_path == settings::RenderPath::Deferred).then(|| {
+            deferred::Deferred::new(
                 &device,
-                &render_pipeline_layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc(), game::instance::InstanceRaw::desc()],
-                shader,
+                &config,
+                &depth_texture.depth_view,
+                &[&texture_bind_group_layout, &camera_bind_group_layout, &light_bind_group_layout, ssao.ao_bind_group_layout()],
             )
-        };
+        });
+        let pipelines = ScenePipelines::new(
+            &device,
+            &[&texture_bind_group_layout, &camera_bind_group_layout, &light_bind_group_layout, ssao.ao_bind_group_layout()],
+            config.format,
+            msaa_target.sample_count,
+        );
+        let outline = outline::Outline::new(
+            &device,
+            &[&texture_bind_group_layout, &camera_bind_group_layout, &light_bind_group_layout],
+            config.format,
+            msaa_target.sample_count,
+        );
+        let debug_views = debug_view::DebugViews::new(
+            &device,
+            &[&texture_bind_group_layout, &camera_bind_group_layout, &light_bind_group_layout],
+            config.format,
+            msaa_target.sample_count,
+        );
+        let debug_draw = debug_draw::DebugDrawRenderer::new(&device, &camera_bind_group_layout, config.format, msaa_target.sample_count);
+        let font = font::Font::from_bytes(load_file::load_bytes!("../res/fonts/DejaVuSans.ttf").to_vec()).unwrap();
+        let text = text::TextRenderer::new(&device, &camera_bind_group_layout, config.format, msaa_target.sample_count, font);
+        let mut gui_renderer = gui::GuiRenderer::new(&device, &queue, config.format);
 
-        let light_render_pipeline = {
-            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Light Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Light Shader"),
-                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/light.wgsl").into()),
-            };
-            create_render_pipeline(
-                &device,
-                &layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc()],
-                shader,
-            )
-        };
-        
-        const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..game::instance::NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..game::instance::NUM_INSTANCES_PER_ROW).map(move |x| {
-                let x = SPACE_BETWEEN * (x as f32 - game::instance::NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                let z = SPACE_BETWEEN * (z as f32 - game::instance::NUM_INSTANCES_PER_ROW as f32 / 2.0);
-        
-                let position = cgmath::Vector3 {x, y: 0.0, z};
-        
-                let rotation = if position.is_zero() {
-                    cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-                } else {
-                    cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                };
-        
-                game::instance::Instance {
-                    position, rotation,
-                }
-            })
-        }).collect::<Vec<_>>();
+        let instances = game::instance::grid_world();
 
         let instance_data = instances.iter().map(game::instance::Instance::to_raw).collect::<Vec<_>>();
         let instance_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Instance Buffer"),
                 contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
+                //  Only the visible instances are copied in each frame, packed at the start
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             }
         );
+        let transparent_instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Transparent Instance Buffer"),
+            size: std::mem::size_of_val(instance_data.as_slice()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         /* 
         let mut res_dir = std::env::current_exe()   //  Get current path of the binary exe file
@@ -285,24 +383,24 @@ impl State {
         res_dir.pop();  //  Remove the binary from the path so we just have its folder location
         res_dir.push("res/cube.obj");    //  Append "res" so we can look up resources in the resource folder
         let obj_path = res_dir.to_string_lossy();*/
-        //  TODO: make this a constant 
 
-        let obj_model = engine::resources::load_model_obj(
-            "../res/cube.obj",
-            &device,
-            &queue,
-            &texture_bind_group_layout,
-        ).await.unwrap();
+        //  Models are decoded in the background, a placeholder cube is drawn until they're ready
+        let mut assets = asset_manager::AssetManager::new(&device, &queue, &texture_bind_group_layout);
+        let obj_model = assets.load_model(game::instance::WORLD_BRICK);
 
-        let debug_material = {
-            let diffuse_bytes = load_file::load_bytes!("../res/bricks/stud.png");
-            let normal_bytes = load_file::load_bytes!("../res/bricks/stud-normal.png");
+        //  Stand ins for brick thumbnails until there's something to render them with
+        let brick_images = [
+            (load_file::load_bytes!("../res/bricks/cube-diffuse.jpg"), "cube-diffuse.jpg"),
+            (load_file::load_bytes!("../res/bricks/stud.png"), "stud.png"),
+        ]
+        .into_iter()
+        .map(|(bytes, label)| {
+            let texture = texture::Texture::from_bytes(&device, &queue, bytes, label, false).unwrap();
+            gui_renderer.add_image(&device, &texture.view)
+        })
+        .collect();
 
-            let diffuse_texture = texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "../res/bricks/alt-diffuse.png", false).unwrap();
-            let normal_texture = texture::Texture::from_bytes(&device, &queue, normal_bytes, "../res/bricks/alt-normal.png", true).unwrap();
-
-            model::Material::new(&device, "alt-material", diffuse_texture, normal_texture, &texture_bind_group_layout)
-        };
+        let stud_material = assets.stud_cap_material(&device, &texture_bind_group_layout);
 
         Self {
             surface,
@@ -310,23 +408,70 @@ impl State {
             queue,
             config,
             size,
-            render_pipeline,
+            pipelines,
+            graphics_settings,
+            supported_sample_counts,
+            msaa_target,
+            post_process,
+            ssao,
+            deferred,
+            outline,
+            debug_view: DebugView::Off,
+            debug_views,
+            debug_draw,
+            debug_model: None,
+            text,
+            frame_time: 0.0,
+            gui: gui::Gui::default(),
+            gui_renderer,
+            show_gui: true,
+            brick_names: (0..instances.len()).map(|i| format!("Brick {}", i)).collect(),
+            brick_images,
+            selected_brick_image: None,
+            export_path: "build.glb".to_string(),
+            instances_changed: false,
             camera,
             projection,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
             camera_controller,
-            mouse_pressed: false,
+            input_map: load_input_map(),
+            gamepads: gamepad::Gamepads::open(),
+            quit_requested: false,
+            controls_selected: None,
+            cursor_position: None,
+            selection: game::selection::Selection::default(),
+            lod_ranges: [0..instances.len() as u32, 0..0, 0..0],
+            instance_lods: vec![Lod::Full; instances.len()],
+            transparent_instance_buffer,
+            transparent_runs: Vec::new(),
+            lod_settings: lod::LodSettings::default(),
+            lod_override: None,
             instances,
             instance_buffer,
+            instance_bvh: culling::Bvh::default(),
+            culled_model: None,
+            gpu_culling: gpu_culler.is_some(),
+            gpu_culler,
+            visible_instances: Vec::new(),
+            visible_instance_data: Vec::new(),
             depth_texture,
+            texture_bind_group_layout,
+            assets,
             obj_model,
             light_uniform,
             light_buffer,
             light_bind_group,
-            light_render_pipeline,
-            debug_material,
+            environment,
+            fog_buffer,
+            stud_material,
+            //  Every chunk starts out dirty, so the first few frames mesh the whole world
+            world: game::world::demo_world(),
+            chunk_mesher: chunk_mesh::ChunkMesher::new(),
+            chunk_meshes: std::collections::HashMap::new(),
+            camera_bind_group_layout,
+            light_bind_group_layout,
         }
     }
 
@@ -337,49 +482,764 @@ impl State {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.recreate_render_targets();
+        }
+    }
+
+    //  The MSAA and depth targets have to match the surface's size and the current sample count
+    fn recreate_render_targets(&mut self) {
+        self.msaa_target = msaa::MsaaTarget::new(&self.device, &self.config, self.msaa_target.sample_count);
+        self.depth_texture = self.msaa_target.create_depth_texture(&self.device, &self.config);
+        if let Some(culler) = &mut self.gpu_culler {
+            culler.resize(
+                &self.device,
+                &self.depth_texture.depth_view,
+                self.msaa_target.sample_count,
+                self.config.width,
+                self.config.height,
+            );
+        }
+        self.post_process.resize(&self.device, &self.config, &self.depth_texture.depth_view, self.msaa_target.sample_count);
+        self.ssao.resize(&self.device, &self.config);
+        if let Some(deferred) = &mut self.deferred {
+            deferred.resize(&self.device, &self.config, &self.depth_texture.depth_view);
+        }
+    }
+
+    //  Anything the adapter can't do is clamped to the closest thing it can
+    pub fn apply_graphics_settings(&mut self, mut settings: settings::GraphicsSettings) {
+        if settings.render_path != self.graphics_settings.render_path {
+            log::warn!("The render path can only be picked at startup, staying on {:?}", self.graphics_settings.render_path);
+            settings.render_path = self.graphics_settings.render_path;
+        }
+        if settings.needs_single_sample() && settings.msaa_samples > 1 {
+            log::info!("TAA and the deferred render path need a single sampled depth buffer, turning MSAA off");
+            settings.msaa_samples = 1;
+        }
+        if settings.anti_aliasing != self.graphics_settings.anti_aliasing {
+            self.post_process.reset_history();
+        }
+        let sample_count = msaa::closest_supported(&self.supported_sample_counts, settings.msaa_samples);
+        if sample_count != settings.msaa_samples {
+            log::warn!("{}x MSAA isn't supported, using {}x", settings.msaa_samples, sample_count);
+        }
+        settings.msaa_samples = sample_count;
+        settings.ssao.sample_count = settings.ssao.sample_count.clamp(1, ssao::MAX_SAMPLES);
+        self.ssao.set_settings(settings.ssao);
+        self.graphics_settings = settings;
+
+        if sample_count != self.msaa_target.sample_count {
+            self.pipelines = ScenePipelines::new(
+                &self.device,
+                &[
+                    &self.texture_bind_group_layout,
+                    &self.camera_bind_group_layout,
+                    &self.light_bind_group_layout,
+                    self.ssao.ao_bind_group_layout(),
+                ],
+                self.config.format,
+                sample_count,
+            );
+            let layouts = [&self.texture_bind_group_layout, &self.camera_bind_group_layout, &self.light_bind_group_layout];
+            self.outline.set_sample_count(&self.device, &layouts, self.config.format, sample_count);
+            self.debug_views.set_sample_count(&self.device, &layouts, self.config.format, sample_count);
+            self.debug_draw.set_sample_count(&self.device, &self.camera_bind_group_layout, self.config.format, sample_count);
+            self.text.set_sample_count(&self.device, &self.camera_bind_group_layout, self.config.format, sample_count);
+            self.msaa_target.sample_count = sample_count;
+            self.recreate_render_targets();
         }
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
+        //  The GUI gets first go, whatever it uses the camera and selection never see
+        if self.gui.handle_event(event) {
+            //  Nothing under the GUI is hovered
+            if let WindowEvent::CursorMoved { .. } = event {
+                self.cursor_position = None;
+            }
+            return true;
+        }
+        let mapped = self.input_map.handle_event(event);
+        self.perform_triggered();
+        if mapped {
+            return true;
+        }
         match event {
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        virtual_keycode: Some(key),
-                        state,
-                        ..
-                    },
-                ..
-            } => self.camera_controller.process_keyboard(*key, *state),
             WindowEvent::MouseWheel { delta, .. } => {
                 self.camera_controller.process_scroll(delta);
                 true
             }
-            WindowEvent::MouseInput {
-                button: MouseButton::Left,
-                state,
-                ..
-            } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+                true
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
                 true
             }
             _ => false,
         }
     }
 
+    //  Whatever the input map saw pressed, and any rebind it finished
+    fn perform_triggered(&mut self) {
+        for action in self.input_map.take_triggered() {
+            self.perform(action);
+        }
+        if let Some(rebound) = self.input_map.take_rebound() {
+            log::info!("Bound {} to {}", rebound.binding.name(), rebound.action.name());
+            for action in rebound.conflicts {
+                log::warn!("{} is also bound to {}", rebound.binding.name(), action.name());
+            }
+            self.save_input_map();
+        }
+    }
+
+    //  What the one-shot actions do, the held ones are read each frame in update()
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Select => match self.selection.hovered() {
+                Some(index) => self.selection.toggle(index),
+                None => self.selection.clear(),
+            },
+            Action::ToggleOrbit => match self.camera_controller.mode() {
+                camera::CameraMode::Orbit => self.camera_controller.fly(),
+                camera::CameraMode::Fly => {
+                    let focus = self.orbit_focus();
+                    self.camera_controller.orbit_around(&self.camera, focus);
+                }
+            },
+            //  Everything when nothing's selected
+            Action::FrameSelection => {
+                let bounds = match self.selection.selected().next() {
+                    Some(_) => self.instance_bounds(self.selection.selected()),
+                    None => self.instance_bounds(0..self.instances.len()),
+                };
+                self.camera_controller.frame(&self.camera, &bounds, self.projection.narrowest_fov());
+            }
+            Action::RotateBrickCw => self.rotate_selected(cgmath::Deg(-90.0)),
+            Action::RotateBrickCcw => self.rotate_selected(cgmath::Deg(90.0)),
+            Action::ToggleGui => {
+                self.show_gui = !self.show_gui;
+            }
+            Action::CycleDebugView => {
+                self.debug_view = self.debug_view.next();
+                log::info!("Debug view: {:?}", self.debug_view);
+                if self.debug_view == DebugView::Wireframe && self.debug_views.draws_meshes(DebugView::Wireframe) {
+                    log::warn!("No line polygon mode on this adapter, chunks are left out of the wireframe");
+                }
+            }
+            Action::Export => self.export(&self.export_path),
+            Action::ToggleGpuCulling => {
+                self.gpu_culling = !self.gpu_culling && self.gpu_culler.is_some();
+                if let Some(culler) = &mut self.gpu_culler {
+                    culler.reset_hiz();
+                }
+                log::info!("{} culling", if self.gpu_culling { "GPU" } else { "CPU" });
+            }
+            Action::ToggleHiZ => {
+                if let Some(culler) = &mut self.gpu_culler {
+                    culler.use_hiz = !culler.use_hiz && culler.has_hiz();
+                    log::info!("Hi-Z occlusion culling {}", if culler.use_hiz { "on" } else { "off" });
+                }
+            }
+            Action::CycleLod => {
+                self.lod_override = match self.lod_override {
+                    None => Some(Lod::Full),
+                    Some(Lod::Full) => Some(Lod::FlatTop),
+                    Some(Lod::FlatTop) => Some(Lod::Impostor),
+                    Some(Lod::Impostor) => None,
+                };
+                match self.lod_override {
+                    Some(lod) => log::info!("Drawing everything at {:?}", lod),
+                    None => log::info!("Picking LODs by screen size"),
+                }
+            }
+            Action::ToggleMsaa => {
+                let mut settings = self.graphics_settings;
+                settings.msaa_samples = msaa::next_sample_count(&self.supported_sample_counts, settings.msaa_samples);
+                self.apply_graphics_settings(settings);
+                log::info!("{}x MSAA (offered: {:?})", self.msaa_target.sample_count, self.supported_sample_counts);
+            }
+            Action::CycleAntiAliasing => {
+                let mut settings = self.graphics_settings;
+                settings.anti_aliasing = settings.anti_aliasing.next();
+                self.apply_graphics_settings(settings);
+                log::info!("Anti-aliasing: {:?}", self.graphics_settings.anti_aliasing);
+            }
+            Action::ToggleSsao => {
+                let mut settings = self.graphics_settings;
+                settings.ssao.enabled = !settings.ssao.enabled;
+                self.apply_graphics_settings(settings);
+                log::info!("SSAO {}", if settings.ssao.enabled { "on" } else { "off" });
+            }
+            Action::CycleFog => {
+                let mut environment = self.environment;
+                environment.fog.mode = environment.fog.mode.next();
+                self.set_environment(environment);
+                log::info!("Fog: {}", environment.fog.mode.name());
+            }
+            Action::Quit => self.quit_requested = true,
+            Action::MoveForward
+            | Action::MoveBackward
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::LookLeft
+            | Action::LookRight
+            | Action::LookUp
+            | Action::LookDown
+            | Action::DragLook
+            | Action::Pan => {}
+        }
+    }
+
+    //  The selected bricks, or the surface under the cursor, or failing those wherever the last orbit was from here
+    fn orbit_focus(&self) -> cgmath::Point3<f32> {
+        if self.selection.selected().next().is_some() {
+            return self.instance_bounds(self.selection.selected()).center();
+        }
+        if let Some((_, point)) = self.cursor_position.and_then(|cursor| self.pick(cursor)) {
+            return point;
+        }
+        self.camera.position + self.camera.forward() * self.camera_controller.orbit().distance
+    }
+
+    fn instance_bounds(&self, indices: impl Iterator<Item = usize>) -> bounds::Aabb {
+        let aabb = self.assets.model(&self.obj_model).aabb;
+        indices.fold(bounds::Aabb::empty(), |bounds, i| {
+            bounds.union(&aabb.transformed(&self.instances[i].model_matrix()))
+        })
+    }
+
+    //  A quarter turn about the vertical, anticlockwise seen from above for positive angles
+    fn rotate_selected(&mut self, angle: cgmath::Deg<f32>) {
+        let rotation = cgmath::Quaternion::from_angle_y(angle);
+        for i in self.selection.selected() {
+            self.instances[i].rotation = rotation * self.instances[i].rotation;
+        }
+        //  Their bounds moved, so the BVH and the GPU culler's copy both need rebuilding
+        self.culled_model = None;
+    }
+
+    fn save_input_map(&self) {
+        let Some(path) = input_config_path() else {
+            return;
+        };
+        if let Err(e) = self.input_map.save(&path) {
+            log::error!("Failed to save input bindings: {:#}", e);
+        }
+    }
+
+    //  Just the selected bricks if there are any, otherwise the whole world
+    fn export(&self, path: &str) {
+        let selected = self.selection.selected().collect::<Vec<_>>();
+        let selection = (!selected.is_empty()).then_some(selected.as_slice());
+        match self.export_build(path, selection) {
+            Ok(()) if selection.is_some() => log::info!("Exported {} selected bricks to {}", selected.len(), path),
+            Ok(()) => log::info!("Exported build to {}", path),
+            Err(e) => log::error!("Failed to export build: {:#}", e),
+        }
+    }
+
+    //  `selection` is indices into instances, None exports the whole world
+    fn export_build(&self, path: &str, selection: Option<&[usize]>) -> anyhow::Result<()> {
+        let model_path = self.assets.models.path(&self.obj_model).unwrap_or(game::instance::WORLD_BRICK);
+        //  Use the copy the model kept on the CPU if it has one, otherwise the file has to be read again
+        let meshes = match self.assets.model(&self.obj_model).mesh_data() {
+            Some(meshes) => meshes,
+            None => pollster::block_on(engine::resources::load_model_obj_data(model_path))?.meshes,
+        };
+        let selected = |i: &usize| selection.is_none_or(|s| s.contains(i));
+        let instances = self.instances.iter()
+            .enumerate()
+            .filter(|(i, _)| selected(i))
+            .map(|(_, instance)| instance)
+            .collect::<Vec<_>>();
+
+        let options = ExportOptions {
+            gltf: engine::gltf_export::GltfExportOptions {
+                environment: Some(self.environment),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        //  World bricks can't be selected, so they only go out with the whole world
+        let world_bricks = match selection {
+            Some(_) => Vec::new(),
+            None => self.world.bricks().map(|(_, brick)| *brick).collect(),
+        };
+        export_world(path, model_path, &meshes, &instances, &world_bricks, &options)
+    }
+
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+        self.queue.write_buffer(&self.fog_buffer, 0, bytemuck::cast_slice(&[environment.fog_uniform()]));
+    }
+
     fn update(&mut self, dt: instant::Duration) {
+        self.assets.update(&self.device, &self.queue, &self.texture_bind_group_layout);
+
+        //  The pad isn't a window event, so it's caught up on here
+        for event in self.gamepads.poll() {
+            self.input_map.handle_gamepad_event(&event);
+        }
+        self.perform_triggered();
+
         //  update code to move objects
+        self.camera_controller.process_actions(&self.input_map);
         self.camera_controller.update_camera(&mut self.camera, dt);
+        let jitter = match self.graphics_settings.anti_aliasing {
+            settings::AntiAliasing::Taa => self.post_process.next_jitter(self.config.width, self.config.height),
+            _ => cgmath::Vector2::zero(),
+        };
+        self.projection.set_jitter(jitter);
         self.camera_uniform.update_view_proj(&self.camera, &self.projection);
         self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
 
+        //  Not while dragging the camera around, the highlight would flicker across everything it passes
+        if !self.input_map.is_down(Action::DragLook) && !self.input_map.is_down(Action::Pan) {
+            let hovered = self.cursor_position.and_then(|cursor| self.pick_instance(cursor));
+            self.selection.set_hovered(hovered);
+        }
+        self.cull_instances();
+        self.update_chunks();
+        if self.debug_views.draws_meshes(self.debug_view) {
+            let model = self.assets.model(&self.obj_model);
+            let key = (self.assets.models.state(&self.obj_model), model.aabb);
+            if self.debug_model != Some(key) {
+                self.debug_views.set_model(&self.device, model);
+                self.debug_model = Some(key);
+            }
+        }
+
         //  Update the light
         let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
         self.light_uniform.position = 
             (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0,).into(), cgmath::Deg(60.0 * dt.as_secs_f32()))
                 * old_position).into();
         self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
+        let [r, g, b] = self.light_uniform.color;
+        debug_draw::global().lock().unwrap().sphere(self.light_uniform.position.into(), 0.25, [r, g, b, 1.0]);
+
+        self.frame_time = match self.frame_time {
+            t if t > 0.0 => t * 0.95 + dt.as_secs_f32() * 0.05,
+            _ => dt.as_secs_f32(),
+        };
+        if self.frame_time > 0.0 {
+            self.text.queue_screen(&format!("{:.0} fps", 1.0 / self.frame_time), [8.0, 8.0], text::TextStyle::default());
+        }
+        //  A name tag over the hovered brick
+        if let Some(i) = self.selection.hovered() {
+            let aabb = self.assets.model(&self.obj_model).aabb.transformed(&self.instances[i].model_matrix());
+            let anchor = cgmath::Point3::new(aabb.center().x, aabb.max.y + 0.1, aabb.center().z);
+            let style = text::TextStyle { size: 32.0, ..Default::default() };
+            self.text.queue_world(&self.brick_names[i], anchor, 0.3, style);
+        }
+        self.build_gui();
+    }
+
+    //  The build tools down the left side of the window
+    fn build_gui(&mut self) {
+        const PALETTE: [[f32; 4]; 8] = [
+            [1.0, 1.0, 1.0, 1.0],
+            [0.1, 0.1, 0.1, 1.0],
+            [0.8, 0.05, 0.05, 1.0],
+            [1.0, 0.5, 0.0, 1.0],
+            [1.0, 0.85, 0.0, 1.0],
+            [0.05, 0.5, 0.1, 1.0],
+            [0.05, 0.2, 0.8, 1.0],
+            [0.4, 0.1, 0.6, 1.0],
+        ];
+        let mut ui = self.gui.begin(self.text.font(0));
+        if !self.show_gui {
+            return;
+        }
+        let line = ui.line_height();
+        let (x, width) = (8.0, 208.0);
+        let mut y = 32.0;
+        ui.panel(gui::Rect::new(0.0, y - 8.0, width + 16.0, self.config.height as f32 - y + 8.0));
+
+        ui.label([x, y], "Color");
+        y += line;
+        let mut picked_color = None;
+        let current_color = self.selection.selected().next().map(|i| self.instances[i].color);
+        for (i, color) in PALETTE.into_iter().enumerate() {
+            let rect = gui::Rect::new(x + i as f32 * 26.0, y, 24.0, 24.0);
+            if ui.swatch(&format!("palette {}", i), rect, color, current_color == Some(color)) {
+                picked_color = Some(color);
+            }
+        }
+        y += 32.0;
+
+        ui.label([x, y], "Fog density");
+        y += line;
+        let mut fog_density = self.environment.fog.density;
+        let fog_changed = ui.slider("fog density", gui::Rect::new(x, y, width, 20.0), &mut fog_density, 0.0..=0.2);
+        y += 28.0;
+
+        ui.label([x, y], "Bricks");
+        y += line;
+        let mut listed = self.selection.selected().next();
+        let list_rect = gui::Rect::new(x, y, width, 8.0 * (line + ui.style().padding));
+        let list_changed = ui.scroll_list("bricks", list_rect, &self.brick_names, &mut listed);
+        y += list_rect.height + 8.0;
+
+        ui.label([x, y], "Thumbnails");
+        y += line;
+        let grid_rect = gui::Rect::new(x, y, width, 104.0);
+        if ui.image_grid("thumbnails", grid_rect, &self.brick_images, 52.0, &mut self.selected_brick_image) {
+            log::info!("Picked brick {:?}", self.selected_brick_image);
+        }
+        y += grid_rect.height + 8.0;
+
+        ui.label([x, y], "Export to");
+        y += line;
+        let mut export = ui.text_input("export path", gui::Rect::new(x, y, width, 24.0), &mut self.export_path);
+        y += 28.0;
+        export |= ui.button("export", gui::Rect::new(x, y, width, 24.0), "Export");
+
+        //  Controls down the right side, pick an action to see and change what it's bound to
+        let x = self.config.width as f32 - width - 8.0;
+        let mut y = 32.0;
+        ui.panel(gui::Rect::new(x - 8.0, y - 8.0, width + 16.0, self.config.height as f32 - y + 8.0));
+        ui.label([x, y], "Controls");
+        y += line;
+        let actions = Action::ALL.iter().map(|action| action.name().to_string()).collect::<Vec<_>>();
+        let controls_rect = gui::Rect::new(x, y, width, 10.0 * (line + ui.style().padding));
+        ui.scroll_list("controls", controls_rect, &actions, &mut self.controls_selected);
+        y += controls_rect.height + 8.0;
+        let controlled = self.controls_selected.map(|i| Action::ALL[i]);
+        if let Some(action) = controlled {
+            for &binding in self.input_map.bindings(action) {
+                let shared = self.input_map.actions_bound_to(binding)
+                    .into_iter()
+                    .filter(|&a| a != action)
+                    .map(|a| a.name())
+                    .collect::<Vec<_>>();
+                if shared.is_empty() {
+                    ui.label([x, y], &binding.name());
+                } else {
+                    ui.label([x, y], &format!("{} (also {})", binding.name(), shared.join(", ")));
+                }
+                y += line;
+            }
+            y += 8.0;
+        }
+        let rebind_text = if self.input_map.rebinding().is_some() { "Press an input..." } else { "Add binding" };
+        let rebind = ui.button("rebind", gui::Rect::new(x, y, width, 24.0), rebind_text);
+        y += 28.0;
+        let clear_bindings = ui.button("clear bindings", gui::Rect::new(x, y, width, 24.0), "Clear bindings");
+
+        if let Some(color) = picked_color {
+            for i in self.selection.selected() {
+                self.instances[i].color = color;
+            }
+            self.instances_changed = true;
+        }
+        if fog_changed {
+            let mut environment = self.environment;
+            environment.fog.density = fog_density;
+            self.set_environment(environment);
+        }
+        if let (true, Some(index)) = (list_changed, listed) {
+            self.selection.clear();
+            self.selection.toggle(index);
+        }
+        match (controlled, self.input_map.rebinding()) {
+            (_, Some(_)) if rebind => self.input_map.cancel_rebind(),
+            (Some(action), None) if rebind => self.input_map.start_rebind(action, None),
+            _ => {}
+        }
+        if let (true, Some(action)) = (clear_bindings, controlled) {
+            self.input_map.set_bindings(action, Vec::new());
+            self.save_input_map();
+        }
+        if export {
+            self.export(&self.export_path);
+        }
+        for text in &self.gui.draw_list().texts {
+            self.text.queue_screen(&text.text, text.position, text.style);
+        }
+    }
+
+    //  Packs the instances inside the camera frustum into the start of the instance buffer. With GPU culling on this
+    //  only has to keep the culler's copy of the instances up to date, the rest happens in render().
+    fn cull_instances(&mut self) {
+        let model = self.assets.model(&self.obj_model);
+        let key = (self.assets.models.state(&self.obj_model), model.aabb);
+        let model_changed = self.culled_model != Some(key);
+        let selection_changed = self.selection.take_changed();
+        let instances_changed = std::mem::take(&mut self.instances_changed);
+        //  Turning a brick can change its bounds
+        if model_changed || instances_changed {
+            let bounds = self.instances.iter().map(|i| model.aabb.transformed(&i.model_matrix())).collect::<Vec<_>>();
+            self.instance_bvh = culling::Bvh::build(&bounds);
+            self.culled_model = Some(key);
+        }
+        //  The culler keeps its own copy of the instances, selection flags and all
+        if model_changed || selection_changed || instances_changed {
+            if let Some(culler) = &mut self.gpu_culler {
+                let instances = self.instances.iter()
+                    .enumerate()
+                    .map(|(i, instance)| (0, instance.to_raw().with_flags(self.selection.flags(i))))
+                    .collect::<Vec<_>>();
+                culler.set_instances(&self.device, &[model], &instances);
+            }
+        }
+        //  The transparent meshes need sorting on the CPU whichever way the rest are culled
+        let has_transparent = std::iter::once(model)
+            .chain(model.lods.iter().map(|(_, lod)| lod))
+            .flat_map(|m| &m.meshes)
+            .any(|mesh| self.assets.material(&mesh.material).is_transparent());
+        self.transparent_runs.clear();
+        if self.gpu_culling_active() && !has_transparent {
+            return;
+        }
+
+        let frustum = culling::Frustum::from_view_proj(&self.camera_uniform.view_proj());
+        self.visible_instances.clear();
+        self.instance_bvh.query(&frustum, &mut self.visible_instances);
+
+        //  Only visible instances get a new level, the rest keep theirs until they come back into view
+        let lod_settings = self.lod_settings();
+        let pixels_per_unit = self.projection.pixels_per_unit(self.config.height);
+        for &i in &self.visible_instances {
+            let sphere = model.bounding_sphere.transformed(&self.instances[i].model_matrix());
+            let pixels = lod::projected_size(&sphere, self.camera.position, pixels_per_unit);
+            self.instance_lods[i] = lod_settings.select(self.instance_lods[i], pixels);
+        }
+        if has_transparent {
+            self.pack_transparent_instances();
+        }
+        if self.gpu_culling_active() {
+            return;
+        }
+        //  Grouped by level for drawing, and in a stable order within each so the draw order doesn't change every frame
+        let instance_lods = &self.instance_lods;
+        self.visible_instances.sort_unstable_by_key(|&i| (instance_lods[i], i));
+
+        self.visible_instance_data.clear();
+        let selection = &self.selection;
+        self.visible_instance_data.extend(
+            self.visible_instances.iter().map(|&i| self.instances[i].to_raw().with_flags(selection.flags(i))),
+        );
+        if !self.visible_instance_data.is_empty() {
+            self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.visible_instance_data));
+        }
+        let mut start = 0;
+        for lod in Lod::ALL {
+            let count = self.visible_instances[start as usize..].iter().take_while(|&&i| instance_lods[i] == lod).count();
+            self.lod_ranges[lod.index()] = start..start + count as u32;
+            start += count as u32;
+        }
+    }
+
+    //  Furthest first, so each transparent mesh is blended over the ones behind it. A run only breaks where the level
+    //  changes, and within a run each mesh is drawn for every instance before the next mesh.
+    fn pack_transparent_instances(&mut self) {
+        let eye = self.camera.position.to_vec();
+        let instances = &self.instances;
+        let distance = |i: usize| (instances[i].position - eye).magnitude2();
+        self.visible_instances.sort_unstable_by(|&a, &b| distance(b).total_cmp(&distance(a)));
+
+        self.visible_instance_data.clear();
+        let selection = &self.selection;
+        self.visible_instance_data.extend(
+            self.visible_instances.iter().map(|&i| self.instances[i].to_raw().with_flags(selection.flags(i))),
+        );
+        if !self.visible_instance_data.is_empty() {
+            self.queue.write_buffer(&self.transparent_instance_buffer, 0, bytemuck::cast_slice(&self.visible_instance_data));
+        }
+        for (index, &i) in self.visible_instances.iter().enumerate() {
+            let lod = self.instance_lods[i];
+            let index = index as u32;
+            match self.transparent_runs.last_mut() {
+                Some((run_lod, run)) if *run_lod == lod => run.end = index + 1,
+                _ => self.transparent_runs.push((lod, index..index + 1)),
+            }
+        }
+    }
+
+    //  The debug views that draw their own meshes need the instances packed by the CPU
+    fn gpu_culling_active(&self) -> bool {
+        self.gpu_culling && !self.debug_views.draws_meshes(self.debug_view)
+    }
+
+    //  World bricks in front of an instance hide it, but can't be hovered or selected themselves
+    fn pick_instance(&self, cursor: winit::dpi::PhysicalPosition<f64>) -> Option<usize> {
+        match self.pick(cursor) {
+            Some((Picked::Instance(i), _)) => Some(i),
+            _ => None,
+        }
+    }
+
+    //  The nearest instance or world brick under a point on the window and where the ray hits it. Instances the BVH
+    //  finds along the ray are tested against the model's box in their own space, the world is stepped through a cell
+    //  at a time.
+    fn pick(&self, cursor: winit::dpi::PhysicalPosition<f64>) -> Option<(Picked, cgmath::Point3<f32>)> {
+        let view_proj = self.projection.calc_unjittered_matrix() * self.camera.calc_matrix();
+        let ndc = cgmath::Vector2::new(
+            (2.0 * cursor.x / self.config.width as f64 - 1.0) as f32,
+            (1.0 - 2.0 * cursor.y / self.config.height as f64) as f32,
+        );
+        let ray = bounds::Ray::from_ndc(&view_proj.invert()?, ndc);
+        let aabb = self.assets.model(&self.obj_model).aabb;
+        let mut candidates = Vec::new();
+        self.instance_bvh.query_ray(&ray, &mut candidates);
+        let instance = candidates.into_iter()
+            .filter_map(|i| {
+                let local_ray = ray.transformed(&self.instances[i].model_matrix().invert()?);
+                Some((Picked::Instance(i), aabb.intersect_ray(&local_ray)?))
+            })
+            //  Instances don't scale, so distances along the local rays are distances along this one
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        //  Only as far as the nearest instance, anything further is behind it
+        let max_distance = instance.map_or(PICK_DISTANCE, |(_, t)| t);
+        let brick = self.world.raycast(ray.origin.into(), ray.direction.into(), max_distance)
+            .map(|(id, t)| (Picked::Brick(id), t));
+        brick.or(instance).map(|(picked, t)| (picked, ray.origin + ray.direction * t))
+    }
+
+    //  The LOD boundaries to use this frame, taking the F8 override into account
+    fn lod_settings(&self) -> lod::LodSettings {
+        self.lod_override.map_or(self.lod_settings, lod::LodSettings::forced)
+    }
+
+    //  Sends chunks that changed off to be remeshed and uploads whichever meshes have come back
+    fn update_chunks(&mut self) {
+        for coord in self.world.take_dirty() {
+            self.chunk_mesher.submit(self.world.snapshot(coord));
+        }
+        for lods in self.chunk_mesher.poll() {
+            if lods.is_empty() {
+                self.chunk_meshes.remove(&lods.coord);
+            } else {
+                let mut mesh = chunk_mesh::ChunkMesh::upload(&self.device, &lods);
+                //  Keep the old mesh's level so a remesh doesn't make the chunk pop
+                if let Some(old) = self.chunk_meshes.get(&lods.coord) {
+                    mesh.lod = old.lod;
+                }
+                self.chunk_meshes.insert(lods.coord, mesh);
+            }
+        }
+
+        let lod_settings = self.lod_settings();
+        let pixels_per_unit = self.projection.pixels_per_unit(self.config.height);
+        for chunk in self.chunk_meshes.values_mut() {
+            let sphere = bounds::BoundingSphere::from_aabb(&chunk.aabb);
+            let pixels = lod::projected_size(&sphere, self.camera.position, pixels_per_unit);
+            chunk.lod = lod_settings.select(chunk.lod, pixels);
+        }
+    }
+
+    //  Every brick instance and chunk, drawn with `[model_pipeline, chunk_pipeline]`. The scene pipelines read the AO
+    //  from group 3, the SSAO prepass ones come before there is any. Chunks are always opaque.
+    fn draw_bricks<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        [model_pipeline, chunk_pipeline]: [&'a wgpu::RenderPipeline; 2],
+        ao_bind_group: Option<&'a wgpu::BindGroup>,
+        view_proj: &cgmath::Matrix4<f32>,
+        surfaces: Surfaces,
+    ) {
+        self.draw_instances(render_pass, model_pipeline, ao_bind_group, surfaces);
+
+        render_pass.set_pipeline(chunk_pipeline);
+        if let Some(ao_bind_group) = ao_bind_group {
+            render_pass.set_bind_group(3, ao_bind_group, &[]);
+        }
+        let frustum = culling::Frustum::from_view_proj(view_proj);
+        for chunk in self.chunk_meshes.values() {
+            if frustum.test_aabb(&chunk.aabb) != culling::Containment::Outside {
+                render_pass.draw_chunk(
+                    chunk,
+                    chunk.lod,
+                    self.assets.material(&self.stud_material),
+                    &self.camera_bind_group,
+                    &self.light_bind_group,
+                );
+            }
+        }
+    }
+
+    //  The visible brick instances at their LODs, with `bind_group_3` for whatever else the pipeline needs
+    fn draw_instances<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        bind_group_3: Option<&'a wgpu::BindGroup>,
+        surfaces: Surfaces,
+    ) {
+        let gpu_culler = self.gpu_culler.as_ref().filter(|_| self.gpu_culling_active());
+        let obj_model = self.assets.model(&self.obj_model);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_pipeline(pipeline);
+        if let Some(bind_group) = bind_group_3 {
+            render_pass.set_bind_group(3, bind_group, &[]);
+        }
+        for lod in Lod::ALL {
+            let indirect = gpu_culler.and_then(|c| {
+                Some((c.instance_slice(0, lod)?, c.indirect_buffer()?, c.indirect_offset(0, lod)?))
+            });
+            match indirect {
+                Some((instances, indirect_buffer, indirect_offset)) => {
+                    render_pass.set_vertex_buffer(1, instances);
+                    render_pass.draw_model_indirect(
+                        obj_model.lod(lod),
+                        &self.assets.materials,
+                        surfaces,
+                        indirect_buffer,
+                        indirect_offset,
+                        &self.camera_bind_group,
+                        &self.light_bind_group,
+                    );
+                }
+                None if gpu_culler.is_none() && !self.lod_ranges[lod.index()].is_empty() => {
+                    render_pass.draw_model_instanced(
+                        obj_model.lod(lod),
+                        &self.assets.materials,
+                        surfaces,
+                        self.lod_ranges[lod.index()].clone(),
+                        &self.camera_bind_group,
+                        &self.light_bind_group
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+
+    //  Outlines around the selected and hovered instances, over everything else in the pass
+    fn draw_outlines<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.selection.is_empty() {
+            return;
+        }
+        render_pass.set_stencil_reference(outline::STENCIL_REFERENCE);
+        self.draw_instances(render_pass, &self.outline.mask, Some(self.outline.bind_group()), Surfaces::All);
+        self.draw_instances(render_pass, &self.outline.outline, Some(self.outline.bind_group()), Surfaces::All);
+    }
+
+    //  The transparent meshes of the visible instances, blended over everything opaque without writing depth
+    fn draw_transparent<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, ao_bind_group: &'a wgpu::BindGroup) {
+        if self.transparent_runs.is_empty() {
+            return;
+        }
+        let obj_model = self.assets.model(&self.obj_model);
+        render_pass.set_pipeline(&self.pipelines.transparent);
+        render_pass.set_bind_group(3, ao_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, self.transparent_instance_buffer.slice(..));
+        for (lod, instances) in &self.transparent_runs {
+            render_pass.draw_model_instanced(
+                obj_model.lod(*lod),
+                &self.assets.materials,
+                Surfaces::Transparent,
+                instances.clone(),
+                &self.camera_bind_group,
+                &self.light_bind_group,
+            );
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -389,54 +1249,154 @@ impl State {
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
+        let view_proj = self.camera_uniform.view_proj();
+        let gpu_culler = self.gpu_culler.as_ref().filter(|_| self.gpu_culling_active());
+        if let Some(culler) = gpu_culler {
+            let pixels_per_unit = self.projection.pixels_per_unit(self.config.height);
+            culler.cull(&mut encoder, &self.queue, &view_proj, self.camera.position, pixels_per_unit, &self.lod_settings());
+        }
+        //  With screen space AA on the scene goes to an offscreen target first, and the AA pass writes the surface
+        let anti_aliasing = self.graphics_settings.anti_aliasing;
+        let scene_view = match anti_aliasing {
+            settings::AntiAliasing::Off => &view,
+            _ => self.post_process.scene_view(),
+        };
+        if self.ssao.enabled() {
+            {
+                let mut prepass = self.ssao.begin_prepass(&mut encoder);
+                self.draw_bricks(
+                    &mut prepass,
+                    [&self.pipelines.render_normals, &self.pipelines.chunk_normals],
+                    None,
+                    &view_proj,
+                    Surfaces::Opaque,
+                );
+            }
+            self.ssao.run(&mut encoder, &self.queue, &view_proj, self.camera.position);
+        }
+        let (color_view, resolve_target) = self.msaa_target.color_attachment(scene_view);
+        //  clear the screen before each frame
+        //  if the screen is completely covered by objects, then it is unneccessary to clear (see Blockland)
+        //  causes bugs if you do stuff like deleting the Skybox
+        let clear_color = self.environment.clear_color();
+        if !self.selection.is_empty() {
+            self.outline.update(&self.queue, self.config.width, self.config.height);
+        }
+        //  The deferred path lights the opaque bricks first, and the forward pass below adds the transparent ones on top.
+        //  Debug views that replace the shading draw everything in the forward pass instead.
+        let deferred = self.deferred.as_ref().filter(|_| !self.debug_view.replaces_shading());
+        if let Some(deferred) = deferred {
+            {
+                let mut geometry_pass = deferred.begin_geometry_pass(&mut encoder, &self.depth_texture.view);
+                self.draw_bricks(
+                    &mut geometry_pass,
+                    [&deferred.geometry_model, &deferred.geometry_chunk],
+                    None,
+                    &view_proj,
+                    Surfaces::Opaque,
+                );
+            }
+            let lights = [deferred::PointLight::new(self.light_uniform.position, self.light_uniform.color, 0.0)];
+            deferred.light(
+                &mut encoder,
+                &self.queue,
+                color_view,
+                clear_color,
+                self.ssao.ao_bind_group(),
+                &self.light_bind_group,
+                &view_proj,
+                self.camera.position,
+                &lights,
+            );
+        }
+        let (color_load, depth_load) = match deferred {
+            Some(_) => (wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+            None => (wgpu::LoadOp::Clear(clear_color), wgpu::LoadOp::Clear(1.0)),
+        };
+        let brick_pipelines = self.debug_views
+            .scene_pipelines(self.debug_view)
+            .unwrap_or([&self.pipelines.render, &self.pipelines.chunk]);
+        //  Whatever was queued this frame is drawn once and then dropped
+        {
+            let mut lines = debug_draw::global().lock().unwrap();
+            self.debug_draw.upload(&self.device, &self.queue, &lines);
+            lines.clear();
+        }
+        self.text.prepare(&self.device, &self.queue, &self.camera.calc_matrix(), self.config.width, self.config.height);
+        self.gui_renderer.prepare(&self.device, &self.queue, self.gui.draw_list(), self.config.width, self.config.height);
         //  the code blow is enclosed in a {} block so we drop the mutable borrow of encoder and can be used after
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: color_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        //  clear the screen before each frame
-                        //  if the screen is completely covered by objects, then it is unneccessary to clear (see Blockland)
-                        //  causes bugs if you do stuff like deleting the Skybox
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: color_load,
                         store: true,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: depth_load,
+                        store: true,
+                    }),
+                    //  Only used for the outlines, so it starts empty even when the depth is kept
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
                         store: true,
                     }),
-                    stencil_ops: None,
                 }),
             });
 
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            use crate::model::DrawLight;
-            render_pass.set_pipeline(&self.light_render_pipeline);
-            render_pass.draw_light_model(
-                &self.obj_model,
-                &self.camera_bind_group,
-                &self.light_bind_group,
-            );
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.draw_model_instanced_with_material(
-                &self.obj_model, 
-                &self.debug_material,
-                0..self.instances.len() as u32, 
-                &self.camera_bind_group, 
-                &self.light_bind_group
-            );
-
+            //  The wireframe fallback draws the bricks from its own meshes, without the chunks. Debug views that replace
+            //  the shading draw the transparent meshes like any other.
+            let draws_meshes = self.debug_views.draws_meshes(self.debug_view);
+            let replaces_shading = self.debug_view.replaces_shading();
+            if deferred.is_none() && !(draws_meshes && replaces_shading) {
+                let surfaces = if replaces_shading { Surfaces::All } else { Surfaces::Opaque };
+                self.draw_bricks(&mut render_pass, brick_pipelines, Some(self.ssao.ao_bind_group()), &view_proj, surfaces);
+            }
+            if !replaces_shading {
+                self.draw_transparent(&mut render_pass, self.ssao.ao_bind_group());
+            }
+            if draws_meshes {
+                self.debug_views.draw_meshes(
+                    &mut render_pass,
+                    self.debug_view,
+                    &self.camera_bind_group,
+                    &self.instance_buffer,
+                    &self.lod_ranges,
+                );
+            }
+            self.debug_draw.draw(&mut render_pass, &self.camera_bind_group);
+            self.text.draw_world(&mut render_pass, &self.camera_bind_group);
+            self.draw_outlines(&mut render_pass);
+        }
+        if self.gpu_culling_active() {
+            if let Some(culler) = &mut self.gpu_culler {
+                culler.build_hiz(&mut encoder, &view_proj);
+            }
+        }
+        let unjittered_view_proj = self.projection.calc_unjittered_matrix() * self.camera.calc_matrix();
+        self.post_process.run(&mut encoder, &self.queue, anti_aliasing, &view, &view_proj, &unjittered_view_proj);
+        //  The GUI and screen text go over the finished frame, after the AA so they stay sharp
+        {
+            let mut hud_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("HUD Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.gui_renderer.draw(&mut hud_pass);
+            self.text.draw_screen(&mut hud_pass, &self.camera_bind_group);
         }
         //  submit accepts anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -446,13 +1406,159 @@ impl State {
     }
 }
 
+//  The pipelines that draw into the scene's colour and depth targets, and the SSAO prepass versions of them. They're
+//  built for one sample count, so they're rebuilt when MSAA changes.
+struct ScenePipelines {
+    render: wgpu::RenderPipeline,
+    chunk: wgpu::RenderPipeline,
+    //  Both render paths draw the transparent meshes with this, after everything opaque
+    transparent: wgpu::RenderPipeline,
+    render_normals: wgpu::RenderPipeline,
+    chunk_normals: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    //  `layouts` are the texture, camera, light and ambient occlusion bind group layouts
+    fn new(
+        device: &wgpu::Device,
+        layouts: &[&wgpu::BindGroupLayout; 4],
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let [texture_bind_group_layout, camera_bind_group_layout, light_bind_group_layout, ao_bind_group_layout] = *layouts;
+        let render_normals = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Normals Pipeline Layout"),
+                bind_group_layouts: &[texture_bind_group_layout, camera_bind_group_layout, light_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Normal Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/shader.wgsl").into()),
+            };
+            create_normal_pipeline(
+                device,
+                &layout,
+                &[model::ModelVertex::desc(), game::instance::InstanceRaw::desc()],
+                shader,
+            )
+        };
+        let (render, transparent) = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    texture_bind_group_layout,
+                    camera_bind_group_layout,
+                    light_bind_group_layout,
+                    ao_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+            let shader = || wgpu::ShaderModuleDescriptor {
+                label: Some("Normal Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/shader.wgsl").into()),
+            };
+            let vertex_layouts = [model::ModelVertex::desc(), game::instance::InstanceRaw::desc()];
+            let render = create_render_pipeline(device, &layout, color_format, false, &vertex_layouts, shader(), sample_count);
+            let transparent = create_render_pipeline(device, &layout, color_format, true, &vertex_layouts, shader(), sample_count);
+            (render, transparent)
+        };
+
+        let chunk_normals = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Chunk Normals Pipeline Layout"),
+                bind_group_layouts: &[camera_bind_group_layout, light_bind_group_layout, texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Chunk Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/chunk.wgsl").into()),
+            };
+            create_normal_pipeline(device, &layout, &[chunk_mesh::ChunkVertex::desc()], shader)
+        };
+        let chunk = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Chunk Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera_bind_group_layout,
+                    light_bind_group_layout,
+                    texture_bind_group_layout,
+                    ao_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Chunk Shader"),
+                source: wgpu::ShaderSource::Wgsl(load_file::load_str!("../res/shaders/chunk.wgsl").into()),
+            };
+            create_render_pipeline(
+                device,
+                &layout,
+                color_format,
+                false,
+                &[chunk_mesh::ChunkVertex::desc()],
+                shader,
+                sample_count,
+            )
+        };
+
+        Self { render, chunk, transparent, render_normals, chunk_normals }
+    }
+}
+
+//  Same vertex stage as the scene pipeline using `shader`, but writes the world space normal from fs_normal into a
+//  single sampled target for SSAO
+fn create_normal_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Normal Prepass Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_normal",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: ssao::NORMAL_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+//  Transparent pipelines use the shader's fs_transparent, blend by alpha and leave the depth buffer alone so whatever
+//  is drawn after them still shows through
 fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
     color_format: wgpu::TextureFormat,
-    depth_format: Option<wgpu::TextureFormat>,
+    transparent: bool,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
+    sample_count: u32,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
 
@@ -466,13 +1572,10 @@ fn create_render_pipeline(
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
-            entry_point: "fs_main",
+            entry_point: if transparent { "fs_transparent" } else { "fs_main" },
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
+                blend: Some(if transparent { model::TRANSPARENT_BLEND } else { wgpu::BlendState::REPLACE }),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
@@ -488,15 +1591,15 @@ fn create_render_pipeline(
             //  Requires Features::CONSERVATIVE_RASTERIZATION
             conservative: false,
         },
-        depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-            format,
-            depth_write_enabled: true,
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: !transparent,
             depth_compare: wgpu::CompareFunction::Less,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -504,16 +1607,136 @@ fn create_render_pipeline(
     })
 }
 
-pub async fn run() {
+//  Furthest the cursor can pick a world brick from
+const PICK_DISTANCE: f32 = 500.0;
+
+//  What's under the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Picked {
+    Instance(usize),
+    Brick(game::world::BrickId),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    pub gltf: engine::gltf_export::GltfExportOptions,
+    pub bake: engine::print_export::BakeOptions,
+}
+
+//  Picks the format from the extension: .glb/.gltf keep bricks separate, .stl/.obj bake everything into one mesh for printing
+pub fn export_world(
+    path: &str,
+    brick_name: &str,
+    brick_meshes: &[model::MeshData],
+    instances: &[&game::instance::Instance],
+    world_bricks: &[game::world::Brick],
+    options: &ExportOptions,
+) -> anyhow::Result<()> {
+    use engine::gltf_export::{self, BrickType, PlacedBrick};
+    use engine::print_export;
+    use game::world::{PLATE_HEIGHT, STUD_SIZE};
+
+    //  World bricks share one box mesh per size, after the instances' brick
+    let mut sizes = Vec::new();
+    let mut world_types = Vec::with_capacity(world_bricks.len());
+    for brick in world_bricks {
+        let index = sizes.iter().position(|&size| size == brick.size).unwrap_or_else(|| {
+            sizes.push(brick.size);
+            sizes.len() - 1
+        });
+        world_types.push(1 + index);
+    }
+    let box_meshes = sizes.iter().map(|&size| [chunk_mesh::brick_mesh_data(size)]).collect::<Vec<_>>();
+
+    let brick_types = std::iter::once(BrickType { name: brick_name, meshes: brick_meshes })
+        .chain(box_meshes.iter().map(|meshes| BrickType { name: &meshes[0].name, meshes }))
+        .collect::<Vec<_>>();
+    let bricks = instances.iter()
+        .map(|instance| PlacedBrick {
+            brick_type: 0,
+            transform: instance.model_matrix(),
+            color: instance.color,
+        })
+        .chain(world_bricks.iter().zip(world_types).map(|(brick, brick_type)| {
+            let [x, y, z] = brick.position;
+            PlacedBrick {
+                brick_type,
+                transform: cgmath::Matrix4::from_translation(cgmath::Vector3::new(
+                    x as f32 * STUD_SIZE,
+                    y as f32 * PLATE_HEIGHT,
+                    z as f32 * STUD_SIZE,
+                )),
+                color: brick.color,
+            }
+        }))
+        .collect::<Vec<_>>();
+
+    let extension = std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("stl") | Some("obj") => {
+            let baked = print_export::bake(&brick_types, &bricks, &options.bake)?;
+            print_export::export_print(path, &baked)
+        }
+        _ => gltf_export::export_gltf(path, &brick_types, &bricks, &options.gltf),
+    }
+}
+
+//  Exports the world without opening a window or touching the GPU
+pub fn export_headless(path: &str, options: &ExportOptions) -> anyhow::Result<()> {
+    mount_mods();
+    let instances = game::instance::grid_world();
+    let world_bricks = game::world::demo_world().bricks().map(|(_, brick)| *brick).collect::<Vec<_>>();
+    let brick = pollster::block_on(engine::resources::load_model_obj_data(game::instance::WORLD_BRICK))?;
+    export_world(path, &brick.name, &brick.meshes, &instances.iter().collect::<Vec<_>>(), &world_bricks, options)
+}
+
+//  Mods live next to the executable and are layered over the base resources
+fn mount_mods() {
+    if let Some(mods_dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(|p| p.join("mods"))) {
+        match engine::resources::mount_mods(&mods_dir) {
+            Ok(mounted) => mounted.iter().for_each(|m| log::info!("Mounted mod {}", m)),
+            Err(e) => log::warn!("Failed to mount mods from {:?}: {:#}", mods_dir, e),
+        }
+    }
+}
+
+//  Bindings are kept next to the executable too, and written back there when one is rebound
+fn input_config_path() -> Option<std::path::PathBuf> {
+    std::env::current_exe().ok().and_then(|p| p.parent().map(|p| p.join("input.json")))
+}
+
+fn load_input_map() -> InputMap {
+    let Some(path) = input_config_path().filter(|path| path.exists()) else {
+        return InputMap::default();
+    };
+    match InputMap::load(&path) {
+        Ok(input_map) => {
+            for (binding, actions) in input_map.conflicts() {
+                let names = actions.iter().map(|a| a.name()).collect::<Vec<_>>();
+                log::warn!("{} is bound to more than one action: {}", binding.name(), names.join(", "));
+            }
+            input_map
+        }
+        Err(e) => {
+            log::warn!("Using the default bindings: {:#}", e);
+            InputMap::default()
+        }
+    }
+}
+
+pub async fn run(graphics_settings: settings::GraphicsSettings) {
     env_logger::init();
+    mount_mods();
+
     let event_loop = EventLoop::new();
     //let window = WindowBuilder::new().build(&event_loop).unwrap();
     let window = WindowBuilder::new()
     .with_title("brickheaven")
     .build(&event_loop).unwrap();
 
-    let mut state = State::new(&window).await;
+    let mut state = State::new(&window, graphics_settings).await;
     let mut last_render_time = instant::Instant::now();
+    let mut showing_progress = false;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -521,24 +1744,20 @@ pub async fn run() {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta, },
                 ..  //  Not using device_id currently
-            } => if state.mouse_pressed {
-                state.camera_controller.process_mouse(delta.0, delta.1)
+            } => {
+                if state.input_map.is_down(Action::DragLook) {
+                    state.camera_controller.process_mouse(delta.0, delta.1);
+                }
+                if state.input_map.is_down(Action::Pan) {
+                    state.camera_controller.process_pan(delta.0, delta.1);
+                }
             }
             Event::WindowEvent {
                 ref event,
                 window_id,
             } if window_id == window.id() && !state.input(event) => {
                     match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
+                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                         WindowEvent::Resized(physical_size) => {
                             state.resize(*physical_size);
                         }
@@ -553,6 +1772,15 @@ pub async fn run() {
                 let dt = now - last_render_time;
                 last_render_time = now;
                 state.update(dt);
+                //  No loading screen yet, so show asset progress in the title bar
+                let progress = state.assets.progress();
+                if !progress.is_done() {
+                    window.set_title(&format!("brickheaven - loading {:.0}%", progress.fraction() * 100.0));
+                    showing_progress = true;
+                } else if showing_progress {
+                    window.set_title("brickheaven");
+                    showing_progress = false;
+                }
                 match state.render() {
                     Ok(_) => {}
                     //  Reconfiure the surface if lost
@@ -569,5 +1797,8 @@ pub async fn run() {
             }
             _ => {}
         }
+        if state.quit_requested {
+            *control_flow = ControlFlow::Exit;
+        }
     });
 }
\ No newline at end of file
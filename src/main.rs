@@ -1,4 +1,29 @@
-use brickheaven::run;
+use brickheaven::{run, export_headless, ExportOptions};
+use brickheaven::engine::settings::{GraphicsSettings, RenderPath};
 fn main() {
-    pollster::block_on(run());
-}
\ No newline at end of file
+    //  `brickheaven --export <file.glb|gltf|stl|obj> [--keep-hidden-faces] [--gpu-instancing]` writes the world out
+    //  without opening a window
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|a| a == "--export") {
+        env_logger::init();
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("--export needs a file name");
+            std::process::exit(2);
+        };
+        let mut options = ExportOptions::default();
+        options.bake.remove_hidden_faces = !args.iter().any(|a| a == "--keep-hidden-faces");
+        options.gltf.gpu_instancing = args.iter().any(|a| a == "--gpu-instancing");
+        if let Err(e) = export_headless(path, &options) {
+            eprintln!("Export failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    //  `brickheaven --deferred` lights opaque bricks in a deferred pass instead of as they're drawn
+    let mut graphics_settings = GraphicsSettings::default();
+    if args.iter().any(|a| a == "--deferred") {
+        graphics_settings.render_path = RenderPath::Deferred;
+    }
+    pollster::block_on(run(graphics_settings));
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use brickheaven::engine::asset_manager::{cube_mesh, AssetManager, Assets, LoadState};
+use brickheaven::engine::model;
+use brickheaven::engine::vfs::{self, DirectoryLayer};
+
+//  Whatever adapter there is, software ones included. None if the machine has no GPU at all.
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    let instance = wgpu::Instance::new(backends);
+    let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(&instance, backends, None))?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+}
+
+//  Same as the one State builds for materials
+fn material_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let texture = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+    let sampler = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            texture(0),
+            sampler(1),
+            texture(2),
+            sampler(3),
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+        label: Some("texture_bind_group_layout"),
+    })
+}
+
+//  The base cube has no materials, this is the same cube with the base textures through an MTL file
+fn mount_textured_model() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("brickheaven-assets-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("progress")).unwrap();
+    let obj = std::fs::read_to_string(root.join("res/bricks/cube.obj")).unwrap();
+    std::fs::write(dir.join("progress/textured.obj"), format!("mtllib textured.mtl\nusemtl brick\n{}", obj)).unwrap();
+    std::fs::write(
+        dir.join("progress/textured.mtl"),
+        "newmtl brick\nmap_Kd ../bricks/cube-diffuse.jpg\nmap_Bump ../bricks/cube-normal.png\n",
+    ).unwrap();
+    vfs::global().write().unwrap().mount("progress", 1, DirectoryLayer::new(dir));
+}
+
+#[test]
+fn progress_counts_loaded_and_failed_assets() {
+    let Some((device, queue)) = device() else {
+        eprintln!("No adapter, skipping");
+        return;
+    };
+    let layout = material_layout(&device);
+    mount_textured_model();
+    let mut assets = AssetManager::new(&device, &queue, &layout);
+    //  One upload a frame, so the queue drains in order over several updates
+    assets.set_upload_budget(1);
+    assert!(assets.progress().is_done());
+
+    let texture = assets.load_texture("bricks/stud.png", false);
+    let missing_texture = assets.load_texture("bricks/missing.png", false);
+    let missing_model = assets.load_model("bricks/missing.obj");
+    //  Its two textures are part of the model, not loads of their own
+    let model = assets.load_model("progress/textured.obj");
+    //  Asking again doesn't count twice
+    assets.load_texture("bricks/./stud.png", false);
+
+    let progress = assets.progress();
+    assert_eq!((progress.total, progress.loaded, progress.failed), (4, 0, 0));
+    assert!(!progress.is_done());
+
+    let start = Instant::now();
+    let mut fraction = 0.0;
+    while !assets.progress().is_done() {
+        assert!(start.elapsed() < Duration::from_secs(30), "loading never finished: {:?}", assets.progress());
+        assets.update(&device, &queue, &layout);
+        let progress = assets.progress();
+        assert_eq!(progress.total, 4);
+        assert!(progress.fraction() >= fraction, "progress went backwards: {:?}", progress);
+        fraction = progress.fraction();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let progress = assets.progress();
+    assert_eq!((progress.total, progress.loaded, progress.failed), (4, 2, 2));
+    assert_eq!(progress.fraction(), 1.0);
+    assert_eq!(assets.textures.state(&texture), LoadState::Loaded);
+    assert_eq!(assets.textures.state(&missing_texture), LoadState::Failed);
+    assert_eq!(assets.models.state(&missing_model), LoadState::Failed);
+    assert_eq!(assets.models.state(&model), LoadState::Loaded);
+    //  Done means the model's textures are in too
+    for path in ["bricks/cube-diffuse.jpg", "bricks/cube-normal.png"] {
+        let texture = assets.textures.get_handle(path).expect(path);
+        assert_eq!(assets.textures.state(&texture), LoadState::Loaded, "{}", path);
+    }
+}
+
+#[test]
+fn mesh_data_keeps_each_meshes_material() {
+    let Some((device, _queue)) = device() else {
+        eprintln!("No adapter, skipping");
+        return;
+    };
+    let mut materials = Assets::<model::Material>::new();
+    let (material, _) = materials.reserve("test#material");
+    let meshes = [1, 0, 1]
+        .into_iter()
+        .map(|index| {
+            let mut data = cube_mesh(&format!("mesh {}", index));
+            data.material = index;
+            data.upload(&device, material.clone(), true)
+        })
+        .collect();
+    let data = model::Model::new(meshes).mesh_data().unwrap();
+    assert_eq!(data.iter().map(|m| m.material).collect::<Vec<_>>(), [1, 0, 1]);
+    assert_eq!(data[0].indices.len(), 36);
+}
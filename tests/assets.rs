@@ -0,0 +1,74 @@
+use brickheaven::engine::assets::{Assets, LoadState};
+
+#[test]
+fn handles_count_their_clones() {
+    let mut assets = Assets::new();
+    let handle = assets.add(1);
+    assert_eq!(assets.ref_count(&handle), 1);
+    let clone = handle.clone();
+    assert_eq!(clone, handle);
+    assert_eq!(assets.ref_count(&handle), 2);
+    drop(clone);
+    assert_eq!(assets.ref_count(&handle), 1);
+
+    //  Still held, so nothing goes
+    assert_eq!(assets.evict_unused(), 0);
+    drop(handle);
+    assert_eq!(assets.evict_unused(), 1);
+    assert!(assets.is_empty());
+}
+
+#[test]
+fn reserve_and_get_handle_revive_unheld_entries() {
+    let mut assets = Assets::new();
+    let (handle, is_new) = assets.reserve("textures/stud.png");
+    assert!(is_new);
+    assert_eq!(assets.state(&handle), LoadState::Queued);
+    assets.insert(&handle, 7);
+    let index = handle.index();
+    drop(handle);
+
+    //  Same slot back, already loaded
+    let (handle, is_new) = assets.reserve("textures/./stud.png");
+    assert!(!is_new);
+    assert_eq!(handle.index(), index);
+    assert_eq!(assets.get(&handle), Some(&7));
+    drop(handle);
+
+    let handle = assets.get_handle("textures/stud.png").expect("unheld entry should still be there");
+    assert_eq!(handle.index(), index);
+    assert_eq!(assets.ref_count(&handle), 1);
+    assert_eq!(assets.len(), 1);
+    assert!(assets.get_handle("textures/missing.png").is_none());
+}
+
+#[test]
+fn evict_unused_keeps_queued_and_replaced_paths() {
+    let mut assets = Assets::new();
+    let (queued, _) = assets.reserve("models/brick.obj");
+    let queued_index = queued.index();
+    drop(queued);
+
+    //  A worker's still filling it in
+    assert_eq!(assets.evict_unused(), 0);
+    let (queued, is_new) = assets.reserve("models/brick.obj");
+    assert!(!is_new);
+    assert_eq!(queued.index(), queued_index);
+    assets.mark_failed(&queued);
+    drop(queued);
+    assert_eq!(assets.evict_unused(), 1);
+    assert!(assets.get_handle("models/brick.obj").is_none());
+
+    //  The path now points at the newer entry, evicting the old one mustn't unmap it
+    let old = assets.add_with_path("materials/red", 1);
+    let new = assets.add_with_path("materials/red", 2);
+    drop(old);
+    assert_eq!(assets.evict_unused(), 1);
+    let found = assets.get_handle("materials/red").unwrap();
+    assert_eq!(found, new);
+    assert_eq!(assets.get(&found), Some(&2));
+
+    //  Freed slots get reused
+    let reused = assets.add(3);
+    assert_eq!(reused.index(), queued_index);
+}
@@ -0,0 +1,76 @@
+use brickheaven::engine::bounds::Aabb;
+use brickheaven::game::camera::{Camera, CameraController, CameraMode};
+use cgmath::{InnerSpace, MetricSpace, Point3, Rad};
+use instant::Duration;
+use winit::event::MouseScrollDelta;
+
+fn settled(controller: &mut CameraController, camera: &mut Camera) {
+    controller.update_camera(camera, Duration::from_secs(1));
+}
+
+#[test]
+fn orbiting_turns_to_face_the_focus_and_dollies_towards_it() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+    let mut controller = CameraController::new(4.0, 1.0);
+    let focus = Point3::new(0.0, 3.0, 4.0);
+    controller.orbit_around(&camera, focus);
+    assert_eq!(controller.mode(), CameraMode::Orbit);
+    settled(&mut controller, &mut camera);
+    assert!(camera.position.distance(Point3::new(0.0, 0.0, 0.0)) < 1e-4);
+    assert!((camera.forward() - (focus - camera.position).normalize()).magnitude() < 1e-4);
+
+    //  A notch of the wheel takes a fixed fraction off the distance
+    controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+    settled(&mut controller, &mut camera);
+    assert!((camera.position.distance(focus) - 5.0 * (-0.1f32).exp()).abs() < 1e-4);
+    assert_eq!(controller.orbit().focus, focus);
+
+    //  Turning keeps the same distance from the focus
+    controller.process_mouse(100.0, 0.0);
+    settled(&mut controller, &mut camera);
+    assert!((camera.position.distance(focus) - controller.orbit().distance).abs() < 1e-4);
+}
+
+#[test]
+fn panning_moves_the_focus_across_the_view() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+    let mut controller = CameraController::new(4.0, 1.0);
+    controller.orbit_around(&camera, Point3::new(10.0, 0.0, 0.0));
+    settled(&mut controller, &mut camera);
+    let before = controller.orbit().focus;
+    let forward = camera.forward();
+
+    controller.process_pan(100.0, -50.0);
+    settled(&mut controller, &mut camera);
+    let moved = controller.orbit().focus - before;
+    assert!(moved.magnitude() > 0.0);
+    assert!(moved.dot(forward).abs() < 1e-4);
+    //  Dragging right and up slides the view left and down, facing +x that's -z and -y
+    assert!(moved.z < 0.0 && moved.y < 0.0);
+}
+
+#[test]
+fn framing_fits_the_bounds_and_blends_there_smoothly() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+    let mut controller = CameraController::new(4.0, 1.0);
+    let bounds = Aabb::from_points([Point3::new(9.0, -1.0, -1.0), Point3::new(11.0, 1.0, 1.0)]);
+    let fov = Rad(std::f32::consts::FRAC_PI_2);
+    controller.frame(&camera, &bounds, fov);
+    let expected = 3f32.sqrt() / (fov.0 / 2.0).sin() * 1.1;
+    assert!((controller.orbit().distance - expected).abs() < 1e-4);
+
+    //  Halfway through the blend it's halfway there
+    let target = controller.orbit().focus - camera.forward() * controller.orbit().distance;
+    let start = camera.position;
+    controller.update_camera(&mut camera, Duration::from_secs_f32(0.175));
+    let halfway = start + (target - start) * 0.5;
+    assert!(camera.position.distance(halfway) < 1e-3);
+    settled(&mut controller, &mut camera);
+    assert!(camera.position.distance(target) < 1e-4);
+
+    //  Flying off starts from where the orbit left the camera
+    controller.fly();
+    settled(&mut controller, &mut camera);
+    assert_eq!(controller.mode(), CameraMode::Fly);
+    assert!(camera.position.distance(target) < 1e-4);
+}
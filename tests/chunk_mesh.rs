@@ -0,0 +1,251 @@
+use std::time::{Duration, Instant};
+
+use brickheaven::engine::chunk_mesh::{brick_mesh_data, mesh_chunk, ChunkMeshData, ChunkMesher};
+use brickheaven::engine::lod::Lod;
+use brickheaven::game::world::{Brick, ChunkCoord, World, CHUNK_SIZE, PLATE_HEIGHT};
+
+const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+//  Flat tops are just the boxes, which keeps the face counts easy to work out
+fn mesh(world: &World, coord: ChunkCoord) -> ChunkMeshData {
+    mesh_chunk(&world.snapshot(coord), Lod::FlatTop)
+}
+
+fn origin() -> ChunkCoord {
+    ChunkCoord { x: 0, z: 0 }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+#[test]
+fn lone_brick_has_six_faces() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [2, 3, 4], RED)).unwrap();
+    let mesh = mesh(&world, origin());
+    assert_eq!(mesh.face_count(), 6);
+    assert_eq!(mesh.vertices.len(), 24);
+    assert!(mesh.vertices.iter().all(|v| v.color == RED));
+}
+
+#[test]
+fn faces_wind_counter_clockwise_around_their_normal() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([3, 1, 5], [2, 3, 4], RED)).unwrap();
+    let mesh = mesh(&world, origin());
+    for tri in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| mesh.vertices[i as usize]);
+        let n = cross(sub(b.position, a.position), sub(c.position, a.position));
+        let along = n[0] * a.normal[0] + n[1] * a.normal[1] + n[2] * a.normal[2];
+        assert!(along > 0.0, "triangle faces away from its normal {:?}", a.normal);
+    }
+}
+
+#[test]
+fn stacked_bricks_lose_the_faces_between_them() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [2, 3, 4], RED)).unwrap();
+    world.add_brick(Brick::new([0, 3, 0], [2, 3, 4], BLUE)).unwrap();
+    assert_eq!(mesh(&world, origin()).face_count(), 10);
+}
+
+#[test]
+fn side_by_side_bricks_lose_the_faces_between_them() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [1, 3, 1], RED)).unwrap();
+    world.add_brick(Brick::new([1, 0, 0], [1, 3, 1], RED)).unwrap();
+    assert_eq!(mesh(&world, origin()).face_count(), 10);
+}
+
+#[test]
+fn partly_covered_face_is_split_into_rectangles() {
+    let mut world = World::new();
+    //  A 1x1 in the corner of a 2x2 plate leaves an L of three uncovered studs, which takes two rectangles
+    world.add_brick(Brick::new([0, 0, 0], [2, 1, 2], RED)).unwrap();
+    world.add_brick(Brick::new([0, 1, 0], [1, 3, 1], BLUE)).unwrap();
+    let mesh = mesh(&world, origin());
+    let red = mesh.vertices.iter().filter(|v| v.color == RED).count() / 4;
+    let blue = mesh.vertices.iter().filter(|v| v.color == BLUE).count() / 4;
+    assert_eq!(red, 7);
+    assert_eq!(blue, 5);
+    assert_eq!(mesh.face_count(), 12);
+}
+
+#[test]
+fn baseplate_fully_covered_by_bricks_keeps_only_its_sides_and_bottom() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [8, 1, 8], RED)).unwrap();
+    for z in (0..8).step_by(2) {
+        for x in (0..8).step_by(4) {
+            world.add_brick(Brick::new([x, 1, z], [4, 3, 2], BLUE)).unwrap();
+        }
+    }
+    let mesh = mesh(&world, origin());
+    let red = mesh.vertices.iter().filter(|v| v.color == RED).count() / 4;
+    assert_eq!(red, 5);
+    //  Each of the 8 bricks keeps its top, plus whichever of its sides are on the outside of the 2x4 grid
+    let blue = mesh.vertices.iter().filter(|v| v.color == BLUE).count() / 4;
+    assert_eq!(blue, 8 + 2 * 4 + 2 * 2);
+}
+
+#[test]
+fn faces_are_hidden_across_chunk_borders() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([CHUNK_SIZE - 1, 0, 0], [1, 3, 1], RED)).unwrap();
+    world.add_brick(Brick::new([CHUNK_SIZE, 0, 0], [1, 3, 1], BLUE)).unwrap();
+    assert_eq!(mesh(&world, origin()).face_count(), 5);
+    assert_eq!(mesh(&world, ChunkCoord { x: 1, z: 0 }).face_count(), 5);
+}
+
+#[test]
+fn bricks_hanging_into_the_next_chunk_hide_its_faces() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([CHUNK_SIZE - 2, 0, 0], [4, 3, 2], RED)).unwrap();
+    world.add_brick(Brick::new([CHUNK_SIZE, 3, 0], [2, 3, 2], BLUE)).unwrap();
+    let next = mesh(&world, ChunkCoord { x: 1, z: 0 });
+    assert_eq!(next.face_count(), 5);
+    //  Only the part of the red brick's top that isn't under the blue one is left
+    assert_eq!(mesh(&world, origin()).face_count(), 6);
+}
+
+#[test]
+fn overlapping_bricks_are_rejected() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [2, 3, 2], RED)).unwrap();
+    assert!(world.add_brick(Brick::new([1, 2, 1], [2, 3, 2], RED)).is_err());
+    assert!(world.add_brick(Brick::new([0, 0, 0], [CHUNK_SIZE + 1, 1, 1], RED)).is_err());
+    assert_eq!(world.brick_count(), 1);
+}
+
+#[test]
+fn raycast_finds_the_nearest_brick_along_the_ray() {
+    let mut world = World::new();
+    let near = world.add_brick(Brick::new([0, 0, 0], [2, 3, 2], RED)).unwrap();
+    let far = world.add_brick(Brick::new([-CHUNK_SIZE, 0, 0], [2, 3, 2], BLUE)).unwrap();
+
+    //  Looking along -x from the right of both bricks at half their height
+    let origin = [10.5, 1.5 * PLATE_HEIGHT, 0.5];
+    let (id, distance) = world.raycast(origin, [-1.0, 0.0, 0.0], 100.0).unwrap();
+    assert_eq!(id, near);
+    assert!((distance - 8.5).abs() < 1e-4, "{}", distance);
+
+    world.remove_brick(near).unwrap();
+    assert_eq!(world.raycast(origin, [-1.0, 0.0, 0.0], 100.0).map(|(id, _)| id), Some(far));
+    //  Out of reach, or looking the other way
+    assert!(world.raycast(origin, [-1.0, 0.0, 0.0], 20.0).is_none());
+    assert!(world.raycast(origin, [1.0, 0.0, 0.0], 100.0).is_none());
+
+    //  Straight down onto the top, which is three plates up
+    let (id, distance) = world.raycast([-CHUNK_SIZE as f32 + 1.0, 2.0, 1.0], [0.0, -1.0, 0.0], 100.0).unwrap();
+    assert_eq!(id, far);
+    assert!((distance - (2.0 - 3.0 * PLATE_HEIGHT)).abs() < 1e-4, "{}", distance);
+}
+
+#[test]
+fn bricks_are_listed_once_each_in_chunk_order() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([CHUNK_SIZE, 0, 0], [1, 1, 1], BLUE)).unwrap();
+    world.add_brick(Brick::new([0, 0, 0], [1, 1, 1], RED)).unwrap();
+    world.add_brick(Brick::new([1, 0, 0], [1, 1, 1], RED)).unwrap();
+    let positions = world.bricks().map(|(_, brick)| brick.position).collect::<Vec<_>>();
+    assert_eq!(positions, vec![[0, 0, 0], [1, 0, 0], [CHUNK_SIZE, 0, 0]]);
+}
+
+#[test]
+fn exported_brick_mesh_is_a_closed_box() {
+    let mesh = brick_mesh_data([2, 3, 4]);
+    assert_eq!(mesh.indices.len(), 6 * 6);
+    let max = mesh.vertices.iter().fold([0.0f32; 3], |max, v| [0, 1, 2].map(|a| max[a].max(v.position[a])));
+    assert_eq!(max, [2.0, 3.0 * PLATE_HEIGHT, 4.0]);
+    for v in &mesh.vertices {
+        let n = v.normal;
+        let t = v.tangent;
+        assert_eq!(n[0] * t[0] + n[1] * t[1] + n[2] * t[2], 0.0);
+    }
+}
+
+#[test]
+fn only_chunks_touching_an_edit_become_dirty() {
+    let mut world = World::new();
+    for x in 0..4 {
+        world.add_brick(Brick::new([x * CHUNK_SIZE + 8, 0, 8], [1, 1, 1], RED)).unwrap();
+    }
+    assert_eq!(world.take_dirty().len(), 4);
+    assert!(world.take_dirty().is_empty());
+
+    //  On the border with chunk 1, chunks 2 and 3 are too far away to be affected
+    let id = world.add_brick(Brick::new([CHUNK_SIZE - 1, 0, 0], [1, 1, 1], RED)).unwrap();
+    assert_eq!(world.take_dirty(), vec![origin(), ChunkCoord { x: 1, z: 0 }]);
+
+    world.remove_brick(id).unwrap();
+    assert_eq!(world.take_dirty(), vec![origin(), ChunkCoord { x: 1, z: 0 }]);
+    assert_eq!(mesh(&world, origin()).face_count(), 6);
+}
+
+#[test]
+fn removing_the_last_brick_leaves_an_empty_mesh() {
+    let mut world = World::new();
+    let id = world.add_brick(Brick::new([0, 0, 0], [1, 1, 1], RED)).unwrap();
+    world.take_dirty();
+    world.remove_brick(id).unwrap();
+    assert_eq!(world.take_dirty(), vec![origin()]);
+    assert!(mesh(&world, origin()).is_empty());
+    assert!(world.chunk(origin()).is_none());
+}
+
+#[test]
+fn mesher_only_returns_the_newest_mesh_of_a_chunk() {
+    let mut world = World::new();
+    let mut mesher = ChunkMesher::new();
+    world.add_brick(Brick::new([0, 0, 0], [1, 3, 1], RED)).unwrap();
+    mesher.submit(world.snapshot(origin()));
+    world.add_brick(Brick::new([1, 0, 0], [1, 3, 1], RED)).unwrap();
+    mesher.submit(world.snapshot(origin()));
+
+    let start = Instant::now();
+    let mut meshes = Vec::new();
+    while mesher.pending() > 0 {
+        assert!(start.elapsed() < Duration::from_secs(10), "mesher never finished");
+        meshes.extend(mesher.poll());
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].level(Lod::FlatTop).face_count(), 10);
+}
+
+#[test]
+fn full_detail_puts_a_stud_on_every_uncovered_top_cell() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [2, 1, 2], RED)).unwrap();
+    world.add_brick(Brick::new([0, 1, 0], [1, 3, 1], BLUE)).unwrap();
+    let snapshot = world.snapshot(origin());
+    let flat = mesh_chunk(&snapshot, Lod::FlatTop);
+    let full = mesh_chunk(&snapshot, Lod::Full);
+    //  Three uncovered cells on the plate and one on top of the brick, 12 side quads and a 10 triangle fan each
+    let stud_triangles = 4 * (12 * 2 + 10);
+    assert_eq!(full.triangle_count(), flat.triangle_count() + stud_triangles);
+    assert!(full.aabb.max.y > flat.aabb.max.y);
+    //  Only the flat tops wear the stud normal map
+    assert!(full.vertices.iter().all(|v| v.stud_normal == 0.0));
+    assert_eq!(flat.vertices.iter().filter(|v| v.stud_normal == 1.0).count(), 3 * 4);
+}
+
+#[test]
+fn impostors_merge_faces_across_bricks_of_the_same_colour() {
+    let mut world = World::new();
+    world.add_brick(Brick::new([0, 0, 0], [1, 3, 1], RED)).unwrap();
+    world.add_brick(Brick::new([1, 0, 0], [1, 3, 1], RED)).unwrap();
+    world.add_brick(Brick::new([2, 0, 0], [1, 3, 1], BLUE)).unwrap();
+    let snapshot = world.snapshot(origin());
+    assert_eq!(mesh_chunk(&snapshot, Lod::FlatTop).face_count(), 14);
+    //  The two reds become one box, the blue stays separate where it's a different colour
+    let impostor = mesh_chunk(&snapshot, Lod::Impostor);
+    assert_eq!(impostor.face_count(), 5 + 5);
+    assert_eq!(impostor.vertices.iter().filter(|v| v.color == BLUE).count() / 4, 5);
+}
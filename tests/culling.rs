@@ -0,0 +1,94 @@
+use brickheaven::engine::bounds::{Aabb, Ray};
+use brickheaven::engine::culling::{Bvh, Containment, Frustum};
+use brickheaven::game::camera::OPENGL_TO_WGPU_MATRIX;
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Vector3};
+
+//  At the origin looking down -z, 90 degrees each way, so at z = -10 it sees x and y from -10 to 10
+fn frustum() -> Frustum {
+    let view = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z(), Vector3::unit_y());
+    let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(90.0), 1.0, 1.0, 100.0);
+    Frustum::from_view_proj(&(proj * view))
+}
+
+fn cube(center: Point3<f32>, half: f32) -> Aabb {
+    Aabb::from_points([center - Vector3::new(half, half, half), center + Vector3::new(half, half, half)])
+}
+
+#[test]
+fn boxes_inside_outside_and_straddling_the_frustum() {
+    let frustum = frustum();
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(0.0, 0.0, -10.0), 1.0)), Containment::Inside);
+    //  Behind the camera, off to the side, past the far plane
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(0.0, 0.0, 10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(20.0, 0.0, -10.0), 1.0)), Containment::Outside);
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(0.0, 0.0, -200.0), 1.0)), Containment::Outside);
+    //  Across the right side plane, and across the near plane
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(10.0, 0.0, -10.0), 1.0)), Containment::Intersecting);
+    assert_eq!(frustum.test_aabb(&cube(Point3::new(0.0, 0.0, -1.0), 0.5)), Containment::Intersecting);
+}
+
+#[test]
+fn bvh_query_matches_testing_every_box() {
+    let frustum = frustum();
+    let mut bounds = Vec::new();
+    for x in -20..20 {
+        for z in -30..10 {
+            bounds.push(cube(Point3::new(x as f32 * 2.0, (x % 3) as f32, z as f32 * 2.0), 0.6));
+        }
+    }
+    let bvh = Bvh::build(&bounds);
+    assert_eq!(bvh.len(), bounds.len());
+
+    let mut visible = Vec::new();
+    bvh.query(&frustum, &mut visible);
+    visible.sort_unstable();
+    let expected = (0..bounds.len())
+        .filter(|&i| frustum.test_aabb(&bounds[i]) != Containment::Outside)
+        .collect::<Vec<_>>();
+    assert!(!expected.is_empty() && expected.len() < bounds.len());
+    assert_eq!(visible, expected);
+}
+
+#[test]
+fn bvh_ray_query_matches_testing_every_box() {
+    let mut bounds = Vec::new();
+    for x in -20..20 {
+        for z in -20..20 {
+            bounds.push(cube(Point3::new(x as f32 * 2.0, (x % 3) as f32, z as f32 * 2.0), 0.6));
+        }
+    }
+    let bvh = Bvh::build(&bounds);
+
+    //  Along a row of boxes at a slight angle, straight down onto one, and up away from all of them
+    let rays = [
+        (Ray { origin: Point3::new(-50.0, 0.2, 0.1), direction: Vector3::new(1.0, 0.0, 0.02).normalize() }, None),
+        (Ray { origin: Point3::new(4.0, 20.0, -6.0), direction: -Vector3::unit_y() }, Some(1)),
+        (Ray { origin: Point3::new(0.0, 50.0, 0.0), direction: Vector3::unit_y() }, Some(0)),
+    ];
+    for (ray, count) in &rays {
+        let mut hits = Vec::new();
+        bvh.query_ray(ray, &mut hits);
+        hits.sort_unstable();
+        let expected = (0..bounds.len()).filter(|&i| bounds[i].intersect_ray(ray).is_some()).collect::<Vec<_>>();
+        assert_eq!(hits, expected);
+        assert_eq!(count.unwrap_or(hits.len()), hits.len());
+        assert!(count.is_some() || hits.len() > 1);
+    }
+}
+
+#[test]
+fn empty_and_single_leaf_hierarchies() {
+    let frustum = frustum();
+    let empty = Bvh::build(&[]);
+    assert!(empty.is_empty());
+    let mut visible = Vec::new();
+    empty.query(&frustum, &mut visible);
+    assert!(visible.is_empty());
+
+    //  Few enough for one leaf, the leaf straddles the frustum so each box is tested on its own
+    let bounds = [cube(Point3::new(0.0, 0.0, -10.0), 1.0), cube(Point3::new(0.0, 0.0, 10.0), 1.0)];
+    let single = Bvh::build(&bounds);
+    assert_eq!(single.len(), 2);
+    single.query(&frustum, &mut visible);
+    assert_eq!(visible, [0]);
+}
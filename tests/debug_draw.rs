@@ -0,0 +1,63 @@
+use brickheaven::engine::bounds::Aabb;
+use brickheaven::engine::debug_draw::DebugDraw;
+use cgmath::{Matrix4, Point3, Vector3};
+
+const WHITE: [f32; 4] = [1.0; 4];
+
+#[test]
+fn shapes_queue_the_expected_lines() {
+    let mut lines = DebugDraw::default();
+    let aabb = Aabb { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) };
+    lines.aabb(&aabb, WHITE);
+    assert_eq!(lines.depth_tested_lines().len(), 12 * 2);
+
+    //  Every box edge is one unit along a single axis
+    for edge in lines.depth_tested_lines().chunks(2) {
+        let (a, b) = (edge[0].position, edge[1].position);
+        let changed = (0..3).filter(|&i| a[i] != b[i]).count();
+        assert_eq!(changed, 1);
+    }
+
+    lines.clear();
+    lines.arrow(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0), WHITE);
+    assert_eq!(lines.depth_tested_lines().len(), 5 * 2);
+    lines.clear();
+    lines.grid(Point3::new(0.0, 0.0, 0.0), 2.0, 1.0, WHITE);
+    assert_eq!(lines.depth_tested_lines().len(), 10 * 2);
+}
+
+#[test]
+fn sphere_points_lie_on_the_sphere() {
+    let mut lines = DebugDraw::default();
+    let center = Point3::new(1.0, 2.0, 3.0);
+    lines.sphere(center, 0.5, WHITE);
+    assert!(!lines.is_empty());
+    for vertex in lines.depth_tested_lines() {
+        let p: Point3<f32> = vertex.position.into();
+        assert!((cgmath::MetricSpace::distance(p, center) - 0.5).abs() < 1e-5);
+    }
+
+    //  Axes follow the transform, red along its x
+    lines.clear();
+    lines.axes(&Matrix4::from_translation(Vector3::new(5.0, 0.0, 0.0)), 2.0);
+    let x = &lines.depth_tested_lines()[..2];
+    assert_eq!(x[0].position, [5.0, 0.0, 0.0]);
+    assert_eq!(x[1].position, [7.0, 0.0, 0.0]);
+    assert_eq!(x[0].color, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn depth_test_routes_lines_until_cleared() {
+    let mut lines = DebugDraw::default();
+    lines.line(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), WHITE);
+    lines.set_depth_test(false);
+    lines.line(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), WHITE);
+    assert_eq!(lines.depth_tested_lines().len(), 2);
+    assert_eq!(lines.on_top_lines().len(), 2);
+
+    lines.clear();
+    assert!(lines.is_empty());
+    lines.line(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 1.0), WHITE);
+    assert_eq!(lines.depth_tested_lines().len(), 2);
+    assert!(lines.on_top_lines().is_empty());
+}
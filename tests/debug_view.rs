@@ -0,0 +1,56 @@
+use brickheaven::engine::debug_view::{tangent_frame_vertices, wireframe_vertices, DebugView};
+use brickheaven::engine::model::{MeshData, ModelVertex};
+
+fn vertex(position: [f32; 3]) -> ModelVertex {
+    ModelVertex {
+        position,
+        tex_coords: [0.0; 2],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [2.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+    }
+}
+
+fn quad() -> MeshData {
+    MeshData {
+        name: "quad".into(),
+        vertices: vec![vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([1.0, 1.0, 0.0]), vertex([0.0, 1.0, 0.0])],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        material: 0,
+    }
+}
+
+#[test]
+fn wireframe_gives_every_triangle_its_own_corners() {
+    let vertices = wireframe_vertices(&quad());
+    assert_eq!(vertices.len(), 6);
+    let positions = vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+    assert_eq!(positions[3..], [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+    for triangle in vertices.chunks(3) {
+        assert_eq!(triangle.iter().map(|v| v.data).collect::<Vec<_>>(), [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+    }
+}
+
+#[test]
+fn tangent_frames_are_fixed_length_lines_coloured_by_axis() {
+    let lines = tangent_frame_vertices(&quad(), 0.5);
+    assert_eq!(lines.len(), 4 * 3 * 2);
+    //  The tangent is 2 long but the line is still 0.5
+    assert_eq!(lines[0].position, [0.0, 0.0, 0.0]);
+    assert_eq!(lines[1].position, [0.5, 0.0, 0.0]);
+    assert_eq!(lines[1].data, [1.0, 0.0, 0.0]);
+    assert_eq!(lines[3].position, [0.0, 0.5, 0.0]);
+    assert_eq!(lines[5].position, [0.0, 0.0, 0.5]);
+    assert_eq!(lines[5].data, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn debug_views_cycle_back_to_off() {
+    let mut view = DebugView::Off;
+    for _ in 0..DebugView::ALL.len() {
+        view = view.next();
+    }
+    assert_eq!(view, DebugView::Off);
+    assert!(!DebugView::TangentFrames.replaces_shading());
+    assert!(DebugView::Depth.replaces_shading());
+}
@@ -0,0 +1,23 @@
+use brickheaven::engine::deferred::PointLight;
+use brickheaven::engine::settings::{AntiAliasing, GraphicsSettings, RenderPath};
+
+#[test]
+fn point_light_matches_the_shader_layout() {
+    //  vec3 + f32, vec3 rounded up to the struct's 16 byte alignment
+    assert_eq!(std::mem::size_of::<PointLight>(), 32);
+    let light = PointLight::new([1.0, 2.0, 3.0], [0.5; 3], 4.0);
+    let floats: &[f32] = bytemuck::cast_slice(std::slice::from_ref(&light));
+    assert_eq!(&floats[..4], &[1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(&floats[4..7], &[0.5; 3]);
+}
+
+#[test]
+fn deferred_path_needs_a_single_sampled_depth_buffer() {
+    let mut settings = GraphicsSettings::default();
+    assert!(!settings.needs_single_sample());
+    settings.render_path = RenderPath::Deferred;
+    assert!(settings.needs_single_sample());
+    settings.render_path = RenderPath::Forward;
+    settings.anti_aliasing = AntiAliasing::Taa;
+    assert!(settings.needs_single_sample());
+}
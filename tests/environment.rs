@@ -0,0 +1,36 @@
+use brickheaven::game::environment::{Environment, FogMode};
+use brickheaven::game::uniform::FogUniform;
+use serde_json::json;
+
+#[test]
+fn environment_round_trips_through_json() {
+    let mut environment = Environment { sky_color: [0.5, 0.25, 0.75], ..Default::default() };
+    environment.fog.mode = FogMode::Height;
+    environment.fog.density = 0.1;
+    environment.fog.height_falloff = 0.5;
+    let loaded = Environment::from_json(&environment.to_json()).unwrap();
+    assert_eq!(loaded, environment);
+}
+
+#[test]
+fn missing_fields_keep_their_defaults_and_unknown_modes_fail() {
+    let loaded = Environment::from_json(&json!({ "fog": { "mode": "exponential" } })).unwrap();
+    let mut expected = Environment::default();
+    expected.fog.mode = FogMode::Exponential;
+    assert_eq!(loaded, expected);
+
+    assert!(Environment::from_json(&json!({ "fog": { "mode": "soup" } })).is_err());
+    assert!(Environment::from_json(&json!({ "skyColor": [1.0, 0.0] })).is_err());
+}
+
+#[test]
+fn fog_uniform_matches_the_shader_layout() {
+    //  vec3 + u32, then five floats rounded up to the struct's 16 byte alignment
+    assert_eq!(std::mem::size_of::<FogUniform>(), 48);
+    let mut environment = Environment::default();
+    environment.fog.mode = FogMode::Height;
+    let uniform = environment.fog_uniform();
+    assert_eq!(uniform.color, environment.sky_color);
+    assert_eq!(uniform.mode, 3);
+    assert_eq!(FogMode::Height.next(), FogMode::Off);
+}
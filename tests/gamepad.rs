@@ -0,0 +1,64 @@
+use brickheaven::engine::gamepad::GamepadEvent;
+use brickheaven::game::camera::{Camera, CameraController};
+use brickheaven::game::input::{Action, AxisResponse, GamepadAxis, GamepadButton, GamepadSettings, InputMap};
+use serde_json::json;
+
+#[test]
+fn dead_zones_are_round_and_curves_shape_what_is_left() {
+    let response = AxisResponse { dead_zone: 0.2, curve: 2.0 };
+    assert_eq!(response.apply(0.1), 0.0);
+    assert_eq!(response.apply(-1.0), -1.0);
+    assert!((response.apply(0.6) - 0.25).abs() < 1e-6);
+
+    let mut map = InputMap::default();
+    //  Each axis is inside the dead zone on its own, together they're out of it
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::LeftStickX, 0.12));
+    assert_eq!(map.axis_value(GamepadAxis::LeftStickX), 0.0);
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::LeftStickY, 0.12));
+    assert!(map.axis_value(GamepadAxis::LeftStickX) > 0.0);
+    //  A full diagonal is as fast as a full push straight ahead
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::LeftStickX, 1.0));
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::LeftStickY, 1.0));
+    let (x, y) = (map.axis_value(GamepadAxis::LeftStickX), map.axis_value(GamepadAxis::LeftStickY));
+    assert!((x.hypot(y) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn sticks_and_triggers_move_the_camera_at_its_speed() {
+    let mut map = InputMap::default();
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::LeftStickY, 1.0));
+    map.handle_gamepad_event(&GamepadEvent::Axis(GamepadAxis::RightTrigger, 1.0));
+    //  Facing down +x
+    let mut camera = Camera::new((0.0, 0.0, 0.0), cgmath::Rad(0.0), cgmath::Rad(0.0));
+    let mut controller = CameraController::new(4.0, 1.0);
+    controller.process_actions(&map);
+    controller.update_camera(&mut camera, instant::Duration::from_millis(500));
+    assert!((camera.position.x - 2.0).abs() < 1e-5);
+    assert!((camera.position.y - 2.0).abs() < 1e-5);
+    assert!(camera.position.z.abs() < 1e-5);
+
+    //  Unplugging lets go of everything
+    map.handle_gamepad_event(&GamepadEvent::Disconnected);
+    controller.process_actions(&map);
+    controller.update_camera(&mut camera, instant::Duration::from_millis(500));
+    assert!((camera.position.x - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn dpad_rotates_bricks_and_settings_load_from_json() {
+    let mut map = InputMap::default();
+    map.handle_gamepad_event(&GamepadEvent::Button(GamepadButton::DPadRight, true));
+    map.handle_gamepad_event(&GamepadEvent::Button(GamepadButton::DPadRight, false));
+    map.handle_gamepad_event(&GamepadEvent::Button(GamepadButton::DPadLeft, true));
+    assert_eq!(map.take_triggered(), [Action::RotateBrickCw, Action::RotateBrickCcw]);
+
+    let loaded = InputMap::from_json(&json!({ "gamepad": { "stickDeadZone": 0.3 } })).unwrap();
+    let expected = GamepadSettings {
+        stick: AxisResponse { dead_zone: 0.3, ..GamepadSettings::default().stick },
+        ..Default::default()
+    };
+    assert_eq!(loaded.gamepad_settings(), expected);
+    let reloaded = InputMap::from_json(&loaded.to_json()).unwrap();
+    assert_eq!(reloaded.gamepad_settings(), expected);
+    assert!(InputMap::from_json(&json!({ "gamepad": { "triggerDeadZone": 1.0 } })).is_err());
+}
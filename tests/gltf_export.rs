@@ -0,0 +1,89 @@
+use brickheaven::engine::gltf_export::{build_gltf, decompose, to_glb, BrickType, GltfExportOptions, PlacedBrick};
+use brickheaven::engine::model::{MeshData, ModelVertex};
+use cgmath::{Deg, InnerSpace, Matrix4, Quaternion, Rotation3, Vector3};
+use serde_json::json;
+
+fn triangle(name: &str) -> MeshData {
+    let vertex = |position| ModelVertex {
+        position,
+        tex_coords: [0.0; 2],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0],
+        bitangent: [0.0, 1.0, 0.0],
+    };
+    MeshData {
+        name: name.into(),
+        vertices: vec![vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([0.0, 1.0, 0.0])],
+        indices: vec![0, 1, 2],
+        material: 0,
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn decomposing_and_recomposing_gives_the_same_matrix() {
+    let rotation = Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 3.0).normalize(), Deg(70.0));
+    let transform = Matrix4::from_translation(Vector3::new(3.0, -2.0, 5.0))
+        * Matrix4::from(rotation)
+        * Matrix4::from_nonuniform_scale(2.0, 0.5, 1.5);
+    let (t, [x, y, z, w], s) = decompose(&transform);
+    let recomposed = Matrix4::from_translation(t.into())
+        * Matrix4::from(Quaternion::new(w, x, y, z))
+        * Matrix4::from_nonuniform_scale(s[0], s[1], s[2]);
+    let (a, b): (&[f32; 16], &[f32; 16]) = (transform.as_ref(), recomposed.as_ref());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < 1e-5, "{:?} != {:?}", transform, recomposed);
+    }
+}
+
+#[test]
+fn glb_header_and_chunks_are_padded_to_four_bytes() {
+    let json = json!({ "asset": { "version": "2.0" } });
+    let glb = to_glb(&json, &[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(u32_at(&glb, 4), 2);
+    assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+    let json_length = u32_at(&glb, 12) as usize;
+    assert_eq!(json_length % 4, 0);
+    assert_eq!(&glb[16..20], b"JSON");
+    let json_chunk = &glb[20..20 + json_length];
+    let text = serde_json::to_vec(&json).unwrap();
+    assert_eq!(&json_chunk[..text.len()], text.as_slice());
+    assert!(json_chunk[text.len()..].iter().all(|&b| b == b' '));
+
+    let bin = 20 + json_length;
+    assert_eq!(u32_at(&glb, bin), 8);
+    assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+    assert_eq!(&glb[bin + 8..], [1, 2, 3, 4, 5, 0, 0, 0]);
+}
+
+#[test]
+fn geometry_is_written_once_per_brick_type_and_colours_become_materials() {
+    let (a, b) = ([triangle("a")], [triangle("b")]);
+    let brick_types = [BrickType { name: "a", meshes: &a }, BrickType { name: "b", meshes: &b }];
+    let (red, blue) = ([1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 0.5]);
+    let brick = |brick_type, x, color| PlacedBrick {
+        brick_type,
+        transform: Matrix4::from_translation(Vector3::new(x, 0.0, 0.0)),
+        color,
+    };
+    let bricks = [brick(0, 0.0, red), brick(0, 1.0, red), brick(1, 2.0, red), brick(1, 3.0, blue)];
+    let (gltf, _) = build_gltf(&brick_types, &bricks, &GltfExportOptions::default()).unwrap();
+
+    assert_eq!(gltf["materials"].as_array().unwrap().len(), 2);
+    assert_eq!(gltf["materials"][1]["alphaMode"], "BLEND");
+    assert_eq!(gltf["nodes"].as_array().unwrap().len(), 4);
+    //  A mesh per type and colour, since the material lives on the primitive, all sharing their type's accessors
+    let meshes = gltf["meshes"].as_array().unwrap();
+    assert_eq!(meshes.len(), 3);
+    let mut positions = meshes.iter().map(|m| m["primitives"][0]["attributes"]["POSITION"].as_u64().unwrap()).collect::<Vec<_>>();
+    positions.sort_unstable();
+    positions.dedup();
+    assert_eq!(positions.len(), brick_types.len());
+
+    assert!(build_gltf(&brick_types, &[brick(2, 0.0, red)], &GltfExportOptions::default()).is_err());
+}
@@ -0,0 +1,98 @@
+use brickheaven::engine::font::Font;
+use brickheaven::engine::gui::{Gui, Rect};
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+
+fn font() -> Font {
+    Font::from_bytes(std::fs::read("res/fonts/DejaVuSans.ttf").unwrap()).unwrap()
+}
+
+fn click(gui: &mut Gui) -> (bool, bool) {
+    (gui.mouse_input(MouseButton::Left, ElementState::Pressed), gui.mouse_input(MouseButton::Left, ElementState::Released))
+}
+
+#[test]
+fn buttons_take_clicks_before_the_scene() {
+    let font = font();
+    let mut gui = Gui::default();
+    let button = Rect::new(10.0, 10.0, 100.0, 30.0);
+    assert!(!gui.begin(&font).button("ok", button, "OK"));
+
+    //  Over the button the GUI uses everything, and the click comes out of the next frame
+    assert!(gui.mouse_moved(20.0, 20.0));
+    assert_eq!(click(&mut gui), (true, true));
+    assert!(gui.begin(&font).button("ok", button, "OK"));
+    assert!(!gui.begin(&font).button("ok", button, "OK"));
+    assert!(!gui.draw_list().texts.is_empty());
+
+    //  Off it the scene gets the mouse, even if a drag from the scene ends up over the button
+    assert!(!gui.mouse_moved(200.0, 200.0));
+    assert!(!gui.mouse_input(MouseButton::Left, ElementState::Pressed));
+    assert!(!gui.mouse_moved(20.0, 20.0));
+    assert!(!gui.mouse_input(MouseButton::Left, ElementState::Released));
+    assert!(!gui.begin(&font).button("ok", button, "OK"));
+
+    //  The same goes for the other buttons, a middle drag from the scene is let go of over the GUI
+    assert!(!gui.mouse_moved(200.0, 200.0));
+    assert!(!gui.mouse_input(MouseButton::Middle, ElementState::Pressed));
+    assert!(gui.mouse_moved(20.0, 20.0));
+    assert!(!gui.mouse_input(MouseButton::Middle, ElementState::Released));
+    assert!(gui.mouse_input(MouseButton::Right, ElementState::Pressed));
+    assert!(gui.mouse_input(MouseButton::Right, ElementState::Released));
+}
+
+#[test]
+fn sliders_follow_drags_and_lists_scroll() {
+    let font = font();
+    let mut gui = Gui::default();
+    let slider = Rect::new(0.0, 0.0, 100.0, 20.0);
+    let mut value = 0.0;
+    gui.begin(&font).slider("value", slider, &mut value, 0.0..=10.0);
+    gui.mouse_moved(50.0, 10.0);
+    gui.mouse_input(MouseButton::Left, ElementState::Pressed);
+    assert!(gui.begin(&font).slider("value", slider, &mut value, 0.0..=10.0));
+    assert!((value - 5.0).abs() < 0.01);
+    //  Still dragging once the mouse is past the end
+    assert!(gui.mouse_moved(500.0, 300.0));
+    gui.begin(&font).slider("value", slider, &mut value, 0.0..=10.0);
+    assert_eq!(value, 10.0);
+    gui.mouse_input(MouseButton::Left, ElementState::Released);
+    gui.begin(&font);
+
+    let items = (0..50).map(|i| format!("Item {}", i)).collect::<Vec<_>>();
+    let list = Rect::new(0.0, 100.0, 100.0, 100.0);
+    let mut selected = None;
+    gui.begin(&font).scroll_list("list", list, &items, &mut selected);
+    gui.mouse_moved(10.0, 105.0);
+    assert!(gui.mouse_wheel(-3.0));
+    gui.begin(&font).scroll_list("list", list, &items, &mut selected);
+    click(&mut gui);
+    assert!(gui.begin(&font).scroll_list("list", list, &items, &mut selected));
+    assert_eq!(selected, Some(3));
+}
+
+#[test]
+fn focused_text_fields_take_the_keyboard() {
+    let font = font();
+    let mut gui = Gui::default();
+    let field = Rect::new(0.0, 0.0, 200.0, 24.0);
+    let mut text = String::from("ab");
+    assert!(!gui.key_input(VirtualKeyCode::W, ElementState::Pressed));
+
+    gui.begin(&font).text_input("name", field, &mut text);
+    gui.mouse_moved(10.0, 10.0);
+    click(&mut gui);
+    gui.begin(&font).text_input("name", field, &mut text);
+    assert!(gui.wants_keyboard());
+    assert!(gui.key_input(VirtualKeyCode::W, ElementState::Pressed));
+    assert!(gui.received_character('w'));
+    assert!(gui.key_input(VirtualKeyCode::Back, ElementState::Pressed));
+    assert!(!gui.key_input(VirtualKeyCode::W, ElementState::Released));
+    assert!(!gui.begin(&font).text_input("name", field, &mut text));
+    assert_eq!(text, "ab");
+
+    gui.received_character('c');
+    gui.key_input(VirtualKeyCode::Return, ElementState::Pressed);
+    assert!(gui.begin(&font).text_input("name", field, &mut text));
+    assert_eq!(text, "abc");
+    assert!(!gui.wants_keyboard());
+}
@@ -0,0 +1,82 @@
+use brickheaven::game::input::{Action, AxisResponse, Binding, GamepadAxis, GamepadSettings, Input, InputMap};
+use serde_json::json;
+use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
+
+#[test]
+fn bindings_round_trip_and_unlisted_actions_keep_their_defaults() {
+    let config = json!({
+        "move_forward": ["I", "Ctrl+Shift+Up", "Gamepad LeftStickY+"],
+        "select": ["Mouse 4"],
+        "quit": [],
+    });
+    let map = InputMap::from_json(&config).unwrap();
+    let forward = map.bindings(Action::MoveForward);
+    assert_eq!(forward[0], Binding::new(Input::Key(VirtualKeyCode::I)));
+    assert_eq!(forward[1].modifiers, ModifiersState::CTRL | ModifiersState::SHIFT);
+    assert_eq!(forward[2].input, Input::GamepadAxis(GamepadAxis::LeftStickY, true));
+    assert_eq!(map.bindings(Action::Select), [Binding::new(Input::Mouse(MouseButton::Other(4)))]);
+    assert!(map.bindings(Action::Quit).is_empty());
+    assert_eq!(map.bindings(Action::MoveBackward), InputMap::default().bindings(Action::MoveBackward));
+
+    let reloaded = InputMap::from_json(&map.to_json()).unwrap();
+    for action in Action::ALL {
+        assert_eq!(reloaded.bindings(action), map.bindings(action));
+    }
+    assert!(InputMap::from_json(&json!({ "fly": ["F"] })).is_err());
+    assert!(InputMap::from_json(&json!({ "quit": ["Hyper+Q"] })).is_err());
+}
+
+#[test]
+fn modifier_combos_override_the_plain_binding_and_axes_are_analog() {
+    let mut map = InputMap::default();
+    map.key_input(VirtualKeyCode::S, ElementState::Pressed);
+    assert!(map.is_down(Action::MoveBackward));
+    assert!(map.take_triggered().contains(&Action::MoveBackward));
+    map.key_input(VirtualKeyCode::S, ElementState::Released);
+
+    map.modifiers_changed(ModifiersState::CTRL);
+    map.key_input(VirtualKeyCode::S, ElementState::Pressed);
+    assert_eq!(map.take_triggered(), [Action::Export]);
+    assert!(!map.is_down(Action::MoveBackward));
+    //  Key repeat doesn't trigger it again
+    map.key_input(VirtualKeyCode::S, ElementState::Pressed);
+    assert!(map.take_triggered().is_empty());
+
+    let linear = AxisResponse { dead_zone: 0.0, curve: 1.0 };
+    map.set_gamepad_settings(GamepadSettings { stick: linear, trigger: linear });
+    map.gamepad_axis(GamepadAxis::LeftStickY, -0.25);
+    assert_eq!(map.value(Action::MoveBackward), 0.25);
+    assert_eq!(map.value(Action::MoveForward), 0.0);
+    assert!(!map.is_down(Action::MoveBackward));
+}
+
+#[test]
+fn rebinding_captures_the_next_input_and_reports_conflicts() {
+    let mut map = InputMap::default();
+    assert!(map.conflicts().is_empty());
+
+    map.start_rebind(Action::Quit, Some(0));
+    map.modifiers_changed(ModifiersState::CTRL);
+    map.key_input(VirtualKeyCode::LControl, ElementState::Pressed);
+    map.key_input(VirtualKeyCode::W, ElementState::Pressed);
+    let rebound = map.take_rebound().unwrap();
+    let ctrl_w = Binding::new(Input::Key(VirtualKeyCode::W)).with_modifiers(ModifiersState::CTRL);
+    assert_eq!(rebound.binding, ctrl_w);
+    assert!(rebound.conflicts.is_empty());
+    assert_eq!(map.bindings(Action::Quit), [ctrl_w]);
+    //  Captured inputs don't trigger anything
+    assert!(map.take_triggered().is_empty());
+
+    map.modifiers_changed(ModifiersState::empty());
+    map.start_rebind(Action::Select, None);
+    map.key_input(VirtualKeyCode::R, ElementState::Pressed);
+    let rebound = map.take_rebound().unwrap();
+    assert_eq!(rebound.conflicts, [Action::RotateBrickCw]);
+    let r = Binding::new(Input::Key(VirtualKeyCode::R));
+    assert_eq!(map.conflicts(), [(r, vec![Action::Select, Action::RotateBrickCw])]);
+
+    map.start_rebind(Action::Select, None);
+    map.key_input(VirtualKeyCode::Escape, ElementState::Pressed);
+    assert_eq!(map.rebinding(), None);
+    assert!(map.take_rebound().is_none());
+}
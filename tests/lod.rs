@@ -0,0 +1,87 @@
+use brickheaven::engine::asset_manager::cube_mesh;
+use brickheaven::engine::bounds::BoundingSphere;
+use brickheaven::engine::lod::{generate_lods, projected_size, Lod, LodSettings, STUD_CAP_MATERIAL};
+use brickheaven::engine::model::MeshData;
+use brickheaven::game::world::STUD_SIZE;
+use cgmath::Point3;
+
+//  A 2x2x2 cube with a small box standing on top of it in place of a stud
+fn studded_brick() -> Vec<MeshData> {
+    let body = cube_mesh("body");
+    let mut stud = cube_mesh("stud");
+    for v in &mut stud.vertices {
+        v.position = [v.position[0] * 0.3, 1.2 + v.position[1] * 0.2, v.position[2] * 0.3];
+    }
+    vec![body, stud]
+}
+
+fn max_y(meshes: &[MeshData]) -> f32 {
+    meshes
+        .iter()
+        .flat_map(|m| m.indices.iter().map(|&i| m.vertices[i as usize].position[1]))
+        .fold(f32::NEG_INFINITY, f32::max)
+}
+
+#[test]
+fn lods_switch_only_once_well_past_a_boundary() {
+    let settings = LodSettings { flat_top_below: 100.0, impostor_below: 20.0, hysteresis: 0.1 };
+    assert_eq!(settings.select(Lod::Full, 95.0), Lod::Full);
+    assert_eq!(settings.select(Lod::Full, 89.0), Lod::FlatTop);
+    assert_eq!(settings.select(Lod::FlatTop, 105.0), Lod::FlatTop);
+    assert_eq!(settings.select(Lod::FlatTop, 111.0), Lod::Full);
+    //  Big jumps skip levels
+    assert_eq!(settings.select(Lod::Full, 5.0), Lod::Impostor);
+    assert_eq!(settings.select(Lod::Impostor, 1000.0), Lod::Full);
+}
+
+#[test]
+fn forced_settings_pin_every_size_to_one_level() {
+    for lod in Lod::ALL {
+        let settings = LodSettings::forced(lod);
+        for current in Lod::ALL {
+            for pixels in [0.0, 1.0, 50.0, 1e6, f32::MAX] {
+                assert_eq!(settings.select(current, pixels), lod);
+            }
+        }
+    }
+}
+
+#[test]
+fn projected_size_halves_with_twice_the_distance() {
+    let sphere = BoundingSphere { center: Point3::new(0.0, 0.0, -10.0), radius: 1.0 };
+    let near = projected_size(&sphere, Point3::new(0.0, 0.0, 0.0), 500.0);
+    let far = projected_size(&sphere, Point3::new(0.0, 0.0, 10.0), 500.0);
+    assert!((near - 100.0).abs() < 1e-3);
+    assert!((far - 50.0).abs() < 1e-3);
+    assert_eq!(projected_size(&sphere, Point3::new(0.0, 0.5, -10.0), 500.0), f32::MAX);
+}
+
+#[test]
+fn flat_top_replaces_the_studs_with_a_cap() {
+    let lods = generate_lods(&studded_brick());
+    assert_eq!(lods.iter().map(|(lod, _)| *lod).collect::<Vec<_>>(), vec![Lod::FlatTop, Lod::Impostor]);
+
+    let flat = &lods[0].1;
+    assert!((max_y(flat) - 1.0).abs() < 1e-5);
+    let cap = flat.iter().find(|m| m.material == STUD_CAP_MATERIAL).unwrap();
+    assert_eq!(cap.indices.len(), 6);
+    assert!(cap.vertices.iter().all(|v| v.position[0].abs() == 1.0 && v.position[2].abs() == 1.0));
+    //  Two studs across each way, one repeat of the stud texture per stud
+    for axis in 0..2 {
+        let uvs = cap.vertices.iter().map(|v| v.tex_coords[axis]);
+        let span = uvs.clone().fold(f32::MIN, f32::max) - uvs.fold(f32::MAX, f32::min);
+        assert!((span - 2.0 / STUD_SIZE).abs() < 1e-5);
+    }
+    //  The body's own top is gone, the cap stands in for it
+    let body = flat.iter().find(|m| m.name.starts_with("body")).unwrap();
+    assert_eq!(body.indices.len(), 5 * 6);
+}
+
+#[test]
+fn bricks_without_studs_only_get_an_impostor() {
+    let lods = generate_lods(&[cube_mesh("plain")]);
+    assert_eq!(lods.len(), 1);
+    let (lod, impostor) = &lods[0];
+    assert_eq!(*lod, Lod::Impostor);
+    assert_eq!(impostor[0].indices.len(), 36);
+}
@@ -0,0 +1,20 @@
+use brickheaven::engine::msaa::{closest_supported, next_sample_count};
+
+#[test]
+fn unsupported_sample_counts_fall_back_to_the_closest_below() {
+    let supported = [1, 4];
+    assert_eq!(closest_supported(&supported, 8), 4);
+    assert_eq!(closest_supported(&supported, 4), 4);
+    assert_eq!(closest_supported(&supported, 2), 1);
+    assert_eq!(closest_supported(&[1], 8), 1);
+    assert_eq!(closest_supported(&[], 4), 1);
+}
+
+#[test]
+fn cycling_sample_counts_wraps_round() {
+    let supported = [1, 2, 4, 8];
+    assert_eq!(next_sample_count(&supported, 1), 2);
+    assert_eq!(next_sample_count(&supported, 4), 8);
+    assert_eq!(next_sample_count(&supported, 8), 1);
+    assert_eq!(next_sample_count(&[1, 4], 2), 4);
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use brickheaven::engine::geometry::NormalGeneration;
+use brickheaven::engine::model::{MeshData, ModelVertex};
+use brickheaven::engine::obj_loading::{build_mesh, material_uniform, parse_obj, ImportError, ImportErrorKind, ImportOptions};
+use brickheaven::engine::resources::ModelData;
+use brickheaven::game::uniform::MaterialUniform;
+
+fn load(file_name: &str, options: &ImportOptions) -> Result<ModelData, ImportError> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = if file_name.starts_with("bricks/") { root.join("res") } else { root.join("tests/fixtures/obj") };
+    let text = std::fs::read_to_string(dir.join(file_name)).unwrap();
+    parse_obj(file_name, &text, options, |p| Ok(std::fs::read_to_string(dir.join(p))?))
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn assert_finite(v: &ModelVertex) {
+    let values = v.position.iter()
+        .chain(&v.tex_coords)
+        .chain(&v.normal)
+        .chain(&v.tangent)
+        .chain(&v.bitangent);
+    for x in values {
+        assert!(x.is_finite(), "non-finite value in {:?}", v);
+    }
+}
+
+fn assert_orthonormal_frames(mesh: &MeshData) {
+    for v in &mesh.vertices {
+        assert_finite(v);
+        assert!((length(v.normal) - 1.0).abs() < 1e-4, "normal not unit length: {:?}", v);
+        assert!((length(v.tangent) - 1.0).abs() < 1e-4, "tangent not unit length: {:?}", v);
+        assert!((length(v.bitangent) - 1.0).abs() < 1e-4, "bitangent not unit length: {:?}", v);
+        assert!(dot(v.normal, v.tangent).abs() < 1e-4, "tangent not orthogonal: {:?}", v);
+        assert!(dot(v.normal, v.bitangent).abs() < 1e-4, "bitangent not orthogonal: {:?}", v);
+        assert!(dot(v.tangent, v.bitangent).abs() < 1e-4, "tangent/bitangent not orthogonal: {:?}", v);
+    }
+}
+
+#[test]
+fn loads_the_base_cube() {
+    let data = load("bricks/cube.obj", &ImportOptions::default()).unwrap();
+    assert_eq!(data.meshes.len(), 1);
+    assert_eq!(data.meshes[0].indices.len(), 36);
+    assert_orthonormal_frames(&data.meshes[0]);
+}
+
+#[test]
+fn generates_flat_normals_when_missing() {
+    let data = load("no_normals.obj", &ImportOptions::default()).unwrap();
+    let mesh = &data.meshes[0];
+    //  Flat shading splits every triangle off into its own vertices
+    assert_eq!(mesh.vertices.len(), mesh.indices.len());
+    for v in &mesh.vertices {
+        assert!((v.normal[2] - 1.0).abs() < 1e-5, "expected +Z normal, got {:?}", v.normal);
+    }
+    assert_orthonormal_frames(mesh);
+}
+
+#[test]
+fn generates_smooth_normals_when_asked() {
+    let options = ImportOptions { normals: NormalGeneration::Smooth };
+    let data = load("positions_only.obj", &options).unwrap();
+    let mesh = &data.meshes[0];
+    //  Smooth shading keeps the shared vertices
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_orthonormal_frames(mesh);
+}
+
+#[test]
+fn projects_uvs_when_missing() {
+    let data = load("no_uvs.obj", &ImportOptions::default()).unwrap();
+    let mesh = &data.meshes[0];
+    for v in &mesh.vertices {
+        assert!((0.0..=1.0).contains(&v.tex_coords[0]) && (0.0..=1.0).contains(&v.tex_coords[1]));
+    }
+    //  The triangle lies flat in XZ so the projection should spread it over both UV axes
+    let us = mesh.vertices.iter().map(|v| v.tex_coords[0]).collect::<Vec<_>>();
+    let vs = mesh.vertices.iter().map(|v| v.tex_coords[1]).collect::<Vec<_>>();
+    assert!(us.iter().cloned().fold(f32::MIN, f32::max) - us.iter().cloned().fold(f32::MAX, f32::min) > 0.99);
+    assert!(vs.iter().cloned().fold(f32::MIN, f32::max) - vs.iter().cloned().fold(f32::MAX, f32::min) > 0.99);
+    assert_orthonormal_frames(mesh);
+}
+
+#[test]
+fn positions_only_gets_everything_generated() {
+    let data = load("positions_only.obj", &ImportOptions::default()).unwrap();
+    assert_orthonormal_frames(&data.meshes[0]);
+}
+
+#[test]
+fn degenerate_triangles_dont_produce_nan() {
+    let data = load("degenerate.obj", &ImportOptions::default()).unwrap();
+    assert_orthonormal_frames(&data.meshes[0]);
+}
+
+#[test]
+fn out_of_range_index_is_an_error() {
+    let err = load("bad_index.obj", &ImportOptions::default()).unwrap_err();
+    assert_eq!(err.file, "bad_index.obj");
+    //  tobj catches it before build_mesh gets a look
+    assert!(matches!(err.kind, ImportErrorKind::Parse(_)), "{:?}", err);
+}
+
+#[test]
+fn build_mesh_rejects_indices_past_its_vertices() {
+    let mesh = tobj::Mesh {
+        positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        indices: vec![0, 1, 5],
+        ..Default::default()
+    };
+    match build_mesh("bad", mesh, &ImportOptions::default()).unwrap_err() {
+        ImportErrorKind::IndexOutOfRange { mesh, index, vertex_count } => {
+            assert_eq!((mesh.as_str(), index, vertex_count), ("bad", 5, 3));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn missing_material_library_names_the_file() {
+    let err = load("missing_mtl.obj", &ImportOptions::default()).unwrap_err();
+    match &err.kind {
+        ImportErrorKind::MaterialLibrary { path, .. } => assert_eq!(path, "does-not-exist.mtl"),
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(err.to_string().contains("missing_mtl.obj"));
+}
+
+#[test]
+fn file_without_faces_is_an_error() {
+    let err = load("empty.obj", &ImportOptions::default()).unwrap_err();
+    assert!(matches!(err.kind, ImportErrorKind::NoGeometry), "{:?}", err);
+}
+
+#[test]
+fn material_uniform_reads_the_mtl_scalars() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/obj/materials.mtl");
+    let text = std::fs::read_to_string(path).unwrap();
+    let (materials, by_name) = tobj::load_mtl_buf(&mut text.as_bytes()).unwrap();
+    let uniform = |name: &str| material_uniform(&materials[by_name[name]]);
+
+    assert_eq!(uniform("glowing"), MaterialUniform {
+        diffuse: [0.8, 0.2, 0.1],
+        opacity: 0.25,
+        specular: [0.5; 3],
+        shininess: 96.0,
+        emissive: [0.1, 0.2, 0.3],
+        _padding: 0,
+    });
+
+    //  A single Ke is a grey, and Ns 0 is clamped so the shader's pow() stays finite
+    let grey = uniform("grey_glow");
+    assert_eq!(grey.emissive, [0.4; 3]);
+    assert_eq!(grey.shininess, 1.0);
+    assert_eq!(grey.opacity, 1.0);
+
+    //  No Kd next to a diffuse map means the map's own colour
+    let textured = uniform("textured");
+    assert_eq!(textured.diffuse, [1.0; 3]);
+    assert_eq!(textured.emissive, [0.0; 3]);
+    assert_eq!(textured.shininess, 10.0);
+}
@@ -0,0 +1,38 @@
+use brickheaven::engine::post_process::{halton, taa_jitter};
+use brickheaven::engine::settings::AntiAliasing;
+
+#[test]
+fn halton_sequence() {
+    let base_2: Vec<f32> = (1..5).map(|i| halton(i, 2)).collect();
+    assert_eq!(base_2, vec![0.5, 0.25, 0.75, 0.125]);
+    let base_3: Vec<f32> = (1..4).map(|i| halton(i, 3)).collect();
+    assert!((base_3[0] - 1.0 / 3.0).abs() < 1e-6);
+    assert!((base_3[1] - 2.0 / 3.0).abs() < 1e-6);
+    assert!((base_3[2] - 1.0 / 9.0).abs() < 1e-6);
+}
+
+#[test]
+fn jitter_stays_within_half_a_pixel() {
+    let (width, height) = (800, 600);
+    let mut seen = Vec::new();
+    for frame in 0..32 {
+        let jitter = taa_jitter(frame, width, height);
+        //  One pixel is 2 / size in NDC
+        assert!(jitter.x.abs() <= 1.0 / width as f32);
+        assert!(jitter.y.abs() <= 1.0 / height as f32);
+        if !seen.contains(&jitter) {
+            seen.push(jitter);
+        }
+    }
+    //  Every frame in a cycle lands somewhere different
+    assert_eq!(seen.len(), 8);
+}
+
+#[test]
+fn anti_aliasing_cycles_back_to_off() {
+    let mut mode = AntiAliasing::Off;
+    for expected in [AntiAliasing::Fxaa, AntiAliasing::Smaa, AntiAliasing::Taa, AntiAliasing::Off] {
+        mode = mode.next();
+        assert_eq!(mode, expected);
+    }
+}
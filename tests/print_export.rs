@@ -0,0 +1,123 @@
+use brickheaven::engine::gltf_export::{BrickType, PlacedBrick};
+use brickheaven::engine::model::{MeshData, ModelVertex};
+use brickheaven::engine::print_export::{bake, BakeOptions};
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+//  A unit cube from 0 to 1, four corners of its own per face like a loaded brick, wound facing out
+fn cube() -> MeshData {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    for axis in 0..3 {
+        for sign in [1.0, -1.0] {
+            let normal = axes[axis] * sign;
+            let u = axes[(axis + 1) % 3];
+            let v = normal.cross(u);
+            let center = Vector3::new(0.5, 0.5, 0.5) + normal * 0.5;
+            let base = vertices.len() as u32;
+            for (a, b) in [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+                vertices.push(ModelVertex {
+                    position: (center + u * a + v * b).into(),
+                    tex_coords: [0.0; 2],
+                    normal: normal.into(),
+                    tangent: u.into(),
+                    bitangent: v.into(),
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    MeshData { name: "cube".into(), vertices, indices, material: 0 }
+}
+
+fn touching_cubes(offset: f32) -> [PlacedBrick; 2] {
+    let brick = |x: f32| PlacedBrick {
+        brick_type: 0,
+        transform: Matrix4::from_translation(Vector3::new(x, 0.0, 0.0)),
+        color: [1.0, 0.0, 0.0, 1.0],
+    };
+    [brick(offset), brick(1.0 + offset)]
+}
+
+#[test]
+fn touching_cubes_weld_into_twelve_corners() {
+    let meshes = [cube()];
+    let brick_types = [BrickType { name: "cube", meshes: &meshes }];
+    let options = BakeOptions { remove_hidden_faces: false, ..Default::default() };
+    let baked = bake(&brick_types, &touching_cubes(0.0), &options).unwrap();
+    assert_eq!(baked.positions.len(), 12);
+    assert_eq!(baked.triangles.len(), 24);
+    assert_eq!(baked.colors.len(), 1);
+}
+
+#[test]
+fn vertices_within_the_weld_distance_merge_across_cell_boundaries() {
+    let meshes = [cube()];
+    let brick_types = [BrickType { name: "cube", meshes: &meshes }];
+    //  The second cube is 2e-5 further along, its near face straddles a 1e-4 cell boundary from the first's far face
+    let mut bricks = touching_cubes(0.00004);
+    bricks[1].transform = Matrix4::from_translation(Vector3::new(1.00006, 0.0, 0.0));
+    let options = BakeOptions { remove_hidden_faces: false, ..Default::default() };
+    let baked = bake(&brick_types, &bricks, &options).unwrap();
+    assert_eq!(baked.positions.len(), 12);
+
+    //  Further apart than the weld distance they stay separate
+    bricks[1].transform = Matrix4::from_translation(Vector3::new(1.0003, 0.0, 0.0));
+    let baked = bake(&brick_types, &bricks, &options).unwrap();
+    assert_eq!(baked.positions.len(), 16);
+}
+
+#[test]
+fn hidden_face_removal_drops_exactly_the_touching_faces() {
+    let meshes = [cube()];
+    let brick_types = [BrickType { name: "cube", meshes: &meshes }];
+    let baked = bake(&brick_types, &touching_cubes(0.0), &BakeOptions::default()).unwrap();
+    //  Two faces of two triangles each
+    assert_eq!(baked.triangles.len(), 20);
+    assert_eq!(baked.triangle_colors.len(), 20);
+    assert_eq!(baked.positions.len(), 12);
+    //  Nothing left lies in the contact plane facing along x
+    for tri in &baked.triangles {
+        let n = baked.triangle_normal(tri);
+        let on_contact = tri.iter().all(|&i| (baked.positions[i as usize][0] - 1.0).abs() < 1e-6);
+        assert!(!(on_contact && n.x.abs() > 0.5), "{:?} faces {:?}", tri, n);
+    }
+    assert!(baked.triangles.iter().all(|tri| baked.triangle_normal(tri).magnitude() > 0.5));
+}
+
+#[test]
+fn a_small_brick_on_a_big_one_bakes_into_a_closed_mesh() {
+    let meshes = [cube()];
+    let brick_types = [BrickType { name: "cube", meshes: &meshes }];
+    let plate = |offset: Vector3<f32>, studs: f32| PlacedBrick {
+        brick_type: 0,
+        transform: Matrix4::from_translation(offset) * Matrix4::from_nonuniform_scale(studs, 0.4, studs),
+        color: [1.0, 0.0, 0.0, 1.0],
+    };
+    //  A 1x1 plate on one corner of a 2x2 one, the contact covers a quarter of the bottom plate's top face
+    let bricks = [plate(Vector3::new(0.0, 0.0, 0.0), 2.0), plate(Vector3::new(0.0, 0.4, 0.0), 1.0)];
+    let baked = bake(&brick_types, &bricks, &BakeOptions::default()).unwrap();
+
+    //  The 2x2 plate is split into 4 + 4 + 4 * 2 cells less the covered one, the 1x1 keeps 5 of its faces
+    assert_eq!(baked.triangles.len(), (15 + 5) * 2);
+    for tri in &baked.triangles {
+        let n = baked.triangle_normal(tri);
+        let inside = tri.iter().all(|&i| {
+            let p = baked.positions[i as usize];
+            (p[1] - 0.4).abs() < 1e-6 && p[0] < 1.0 + 1e-6 && p[2] < 1.0 + 1e-6
+        });
+        assert!(!(inside && n.y.abs() > 0.5), "{:?} is in the contact", tri);
+    }
+
+    //  Every edge is used once each way
+    let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+    for tri in &baked.triangles {
+        for i in 0..3 {
+            let (a, b) = (tri[i], tri[(i + 1) % 3]);
+            *edges.entry((a.min(b), a.max(b))).or_default() += if a < b { 1 } else { -1 };
+        }
+    }
+    assert!(edges.values().all(|&count| count == 0), "open edges: {:?}", edges.iter().filter(|(_, &c)| c != 0).collect::<Vec<_>>());
+}
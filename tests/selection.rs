@@ -0,0 +1,45 @@
+use brickheaven::engine::bounds::{Aabb, Ray};
+use brickheaven::game::instance::InstanceRaw;
+use brickheaven::game::selection::Selection;
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector2, Vector3};
+
+#[test]
+fn selection_flags_follow_selection_and_hover() {
+    let mut selection = Selection::default();
+    assert!(selection.is_empty());
+    selection.toggle(3);
+    selection.set_hovered(Some(5));
+    assert_eq!(selection.flags(3), InstanceRaw::SELECTED);
+    assert_eq!(selection.flags(5), InstanceRaw::HOVERED);
+    assert_eq!(selection.flags(4), 0);
+    assert!(selection.take_changed());
+    assert!(!selection.take_changed());
+
+    //  Hovering the same brick again isn't a change
+    selection.set_hovered(Some(5));
+    assert!(!selection.take_changed());
+    selection.toggle(3);
+    selection.set_hovered(None);
+    assert!(selection.is_empty());
+    assert!(selection.take_changed());
+}
+
+#[test]
+fn rays_hit_the_near_side_of_boxes() {
+    let aabb = Aabb { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) };
+    let ray = Ray { origin: Point3::new(0.0, 0.0, 5.0), direction: Vector3::new(0.0, 0.0, -1.0) };
+    assert_eq!(aabb.intersect_ray(&ray), Some(4.0));
+    let miss = Ray { origin: Point3::new(2.0, 0.0, 5.0), ..ray };
+    assert_eq!(aabb.intersect_ray(&miss), None);
+    let behind = Ray { direction: Vector3::new(0.0, 0.0, 1.0), ..ray };
+    assert_eq!(aabb.intersect_ray(&behind), None);
+    let inside = Ray { origin: Point3::new(0.0, 0.0, 0.0), ..ray };
+    assert_eq!(aabb.intersect_ray(&inside), Some(0.0));
+}
+
+#[test]
+fn screen_rays_start_on_the_near_plane() {
+    let ray = Ray::from_ndc(&Matrix4::identity(), Vector2::new(0.5, -0.5));
+    assert_eq!(ray.origin, Point3::new(0.5, -0.5, 0.0));
+    assert_eq!(ray.direction, Vector3::new(0.0, 0.0, 1.0));
+}
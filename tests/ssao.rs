@@ -0,0 +1,22 @@
+use brickheaven::engine::ssao::{hemisphere_kernel, MAX_SAMPLES};
+
+#[test]
+fn kernel_stays_in_the_hemisphere() {
+    let kernel = hemisphere_kernel(MAX_SAMPLES);
+    assert_eq!(kernel.len(), MAX_SAMPLES as usize);
+    for [x, y, z, _] in &kernel {
+        let length = (x * x + y * y + z * z).sqrt();
+        assert!(*z > 0.0);
+        assert!(length <= 1.0 + 1e-5);
+        assert!(length >= 0.1 - 1e-5);
+    }
+}
+
+#[test]
+fn kernel_reaches_further_with_each_sample() {
+    let lengths: Vec<f32> = hemisphere_kernel(16)
+        .iter()
+        .map(|[x, y, z, _]| (x * x + y * y + z * z).sqrt())
+        .collect();
+    assert!(lengths.windows(2).all(|pair| pair[0] < pair[1]));
+}
@@ -0,0 +1,84 @@
+use brickheaven::engine::font::{Font, GlyphBitmap};
+use brickheaven::engine::text::{layout, GlyphAtlas, GlyphKey};
+
+fn font() -> Font {
+    Font::from_bytes(std::fs::read("res/fonts/DejaVuSans.ttf").unwrap()).unwrap()
+}
+
+#[test]
+fn fonts_map_characters_and_kern_pairs() {
+    let font = font();
+    let (a, v) = (font.glyph_index('A'), font.glyph_index('V'));
+    assert_ne!(a, 0);
+    assert_ne!(font.glyph_index('é'), 0);
+    assert!(font.kerning(a, v, 32.0) < 0.0);
+    assert_eq!(font.kerning(font.glyph_index('x'), font.glyph_index('x'), 32.0), 0.0);
+
+    //  An 'o' is filled around the edge and empty in the middle
+    let o = font.rasterize(font.glyph_index('o'), 32.0);
+    let at = |x: u32, y: u32| o.coverage[(y * o.width + x) as usize];
+    assert!(o.top < 0 && o.height > 10);
+    assert_eq!(at(o.width / 2, o.height / 2), 0);
+    assert!(at(o.width / 2, 0) > 128 || at(o.width / 2, 1) > 128);
+
+    assert!(Font::from_bytes(b"not a font".to_vec()).is_err());
+    assert_eq!(font.rasterize(font.glyph_index(' '), 32.0).width, 0);
+}
+
+#[test]
+fn cff_fonts_rasterize_and_kern_from_gpos() {
+    //  CFF outlines and GPOS kerning only, there's no glyf or kern table. ◯ pulls ☼ all the way back over itself.
+    let font = Font::from_bytes(std::fs::read("tests/fixtures/fonts/TestGPOSTwo.otf").unwrap()).unwrap();
+    let (circle, sun, space) = (font.glyph_index('◯'), font.glyph_index('☼'), font.glyph_index(' '));
+    assert_ne!(circle, 0);
+    assert_eq!(font.advance(circle, 32.0), 25.6);
+    assert_eq!(font.kerning(circle, sun, 32.0), -25.6);
+    assert_eq!(font.kerning(circle, space, 32.0), 1.6);
+    assert_eq!(font.kerning(sun, circle, 32.0), 0.0);
+
+    let o = font.rasterize(circle, 32.0);
+    let at = |x: u32, y: u32| o.coverage[(y * o.width + x) as usize];
+    assert!(o.top < 0 && o.height > 10);
+    assert_eq!(at(o.width / 2, o.height / 2), 0);
+    assert!(at(o.width / 2, 0) > 128 || at(o.width / 2, 1) > 128);
+}
+
+#[test]
+fn layout_wraps_between_words() {
+    let font = font();
+    let one_line = layout(&font, "brick heaven", 20.0, None);
+    assert_eq!(one_line.lines, 1);
+    assert_eq!(one_line.glyphs.len(), 11);
+
+    //  Too narrow for both words, but wide enough for either
+    let wrapped = layout(&font, "brick heaven", 20.0, Some(one_line.width * 0.7));
+    assert_eq!(wrapped.lines, 2);
+    assert!(wrapped.width <= one_line.width * 0.7);
+    let h = wrapped.glyphs[5];
+    assert_eq!(h.x, 0.0);
+    assert!(h.y > wrapped.glyphs[0].y);
+
+    //  Newlines always break, and a word wider than the line is split
+    assert_eq!(layout(&font, "a\nb", 20.0, None).lines, 2);
+    let split = layout(&font, "mmmmmmmm", 20.0, Some(40.0));
+    assert!(split.lines > 1 && split.width <= 40.0);
+}
+
+#[test]
+fn atlas_packs_glyphs_until_full() {
+    let mut atlas = GlyphAtlas::new(32, 32);
+    let bitmap = GlyphBitmap { width: 15, height: 15, left: 0, top: -15, coverage: vec![255; 15 * 15] };
+    let key = |glyph| GlyphKey { font: 0, glyph, size: 16 };
+    let placed = (0..4).map(|glyph| atlas.insert(key(glyph), &bitmap).unwrap()).collect::<Vec<_>>();
+    assert_eq!((placed[1].x, placed[1].y), (16, 0));
+    assert_eq!((placed[2].x, placed[2].y), (0, 16));
+    assert_eq!(atlas.pixels()[16 * 32 + 16], 255);
+    assert_eq!(atlas.pixels()[15], 0);
+    assert!(atlas.insert(key(4), &bitmap).is_none());
+    assert_eq!(atlas.get(&key(3)), Some(placed[3]));
+
+    assert!(atlas.take_dirty());
+    atlas.clear();
+    assert!(atlas.is_empty());
+    assert!(atlas.insert(key(4), &bitmap).is_some());
+}
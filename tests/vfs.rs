@@ -0,0 +1,125 @@
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use brickheaven::engine::vfs::{self, DirectoryLayer, Layer, PackLayer, Vfs, ZipLayer, BASE_PRIORITY};
+
+//  Somewhere to write packs that no other test is using
+fn scratch_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("brickheaven-vfs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn pack(name: &str, files: &[(&str, &str)]) -> PackLayer {
+    let files = files.iter().map(|(path, contents)| (path.to_string(), contents.as_bytes().to_vec())).collect::<Vec<_>>();
+    let path = scratch_file(name);
+    vfs::write_pack(&path, &files).unwrap();
+    let layer = PackLayer::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    layer
+}
+
+#[test]
+fn pack_round_trips_its_files() {
+    let files = vec![
+        ("bricks/cube.obj".to_string(), b"v 0 0 0".to_vec()),
+        ("./textures\\stud.png".to_string(), vec![0, 1, 2, 255]),
+        ("empty.txt".to_string(), Vec::new()),
+    ];
+    let path = scratch_file("round_trip.bhpack");
+    vfs::write_pack(&path, &files).unwrap();
+    assert_eq!(&std::fs::read(&path).unwrap()[..4], vfs::PACK_MAGIC);
+
+    let mut vfs = Vfs::new();
+    vfs.mount_path(&path, BASE_PRIORITY).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(vfs.read("bricks/cube.obj").unwrap(), b"v 0 0 0");
+    //  Names are normalised on the way in
+    assert_eq!(vfs.read("textures/stud.png").unwrap(), vec![0, 1, 2, 255]);
+    assert!(vfs.read("empty.txt").unwrap().is_empty());
+    assert!(vfs.read("missing.txt").is_err());
+
+    //  A pack that lies about where its data is gets rejected
+    let mut bytes = b"BHPK".to_vec();
+    bytes.extend_from_slice(&vfs::PACK_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.push(b'a');
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    bytes.extend_from_slice(&1000u64.to_le_bytes());
+    assert!(PackLayer::from_bytes(bytes).is_err());
+}
+
+#[test]
+fn higher_priority_layers_win() {
+    let mut vfs = Vfs::new();
+    vfs.mount("mod", 10, pack("mod.bhpack", &[("bricks/cube.obj", "mod")]));
+    vfs.mount("base", BASE_PRIORITY, pack("base.bhpack", &[("bricks/cube.obj", "base"), ("bricks/plate.obj", "base")]));
+    assert_eq!(vfs.read_to_string("bricks/cube.obj").unwrap(), "mod");
+    //  Anything the mod doesn't have still comes from below
+    assert_eq!(vfs.read_to_string("bricks/plate.obj").unwrap(), "base");
+    assert_eq!(vfs.mounts().map(|m| m.name.as_str()).collect::<Vec<_>>(), ["mod", "base"]);
+
+    //  Equal priority goes to whatever was mounted last
+    vfs.mount("patch", 10, pack("patch.bhpack", &[("bricks/cube.obj", "patch")]));
+    assert_eq!(vfs.read_to_string("bricks/cube.obj").unwrap(), "patch");
+
+    assert!(vfs.unmount("patch"));
+    assert!(vfs.unmount("mod"));
+    assert!(!vfs.unmount("mod"));
+    assert_eq!(vfs.read_to_string("bricks/cube.obj").unwrap(), "base");
+}
+
+#[test]
+fn list_bricks_merges_layers() {
+    let base = pack("list_base.bhpack", &[
+        ("bricks/cube.obj", ""),
+        ("bricks/cube.mtl", ""),
+        ("bricks/slope.GLB", ""),
+        ("textures/stud.png", ""),
+    ]);
+    let overlay = pack("list_overlay.bhpack", &[("bricks/cube.obj", ""), ("bricks/round/cone.gltf", "")]);
+    assert!(overlay.contains("bricks/cube.obj"));
+
+    let mut vfs = Vfs::new();
+    vfs.mount("base", BASE_PRIORITY, base);
+    vfs.mount("overlay", 1, overlay);
+    assert_eq!(vfs.list_bricks(), ["bricks/cube.obj", "bricks/round/cone.gltf", "bricks/slope.GLB"]);
+    assert_eq!(vfs.list("textures"), ["textures/stud.png"]);
+}
+
+#[test]
+fn zip_layers_override_directories() {
+    let dir = scratch_file("zip_base");
+    for (path, contents) in [("bricks/cube.obj", "base"), ("bricks/plate.obj", "base"), ("readme.txt", "base")] {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    //  Stored and deflated entries, a directory entry and a name that needs normalising
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.add_directory("bricks/", stored).unwrap();
+    zip.start_file("bricks/cube.obj", deflated).unwrap();
+    zip.write_all(b"zip").unwrap();
+    zip.start_file("./bricks\\wedge.obj", stored).unwrap();
+    zip.write_all(b"zip").unwrap();
+    let data = zip.finish().unwrap().into_inner();
+    let layer = ZipLayer::from_bytes(data).unwrap();
+    assert!(layer.contains("bricks/wedge.obj"));
+    assert!(!layer.contains("bricks"));
+
+    let mut vfs = Vfs::new();
+    vfs.mount("base", BASE_PRIORITY, DirectoryLayer::new(&dir));
+    vfs.mount("mod", 1, layer);
+    assert_eq!(vfs.read_to_string("bricks/cube.obj").unwrap(), "zip");
+    assert_eq!(vfs.read_to_string("bricks/wedge.obj").unwrap(), "zip");
+    assert_eq!(vfs.read_to_string("bricks/plate.obj").unwrap(), "base");
+    assert_eq!(vfs.list_bricks(), ["bricks/cube.obj", "bricks/plate.obj", "bricks/wedge.obj"]);
+
+    assert!(vfs.unmount("mod"));
+    assert_eq!(vfs.read_to_string("bricks/cube.obj").unwrap(), "base");
+    std::fs::remove_dir_all(&dir).unwrap();
+}